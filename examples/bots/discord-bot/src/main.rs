@@ -98,7 +98,7 @@ async fn check(
 
     let discord_status = accounts
         .iter()
-        .find(|acc| acc.platform == Platform::Discord)
+        .find(|acc| acc.platform == Platform::DISCORD)
         .map(|acc| acc.cred_status);
 
     let message = match discord_status {
@@ -107,9 +107,12 @@ async fn check(
             if let Some(full_name) = verification.full_name {
                 message.push_str(&format!("\n- Real name: {full_name}"));
             }
+            for claim in &verification.claims {
+                message.push_str(&format!("\n- {}", claim.description));
+            }
             for account in accounts
                 .into_iter()
-                .filter(|acc| acc.platform != Platform::Discord)
+                .filter(|acc| acc.platform != Platform::DISCORD)
             {
                 message.push_str("\n- ");
                 match account.cred_status {
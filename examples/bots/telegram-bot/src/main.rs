@@ -192,7 +192,7 @@ async fn check_user(
 
             let telegram_status = accounts
                 .iter()
-                .find(|acc| acc.platform == Platform::Telegram)
+                .find(|acc| acc.platform == Platform::TELEGRAM)
                 .map(|acc| acc.cred_status);
 
             let message = match telegram_status {
@@ -202,9 +202,12 @@ async fn check_user(
                         let full_name = markdown::escape(&full_name.to_string());
                         message.push_str(&format!("\n• Real name: {full_name}"));
                     }
+                    for claim in &verification.claims {
+                        message.push_str(&format!("\n• {}", markdown::escape(&claim.description)));
+                    }
                     for account in accounts
                         .into_iter()
-                        .filter(|acc| acc.platform != Platform::Telegram)
+                        .filter(|acc| acc.platform != Platform::TELEGRAM)
                     {
                         message.push_str("\n• ");
                         match account.cred_status {
@@ -1,6 +1,5 @@
 import { DiscordLoginButton } from 'react-social-login-buttons';
 import { nanoid } from 'nanoid';
-import { DiscordConfig } from '../types';
 import { requestCredential } from 'shared/util';
 import { Platform } from 'shared/types';
 import { useContext, useEffect } from 'react';
@@ -16,7 +15,6 @@ type DiscordWindowMessage =
   | { type: 'error'; error: string; state: string | null };
 
 const ISSUER_URL = location.href;
-const { discordClientId } = config as DiscordConfig;
 
 let oAuth2State: string | undefined;
 
@@ -25,16 +23,9 @@ let oAuthWindow: Window | null;
 function openDiscordVerification() {
   oAuth2State = nanoid();
 
-  const params = new URLSearchParams({
-    client_id: discordClientId,
-    redirect_uri: ISSUER_URL + 'discord-oauth2',
-    response_type: 'code',
-    scope: 'identify',
-    state: oAuth2State,
-  });
-
-  const oAuth2URL =
-    'https://discord.com/api/oauth2/authorize?' + params.toString();
+  // The issuer binds the state to the session before redirecting to Discord.
+  const params = new URLSearchParams({ state: oAuth2State });
+  const oAuth2URL = ISSUER_URL + 'discord-login?' + params.toString();
 
   const width = window.innerWidth / 2;
   const height = window.innerHeight / 2;
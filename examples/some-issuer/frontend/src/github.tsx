@@ -0,0 +1,23 @@
+import React from 'react';
+import ReactDOM from 'react-dom/client';
+
+import App from './github/App';
+import githubLogo from 'assets/github-logo.svg';
+import './scss/github.scss';
+import { Platform } from 'shared/types';
+import Layout from 'shared/Layout';
+
+if (config.type !== Platform.Github) {
+  throw new Error('Expected github config');
+}
+
+ReactDOM.createRoot(document.getElementById('root')!).render(
+  <React.StrictMode>
+    <Layout
+      platform="GitHub"
+      logo={<img src={githubLogo} alt="GitHub logo" />}
+    >
+      <App />
+    </Layout>
+  </React.StrictMode>,
+);
@@ -0,0 +1,90 @@
+import { GithubLoginButton } from 'react-social-login-buttons';
+import { nanoid } from 'nanoid';
+import { requestCredential } from 'shared/util';
+import { Platform } from 'shared/types';
+import { useContext, useEffect } from 'react';
+import { appState } from 'shared/app-state';
+
+type GithubWindowMessage =
+  | {
+      type: 'success';
+      userId: string;
+      username: string;
+      state: string | null;
+    }
+  | { type: 'error'; error: string; state: string | null };
+
+const ISSUER_URL = location.href;
+
+let oAuth2State: string | undefined;
+
+let oAuthWindow: Window | null;
+
+function openGithubVerification() {
+  oAuth2State = nanoid();
+
+  // The issuer binds the state to the session before redirecting to GitHub.
+  const params = new URLSearchParams({ state: oAuth2State });
+  const oAuth2URL = ISSUER_URL + 'github-login?' + params.toString();
+
+  const width = window.innerWidth / 2;
+  const height = window.innerHeight / 2;
+  const left = window.screenX + width / 2;
+  const top = window.screenY + height / 2;
+
+  oAuthWindow = window.open(
+    oAuth2URL,
+    undefined,
+    `popup,width=${width},height=${height},left=${left},top=${top}`,
+  );
+}
+
+function App() {
+  const { onTransactionFinalized, onTransactionSubmit } = useContext(appState);
+  // When GitHub authentication happens, a window is opened
+  // that sends a 'message' event back with the user id and username
+  useEffect(() => {
+    const onGithubWindowMessage = async (event: MessageEvent) => {
+      // Check that the message is coming from the window we just created.
+      if (!oAuthWindow || event.source !== oAuthWindow) return;
+      if (event.origin + '/' !== ISSUER_URL) return;
+      const data = event.data as GithubWindowMessage;
+      // Prevents CSRF attacks,
+      // see https://auth0.com/docs/secure/attack-protection/state-parameters
+      if (data.state !== oAuth2State) return;
+
+      if (data.type === 'error') {
+        // Do nothing for now.
+        // At some point we can handle different error types.
+      } else if (data.type === 'success') {
+        await requestCredential(
+          {
+            platform: Platform.Github,
+            user: { id: data.userId, username: data.username },
+          },
+          onTransactionSubmit,
+          onTransactionFinalized,
+        );
+      }
+    };
+
+    const eventHandler = (event: MessageEvent) => {
+      onGithubWindowMessage(event).catch((error) => {
+        alert(`An error occured: ${(error as Error).message ?? error}`);
+      });
+    };
+
+    addEventListener('message', eventHandler);
+    return () => removeEventListener('message', eventHandler);
+  }, [onTransactionFinalized, onTransactionSubmit]);
+
+  return (
+    <GithubLoginButton
+      className="app__login"
+      size="40px"
+      onClick={openGithubVerification}
+    />
+  );
+}
+
+export default App;
@@ -0,0 +1,7 @@
+import { CommonConfig, Platform } from '../shared/types';
+
+export interface GithubConfig extends CommonConfig {
+  type: Platform.Github;
+  githubClientId: string;
+  githubOauthUrl: string;
+}
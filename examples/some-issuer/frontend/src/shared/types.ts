@@ -1,6 +1,7 @@
 export enum Platform {
   Telegram = 'telegram',
   Discord = 'discord',
+  Github = 'github',
 }
 
 export interface CommonConfig {
@@ -41,6 +41,16 @@ interface DiscordRequest {
   user: DiscordUser;
 }
 
+interface GithubUser {
+  id: string;
+  username: string;
+}
+
+interface GithubRequest {
+  platform: Platform.Github;
+  user: GithubUser;
+}
+
 interface RpcError {
   code: string;
 }
@@ -49,7 +59,7 @@ const CONTRACT_DID = `$did:ccd:${config.network}:sci:${config.contract.index}:${
 const ISSUER_URL = location.href;
 
 export async function requestCredential(
-  req: TelegramRequest | DiscordRequest,
+  req: TelegramRequest | DiscordRequest | GithubRequest,
   onSubmit: (txHash: string) => void,
   onFinalized: () => void,
 ) {
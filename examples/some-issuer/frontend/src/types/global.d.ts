@@ -1,7 +1,8 @@
 import { DiscordConfig } from '../discord/types';
 import { TelegramConfig } from '../telegram/types';
+import { GithubConfig } from '../github/types';
 
-export type Config = DiscordConfig | TelegramConfig;
+export type Config = DiscordConfig | TelegramConfig | GithubConfig;
 
 declare global {
   declare const config: Config;
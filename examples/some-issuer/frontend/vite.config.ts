@@ -5,6 +5,7 @@ import { Config } from './src/types/global';
 import { Platform } from './src/shared/types';
 import { TelegramConfig } from './src/telegram/types';
 import { DiscordConfig } from './src/discord/types';
+import { GithubConfig } from './src/github/types';
 
 interface PlatformConfig {
   port: number;
@@ -17,7 +18,11 @@ interface HtmlPlaceholders {
   config: Config;
 }
 
-const SUPPORTED_PLATFORMS = [Platform.Discord, Platform.Telegram];
+const SUPPORTED_PLATFORMS = [
+  Platform.Discord,
+  Platform.Telegram,
+  Platform.Github,
+];
 const DEFAULT_NETWORK = 'testnet';
 
 const platform: Platform = process.env.VITE_SOME_ISSUER_PLATFORM as Platform;
@@ -49,6 +54,14 @@ const discordConfig: DiscordConfig = {
   contract: parseContractAddress(process.env.DISCORD_ISSUER_REGISTRY_ADDRESS),
 };
 
+const githubConfig: GithubConfig = {
+  type: Platform.Github,
+  network: process.env.GITHUB_ISSUER_NETWORK ?? DEFAULT_NETWORK,
+  githubClientId: process.env.GITHUB_CLIENT_ID!,
+  githubOauthUrl: process.env.GITHUB_ISSUER_OAUTH_URL ?? 'https://github.com/',
+  contract: parseContractAddress(process.env.GITHUB_ISSUER_REGISTRY_ADDRESS),
+};
+
 const configs: { [p in Platform]: PlatformConfig } = {
   [Platform.Telegram]: {
     // If not served on default port (80), telegram login doesn't work due to iframe restrictions
@@ -61,6 +74,11 @@ const configs: { [p in Platform]: PlatformConfig } = {
     title: 'Discord Web3 ID issuer',
     config: discordConfig,
   },
+  [Platform.Github]: {
+    port: 8082,
+    title: 'GitHub Web3 ID issuer',
+    config: githubConfig,
+  },
 };
 
 const { port, config, title } = configs[platform];
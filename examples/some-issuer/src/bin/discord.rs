@@ -1,13 +1,14 @@
 use anyhow::Context;
 use axum::{
-    extract::{Query, State},
-    response::Html,
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
+    response::{Html, Redirect, Response},
     routing::{get, post},
     Json, Router,
 };
 use axum_prometheus::PrometheusMetricLayerBuilder;
 use axum_sessions::{
-    async_session::CookieStore,
+    async_session::{CookieStore, SessionStore},
     extractors::{ReadableSession, WritableSession},
     SessionLayer,
 };
@@ -26,12 +27,19 @@ use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use some_issuer::{
-    configure_endpoint, send_tx, start_services, IssueChannelData, IssuerWorker, SyncState,
+    configure_endpoint, initial_nonce, refresh_params, send_tx, start_services, CryptoParams,
+    FileSessionStore, IssuerConfig, IssuerHandle, IssuerWorker, ParamsReceiver, PlatformUser,
+    PostgresSessionStore, RateLimitConfig, RateLimiter, SessionBackend, SocialMediaPolicy,
 };
 use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
 const DISCORD_API_ENDPOINT: &str = "https://discord.com/api/v10";
+const DISCORD_AUTHORIZE_URL: &str = "https://discord.com/api/oauth2/authorize";
+/// The session key of the OAuth2 `state` of the flow started in the session.
+const OAUTH_STATE_KEY: &str = "discord_oauth_state";
+/// The minimum length of the session secret required by `axum_sessions`.
+const MIN_SESSION_SECRET_LEN: usize = 64;
 const HTML_TITLE: &str = "Discord Web3 ID issuer";
 const OAUTH_TEMPLATE: &str = include_str!("../../templates/discord-oauth.hbs");
 
@@ -135,30 +143,116 @@ struct App {
     )]
     frontend_assets: std::path::PathBuf,
     #[clap(
-        long = "rate-limit-capacity",
-        help = "The number of issued credentials that we remember for rate limiting.",
-        default_value = "50000",
-        env = "DISCORD_ISSUER_RATE_LIMIT_CAPACITY"
+        long = "rate-limit-window",
+        help = "The length in seconds of the sliding window in which credentials count towards \
+                the rate limits.",
+        default_value = "86400",
+        env = "DISCORD_ISSUER_RATE_LIMIT_WINDOW"
     )]
-    rate_limit_queue_capacity: usize,
+    rate_limit_window: u64,
     #[clap(
-        long = "rate-limit-repeats",
-        help = "The number of times the same user id can be issued before being rate limited.",
+        long = "rate-limit-per-user",
+        help = "The number of credentials the same user id can be issued within the window.",
         default_value = "5",
-        env = "DISCORD_ISSUER_RATE_LIMIT_REPEATS"
+        env = "DISCORD_ISSUER_RATE_LIMIT_PER_USER"
     )]
-    rate_limit_max_repeats: usize,
+    rate_limit_per_user: u32,
+    #[clap(
+        long = "rate-limit-per-ip",
+        help = "The number of credentials the same client IP can be issued within the window.",
+        default_value = "20",
+        env = "DISCORD_ISSUER_RATE_LIMIT_PER_IP"
+    )]
+    rate_limit_per_ip: u32,
+    #[clap(
+        long = "rate-limit-store",
+        help = "Path to an SQLite database in which issued credentials are recorded, so that the \
+                rate limits survive restarts. If not given they are only kept in memory.",
+        env = "DISCORD_ISSUER_RATE_LIMIT_STORE"
+    )]
+    rate_limit_store: Option<PathBuf>,
+    #[clap(
+        long = "client-ip-header",
+        help = "Header set by a trusted reverse proxy with the address of the client, e.g., \
+                x-forwarded-for. The last address in the header is used. If not given, the peer \
+                address of the connection is used.",
+        env = "DISCORD_ISSUER_CLIENT_IP_HEADER"
+    )]
+    client_ip_header: Option<http::HeaderName>,
+    #[clap(
+        long = "session-secret",
+        help = "Hex encoded secret of at least 64 bytes used to sign session cookies. All \
+                replicas must use the same secret. If not given a random secret is generated, \
+                and sessions do not survive a restart.",
+        env = "DISCORD_ISSUER_SESSION_SECRET"
+    )]
+    session_secret: Option<SessionSecret>,
+    #[clap(
+        long = "session-ttl",
+        help = "The number of seconds a session is valid for.",
+        default_value = "600",
+        env = "DISCORD_ISSUER_SESSION_TTL"
+    )]
+    session_ttl: u64,
+    #[clap(
+        long = "session-store-dir",
+        help = "Directory in which sessions are stored as files. It can be shared by replicas \
+                on a common volume. If neither this nor --session-db is given the session data \
+                is kept in the cookie.",
+        env = "DISCORD_ISSUER_SESSION_STORE_DIR",
+        conflicts_with = "session_db"
+    )]
+    session_store_dir: Option<PathBuf>,
+    #[clap(
+        long = "session-db",
+        help = "Connection string of a Postgres database in which sessions are stored.",
+        env = "DISCORD_ISSUER_SESSION_DB"
+    )]
+    session_db: Option<tokio_postgres::Config>,
     #[clap(
         long = "prometheus-address",
         help = "If set, a /metrics endpoint will be available on the address.",
         env = "DISCORD_ISSUER_PROMETHEUS_ADDRESS"
     )]
     prometheus_address: Option<std::net::SocketAddr>,
+    #[clap(
+        long = "params-refresh-interval",
+        help = "Interval (in seconds) at which to check whether the cryptographic parameters \
+                have changed.",
+        default_value = "60",
+        env = "DISCORD_ISSUER_PARAMS_REFRESH_INTERVAL"
+    )]
+    params_refresh_interval: u64,
+}
+
+/// The secret used to sign session cookies.
+#[derive(Clone)]
+struct SessionSecret(Vec<u8>);
+
+impl std::str::FromStr for SessionSecret {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let secret = hex::decode(s).context("Session secret must be hex encoded.")?;
+        anyhow::ensure!(
+            secret.len() >= MIN_SESSION_SECRET_LEN,
+            "Session secret must be at least {MIN_SESSION_SECRET_LEN} bytes."
+        );
+        Ok(Self(secret))
+    }
+}
+
+impl std::fmt::Debug for SessionSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionSecret(..)")
+    }
 }
 
 #[derive(Clone)]
 struct AppState {
-    issuer_sender: tokio::sync::mpsc::Sender<IssueChannelData>,
+    issuer: IssuerHandle<PlatformUser>,
+    rate_limiter: RateLimiter,
+    crypto_params: ParamsReceiver,
     discord_client_id: Arc<str>,
     discord_client_secret: Arc<str>,
     http_client: reqwest::Client,
@@ -166,6 +260,7 @@ struct AppState {
     discord_redirect_uri: Arc<Url>,
     dapp_domain: Arc<Url>,
     verifier_dapp_domain: Arc<String>,
+    session_store: SessionBackend,
 }
 
 /// Request for issuance of Discord credential.
@@ -201,6 +296,14 @@ struct AccessTokenResponse {
 struct Oauth2RedirectParams {
     pub(crate) code: Option<String>,
     pub(crate) error: Option<String>,
+    pub(crate) state: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Oauth2StartParams {
+    /// The OAuth2 `state` generated by the frontend, which it checks when the
+    /// redirect page reports back.
+    state: String,
 }
 
 #[derive(Serialize)]
@@ -244,13 +347,54 @@ struct FrontendConfig {
     contract: ContractConfig,
 }
 
-/// Handles OAuth2 redirects and inserts an id in the session.
+/// Starts the OAuth2 flow by binding the `state` parameter to the session and
+/// redirecting to Discord.
+#[tracing::instrument(level = "debug", skip(state, session))]
+async fn start_oauth(
+    State(state): State<AppState>,
+    Query(params): Query<Oauth2StartParams>,
+    mut session: WritableSession,
+) -> Result<Redirect, StatusCode> {
+    if params.state.is_empty() || params.state.len() > 128 {
+        tracing::warn!("Invalid OAuth2 state.");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    session
+        .insert(OAUTH_STATE_KEY, &params.state)
+        .map_err(|e| {
+            tracing::warn!("Cannot serialize OAuth2 state: {e}.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut url = Url::parse(DISCORD_AUTHORIZE_URL).map_err(|e| {
+        tracing::error!("Invalid Discord authorization URL: {e}.");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    url.query_pairs_mut()
+        .append_pair("client_id", &state.discord_client_id)
+        .append_pair("redirect_uri", state.discord_redirect_uri.as_str())
+        .append_pair("response_type", "code")
+        .append_pair("scope", "identify")
+        .append_pair("state", &params.state);
+    Ok(Redirect::to(url.as_str()))
+}
+
+/// Handles OAuth2 redirects and inserts an id in the session. The `state`
+/// parameter must be the one the flow was started with in this session.
 #[tracing::instrument(level = "debug", skip(state, session))]
 async fn handle_oauth_redirect(
     State(state): State<AppState>,
     Query(params): Query<Oauth2RedirectParams>,
-    session: WritableSession,
+    mut session: WritableSession,
 ) -> Result<Html<String>, StatusCode> {
+    // The state can only be used once.
+    let expected_state: Option<String> = session.get(OAUTH_STATE_KEY);
+    session.remove(OAUTH_STATE_KEY);
+    if expected_state.is_none() || expected_state != params.state {
+        tracing::warn!("OAuth2 state does not match the session.");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     if let Some(code) = params.code {
         match state.make_oauth_redirect_response(code, session).await {
             Ok(response) => Ok(Html(response)),
@@ -280,9 +424,11 @@ async fn handle_oauth_redirect(
 #[tracing::instrument(level = "debug", skip_all, fields(holder_id = %request.credential.holder_id))]
 async fn issue_discord_credential(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     session: ReadableSession,
     Json(request): Json<DiscordIssueRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Response, StatusCode> {
     tracing::debug!("Issuing Discord credential.");
     let user_id = match session.get("discord_id") {
         Some(id) => id,
@@ -300,7 +446,16 @@ async fn issue_discord_credential(
         }
     };
 
-    send_tx(request.credential, user_id, username, &state.issuer_sender).await
+    let client_ip = state.rate_limiter.client_ip(peer, &headers);
+    Ok(send_tx(
+        request.credential,
+        user_id,
+        username,
+        client_ip,
+        &state.issuer,
+        &state.rate_limiter,
+    )
+    .await)
 }
 
 impl AppState {
@@ -363,6 +518,16 @@ impl AppState {
             .await
             .context("Error getting Discord user.")?;
 
+        // Give the authenticated session a new id, so that an id planted in
+        // the browser before the login cannot be used to act as the user. The
+        // session under the old id, which still holds the OAuth2 state, is
+        // deleted.
+        self.session_store
+            .destroy_session(session.clone())
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to delete the unauthenticated session: {e}"))?;
+        session.regenerate();
+
         // Discord added the option to get unique usernames. If the discriminator is
         // "0", it indicates that the user has a unique username.
         let username = if user.discriminator == "0" {
@@ -394,14 +559,17 @@ impl AppState {
 }
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 struct Health {
     version: &'static str,
+    crypto_params_hash: String,
 }
 
-#[tracing::instrument(level = "info")]
-async fn health() -> Json<Health> {
+#[tracing::instrument(level = "info", skip_all)]
+async fn health(State(state): State<AppState>) -> Json<Health> {
     Json(Health {
         version: env!("CARGO_PKG_VERSION"),
+        crypto_params_hash: state.crypto_params.borrow().hash.clone(),
     })
 }
 
@@ -414,6 +582,7 @@ async fn main() -> anyhow::Result<()> {
         let log_filter = tracing_subscriber::filter::Targets::new()
             .with_target(module_path!(), app.log_level)
             .with_target("some_issuer", app.log_level)
+            .with_target("web3id_issuer_engine", app.log_level)
             .with_target("tower_http", app.log_level);
         tracing_subscriber::registry()
             .with(tracing_subscriber::fmt::layer())
@@ -435,24 +604,32 @@ async fn main() -> anyhow::Result<()> {
         .context("Unable to read issuer's key.")?;
     let issuer_account = WalletAccount::from_json_file(app.wallet)?;
 
-    let nonce = node_client
-        .get_next_account_sequence_number(&issuer_account.address)
-        .await?;
+    let rate_limiter = RateLimiter::new(RateLimitConfig {
+        window: Duration::from_secs(app.rate_limit_window),
+        max_per_user: app.rate_limit_per_user,
+        max_per_ip: app.rate_limit_per_ip,
+        store: app.rate_limit_store,
+        client_ip_header: app.client_ip_header,
+    })
+    .context("Unable to set up rate limiting.")?;
+
+    let nonce = initial_nonce(&mut node_client, &issuer_account.address).await?;
+
     anyhow::ensure!(
-        nonce.all_final,
-        "Not all transactions are finalized. Refusing to start."
+        app.params_refresh_interval > 0,
+        "Parameters refresh interval should be positive."
     );
-
+    let crypto_params = CryptoParams::query(&mut node_client).await?;
     tracing::info!(
-        "Using account {} starting at nonce {}.",
-        issuer_account.address,
-        nonce.nonce
+        "Using cryptographic parameters with hash {}.",
+        crypto_params.hash
     );
-
-    let crypto_params = node_client
-        .get_cryptographic_parameters(BlockIdentifier::LastFinal)
-        .await?
-        .response;
+    let (params_sender, crypto_params) = tokio::sync::watch::channel(Arc::new(crypto_params));
+    tokio::spawn(refresh_params(
+        node_client.clone(),
+        std::time::Duration::from_secs(app.params_refresh_interval),
+        params_sender,
+    ));
 
     let mut contract_client = Cis4Contract::create(node_client, app.registry).await?;
 
@@ -471,11 +648,48 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Unable to get registry metadata")?;
 
-    let (issuer_sender, issuer_receiver) = tokio::sync::mpsc::channel(100);
+    let (issuer_worker, issuer) = IssuerWorker::new(
+        IssuerConfig {
+            crypto_params: crypto_params.clone(),
+            contract_client,
+            network: app.network,
+            issuer: issuer_account,
+            issuer_key: Arc::new(issuer_key),
+            credential_type: registry_metadata.credential_type,
+            credential_schema_url: registry_metadata.credential_schema.schema_ref.url().into(),
+            max_register_energy: app.max_register_energy,
+        },
+        SocialMediaPolicy::new(metadata_url),
+        nonce,
+    );
+
+    let session_store = if let Some(dir) = app.session_store_dir {
+        SessionBackend::File(FileSessionStore::new(dir)?)
+    } else if let Some(db_config) = app.session_db {
+        SessionBackend::Postgres(PostgresSessionStore::connect(db_config).await?)
+    } else {
+        SessionBackend::Cookie(CookieStore::new())
+    };
+    // Expired sessions in the cookie store are simply ignored, the others are
+    // deleted periodically.
+    if !matches!(session_store, SessionBackend::Cookie(_)) {
+        let session_store = session_store.clone();
+        let mut interval = tokio::time::interval(Duration::from_secs(app.session_ttl.max(60)));
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                if let Err(e) = session_store.remove_expired().await {
+                    tracing::warn!("Unable to remove expired sessions: {e:#}");
+                }
+            }
+        });
+    }
 
     let discord_redirect_uri = app.url.join("discord-oauth2")?;
     let state = AppState {
-        issuer_sender,
+        issuer,
+        rate_limiter,
+        crypto_params,
         discord_client_id: app.discord_client_id.clone().into(),
         discord_client_secret: app.discord_client_secret.into(),
         http_client,
@@ -483,12 +697,23 @@ async fn main() -> anyhow::Result<()> {
         handlebars: Arc::new(handlebars),
         dapp_domain: Arc::new(app.url),
         verifier_dapp_domain: Arc::new(app.verifier_dapp_domain.clone()),
+        session_store: session_store.clone(),
     };
 
-    let session_store = CookieStore::new();
-    let mut session_secret = [0u8; 128];
-    rand::thread_rng().fill(&mut session_secret);
+    let session_secret = match app.session_secret {
+        Some(secret) => secret.0,
+        None => {
+            tracing::warn!(
+                "No session secret given. Sessions will not survive a restart or be shared with \
+                 other replicas."
+            );
+            let mut secret = vec![0u8; 128];
+            rand::thread_rng().fill(&mut secret[..]);
+            secret
+        }
+    };
     let session_layer = SessionLayer::new(session_store, &session_secret)
+        .with_session_ttl(Some(Duration::from_secs(app.session_ttl)))
         .with_persistence_policy(axum_sessions::PersistencePolicy::ChangedOnly)
         .with_same_site_policy(axum_sessions::SameSite::None)
         .with_http_only(true)
@@ -534,6 +759,7 @@ async fn main() -> anyhow::Result<()> {
         .route("/", get(|| async { Html(index_html) }))
         .nest_service("/assets", serve_dir_service)
         .route("/credential", post(issue_discord_credential))
+        .route("/discord-login", get(start_oauth))
         .route("/discord-oauth2", get(handle_oauth_redirect))
         .route("/health", get(health))
         .route_layer(session_layer)
@@ -552,26 +778,8 @@ async fn main() -> anyhow::Result<()> {
         .layer(tower_http::compression::CompressionLayer::new())
         .layer(prometheus_layer);
 
-    let issuer_state = IssuerWorker {
-        crypto_params: Arc::new(crypto_params),
-        contract_client,
-        credential_type: registry_metadata.credential_type,
-        network: app.network,
-        issuer: Arc::new(issuer_account),
-        issuer_key: Arc::new(issuer_key),
-        state: SyncState::new(
-            nonce.nonce,
-            app.rate_limit_queue_capacity,
-            app.rate_limit_max_repeats,
-        ),
-        max_register_energy: app.max_register_energy,
-        metadata_url: Arc::new(metadata_url),
-        credential_schema_url: registry_metadata.credential_schema.schema_ref.url().into(),
-        receiver: issuer_receiver,
-    };
-
     start_services(
-        issuer_state,
+        issuer_worker,
         metric_handle,
         app.prometheus_address,
         app.listen_address,
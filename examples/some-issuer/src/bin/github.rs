@@ -0,0 +1,705 @@
+use anyhow::Context;
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
+    response::{Html, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use axum_prometheus::PrometheusMetricLayerBuilder;
+use axum_sessions::{
+    async_session::CookieStore,
+    extractors::{ReadableSession, WritableSession},
+    SessionLayer,
+};
+use clap::Parser;
+use concordium_rust_sdk::{
+    cis4::Cis4Contract,
+    contract_client::CredentialInfo,
+    types::{ContractAddress, Energy, WalletAccount},
+    v2::{self, BlockIdentifier},
+    web3id::did::Network,
+};
+use handlebars::Handlebars;
+use http::{HeaderValue, StatusCode};
+use rand::Rng;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use some_issuer::{
+    configure_endpoint, initial_nonce, refresh_params, send_tx, start_services, CryptoParams,
+    IssuerConfig, IssuerHandle, IssuerWorker, ParamsReceiver, PlatformUser, RateLimitConfig,
+    RateLimiter, SocialMediaPolicy,
+};
+use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tower_http::{cors::CorsLayer, services::ServeDir};
+
+/// The session key of the OAuth2 `state` of the flow started in the session.
+const OAUTH_STATE_KEY: &str = "github_oauth_state";
+const HTML_TITLE: &str = "GitHub Web3 ID issuer";
+const OAUTH_TEMPLATE: &str = include_str!("../../templates/github-oauth.hbs");
+
+#[derive(clap::Parser, Debug)]
+#[clap(arg_required_else_help(true))]
+#[clap(version, author)]
+struct App {
+    #[clap(
+        long = "node",
+        help = "GRPC V2 interface of the node.",
+        default_value = "http://localhost:20000",
+        env = "GITHUB_ISSUER_NODE"
+    )]
+    endpoint: v2::Endpoint,
+    #[clap(
+        long = "log-level",
+        default_value = "info",
+        help = "Maximum log level.",
+        env = "GITHUB_ISSUER_LOG_LEVEL"
+    )]
+    log_level: tracing_subscriber::filter::LevelFilter,
+    #[clap(
+        long = "network",
+        help = "The network of the issuer.",
+        default_value = "testnet",
+        env = "GITHUB_ISSUER_NETWORK"
+    )]
+    network: Network,
+    #[clap(
+        long = "request-timeout",
+        help = "Request timeout in milliseconds.",
+        default_value = "5000",
+        env = "GITHUB_ISSUER_REQUEST_TIMEOUT"
+    )]
+    request_timeout: u64,
+    #[clap(
+        long = "registry",
+        help = "Address of the registry smart contract.",
+        env = "GITHUB_ISSUER_REGISTRY_ADDRESS"
+    )]
+    registry: ContractAddress,
+    #[clap(
+        long = "wallet",
+        help = "Path to the wallet keys.",
+        env = "GITHUB_ISSUER_WALLET"
+    )]
+    wallet: PathBuf,
+    #[clap(
+        long = "issuer-key",
+        help = "Path to the issuer's key, used to sign commitments.",
+        env = "GITHUB_ISSUER_KEY"
+    )]
+    issuer_key: PathBuf,
+    #[clap(
+        long = "max-register-energy",
+        help = "The amount of energy to allow for execution of the register credential \
+                transaction. This must be less than max block energy of the chain the service is \
+                connected to.",
+        default_value = "10000",
+        env = "GITHUB_ISSUER_MAX_REGISTER_ENERGY"
+    )]
+    max_register_energy: Energy,
+    #[clap(
+        long = "github-client-id",
+        help = "GitHub client ID for OAuth2.",
+        env = "GITHUB_CLIENT_ID"
+    )]
+    github_client_id: String,
+    #[clap(
+        long = "github-client-secret",
+        help = "GitHub client secret for OAuth2.",
+        env = "GITHUB_CLIENT_SECRET"
+    )]
+    github_client_secret: String,
+    #[clap(
+        long = "github-oauth-url",
+        help = "Base URL of the GitHub OAuth2 endpoints.",
+        default_value = "https://github.com/",
+        env = "GITHUB_ISSUER_OAUTH_URL"
+    )]
+    github_oauth_url: Url,
+    #[clap(
+        long = "github-api-url",
+        help = "Base URL of the GitHub API, used to get the authenticated user.",
+        default_value = "https://api.github.com/",
+        env = "GITHUB_ISSUER_API_URL"
+    )]
+    github_api_url: Url,
+    #[clap(
+        long = "listen-address",
+        help = "Socket address for the GitHub issuer.",
+        default_value = "0.0.0.0:8082",
+        env = "GITHUB_ISSUER_LISTEN_ADDRESS"
+    )]
+    listen_address: SocketAddr,
+    #[clap(
+        long = "url",
+        help = "URL of the GitHub issuer.",
+        default_value = "http://127.0.0.1:8082/",
+        env = "GITHUB_ISSUER_URL"
+    )]
+    url: Url,
+    #[clap(
+        long = "verifier-dapp-domain",
+        help = "The domain of the verifier dApp, used for CORS.",
+        default_value = "http://127.0.0.1",
+        env = "GITHUB_ISSUER_VERIFIER_DAPP_URL"
+    )]
+    verifier_dapp_domain: String,
+    #[clap(
+        long = "frontend",
+        default_value = "./frontend/dist/github",
+        help = "Path to the directory where frontend assets are located.",
+        env = "GITHUB_ISSUER_FRONTEND"
+    )]
+    frontend_assets: std::path::PathBuf,
+    #[clap(
+        long = "rate-limit-window",
+        help = "The length in seconds of the sliding window in which credentials count towards \
+                the rate limits.",
+        default_value = "86400",
+        env = "GITHUB_ISSUER_RATE_LIMIT_WINDOW"
+    )]
+    rate_limit_window: u64,
+    #[clap(
+        long = "rate-limit-per-user",
+        help = "The number of credentials the same user id can be issued within the window.",
+        default_value = "5",
+        env = "GITHUB_ISSUER_RATE_LIMIT_PER_USER"
+    )]
+    rate_limit_per_user: u32,
+    #[clap(
+        long = "rate-limit-per-ip",
+        help = "The number of credentials the same client IP can be issued within the window.",
+        default_value = "20",
+        env = "GITHUB_ISSUER_RATE_LIMIT_PER_IP"
+    )]
+    rate_limit_per_ip: u32,
+    #[clap(
+        long = "rate-limit-store",
+        help = "Path to an SQLite database in which issued credentials are recorded, so that the \
+                rate limits survive restarts. If not given they are only kept in memory.",
+        env = "GITHUB_ISSUER_RATE_LIMIT_STORE"
+    )]
+    rate_limit_store: Option<PathBuf>,
+    #[clap(
+        long = "client-ip-header",
+        help = "Header set by a trusted reverse proxy with the address of the client, e.g., \
+                x-forwarded-for. The last address in the header is used. If not given, the peer \
+                address of the connection is used.",
+        env = "GITHUB_ISSUER_CLIENT_IP_HEADER"
+    )]
+    client_ip_header: Option<http::HeaderName>,
+    #[clap(
+        long = "prometheus-address",
+        help = "If set, a /metrics endpoint will be available on the address.",
+        env = "GITHUB_ISSUER_PROMETHEUS_ADDRESS"
+    )]
+    prometheus_address: Option<std::net::SocketAddr>,
+    #[clap(
+        long = "params-refresh-interval",
+        help = "Interval (in seconds) at which to check whether the cryptographic parameters \
+                have changed.",
+        default_value = "60",
+        env = "GITHUB_ISSUER_PARAMS_REFRESH_INTERVAL"
+    )]
+    params_refresh_interval: u64,
+}
+
+#[derive(Clone)]
+struct AppState {
+    issuer: IssuerHandle<PlatformUser>,
+    rate_limiter: RateLimiter,
+    crypto_params: ParamsReceiver,
+    github_client_id: Arc<str>,
+    github_client_secret: Arc<str>,
+    github_oauth_url: Arc<Url>,
+    github_api_url: Arc<Url>,
+    http_client: reqwest::Client,
+    handlebars: Arc<Handlebars<'static>>,
+    github_redirect_uri: Arc<Url>,
+    dapp_domain: Arc<Url>,
+    verifier_dapp_domain: Arc<String>,
+}
+
+/// Request for issuance of GitHub credential.
+#[derive(Debug, Deserialize)]
+struct GithubIssueRequest {
+    credential: CredentialInfo,
+}
+
+/// The parts of a GitHub user that are used in the credential.
+#[derive(Deserialize, Serialize, Debug)]
+struct User {
+    id: u64,
+    login: String,
+}
+
+#[derive(Serialize)]
+struct AccessTokenRequestData<'a> {
+    client_id: &'a str,
+    client_secret: &'a str,
+    code: &'a str,
+    redirect_uri: &'a str,
+}
+
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+    token_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Oauth2RedirectParams {
+    pub(crate) code: Option<String>,
+    pub(crate) error: Option<String>,
+    pub(crate) state: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Oauth2StartParams {
+    /// The OAuth2 `state` generated by the frontend, which it checks when the
+    /// redirect page reports back.
+    state: String,
+}
+
+#[derive(Serialize)]
+struct OauthTemplateParams<'a> {
+    id: &'a str,
+    username: &'a str,
+    dapp_domain: &'a str,
+    verifier_dapp_domain: &'a str,
+}
+
+#[derive(Serialize)]
+struct OauthErrorParams<'a> {
+    error: &'a str,
+    dapp_domain: &'a str,
+    verifier_dapp_domain: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractConfig {
+    index: String,
+    subindex: String,
+}
+
+impl From<ContractAddress> for ContractConfig {
+    fn from(value: ContractAddress) -> Self {
+        Self {
+            index: value.index.to_string(),
+            subindex: value.subindex.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FrontendConfig {
+    #[serde(rename = "type")]
+    config_type: String,
+    github_client_id: String,
+    github_oauth_url: String,
+    network: Network,
+    contract: ContractConfig,
+}
+
+/// Starts the OAuth2 flow by binding the `state` parameter to the session and
+/// redirecting to GitHub.
+#[tracing::instrument(level = "debug", skip(state, session))]
+async fn start_oauth(
+    State(state): State<AppState>,
+    Query(params): Query<Oauth2StartParams>,
+    mut session: WritableSession,
+) -> Result<Redirect, StatusCode> {
+    if params.state.is_empty() || params.state.len() > 128 {
+        tracing::warn!("Invalid OAuth2 state.");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    session
+        .insert(OAUTH_STATE_KEY, &params.state)
+        .map_err(|e| {
+            tracing::warn!("Cannot serialize OAuth2 state: {e}.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut url = state
+        .github_oauth_url
+        .join("login/oauth/authorize")
+        .map_err(|e| {
+            tracing::error!("Invalid GitHub authorization URL: {e}.");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    // No scopes are needed to read the public profile of the user.
+    url.query_pairs_mut()
+        .append_pair("client_id", &state.github_client_id)
+        .append_pair("redirect_uri", state.github_redirect_uri.as_str())
+        .append_pair("scope", "")
+        .append_pair("state", &params.state);
+    Ok(Redirect::to(url.as_str()))
+}
+
+/// Handles OAuth2 redirects and inserts an id in the session. The `state`
+/// parameter must be the one the flow was started with in this session.
+#[tracing::instrument(level = "debug", skip(state, session))]
+async fn handle_oauth_redirect(
+    State(state): State<AppState>,
+    Query(params): Query<Oauth2RedirectParams>,
+    mut session: WritableSession,
+) -> Result<Html<String>, StatusCode> {
+    // The state can only be used once.
+    let expected_state: Option<String> = session.get(OAUTH_STATE_KEY);
+    session.remove(OAUTH_STATE_KEY);
+    if expected_state.is_none() || expected_state != params.state {
+        tracing::warn!("OAuth2 state does not match the session.");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if let Some(code) = params.code {
+        match state.make_oauth_redirect_response(code, session).await {
+            Ok(response) => Ok(Html(response)),
+            Err(err) => {
+                tracing::warn!("Unsuccessful OAuth2 redirect: {err}");
+                Err(StatusCode::BAD_REQUEST)
+            }
+        }
+    } else if let Some(error) = params.error {
+        let params = OauthErrorParams {
+            error: error.as_str(),
+            dapp_domain: &state.dapp_domain.to_string(),
+            verifier_dapp_domain: &state.verifier_dapp_domain,
+        };
+
+        let output = state.handlebars.render("oauth", &params).map_err(|e| {
+            tracing::warn!("Unable to render oauth template with an error: {e}.");
+            StatusCode::BAD_REQUEST
+        })?;
+        Ok(Html(output))
+    } else {
+        tracing::warn!("Neither code nor error parameters are present.");
+        Err(StatusCode::BAD_REQUEST)
+    }
+}
+
+#[tracing::instrument(level = "debug", skip_all, fields(holder_id = %request.credential.holder_id))]
+async fn issue_github_credential(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    session: ReadableSession,
+    Json(request): Json<GithubIssueRequest>,
+) -> Result<Response, StatusCode> {
+    tracing::debug!("Issuing GitHub credential.");
+    let user_id = match session.get("github_id") {
+        Some(id) => id,
+        None => {
+            tracing::warn!("Missing session user id for GitHub request.");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let username = match session.get("github_username") {
+        Some(username) => username,
+        None => {
+            tracing::warn!("Missing session username for GitHub request.");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+
+    let client_ip = state.rate_limiter.client_ip(peer, &headers);
+    Ok(send_tx(
+        request.credential,
+        user_id,
+        username,
+        client_ip,
+        &state.issuer,
+        &state.rate_limiter,
+    )
+    .await)
+}
+
+impl AppState {
+    /// Exchanges an OAuth2 `code` for a User.
+    pub(crate) async fn get_user(&self, code: &str) -> anyhow::Result<User> {
+        let data = AccessTokenRequestData {
+            client_id: &self.github_client_id,
+            client_secret: &self.github_client_secret,
+            code,
+            redirect_uri: self.github_redirect_uri.as_str(),
+        };
+
+        let response = self
+            .http_client
+            .post(self.github_oauth_url.join("login/oauth/access_token")?)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Accept", "application/json")
+            .body(serde_urlencoded::to_string(data)?)
+            .send()
+            .await?;
+        anyhow::ensure!(
+            response.status().is_success(),
+            "user authetication failed: {}",
+            response.text().await?
+        );
+
+        // GitHub reports errors in the body of a successful response, in which case
+        // the response does not parse as an access token.
+        let response: AccessTokenResponse = response.json().await?;
+        anyhow::ensure!(
+            response.token_type.eq_ignore_ascii_case("bearer"),
+            "expected bearer token, got '{}'",
+            response.token_type
+        );
+
+        let response = self
+            .http_client
+            .get(self.github_api_url.join("user")?)
+            .bearer_auth(response.access_token)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?;
+        anyhow::ensure!(
+            response.status().is_success(),
+            "Unable to get user information."
+        );
+        let user = response.json().await?;
+        Ok(user)
+    }
+
+    pub(crate) async fn make_oauth_redirect_response(
+        &self,
+        code: String,
+        mut session: WritableSession,
+    ) -> anyhow::Result<String> {
+        let user = self
+            .get_user(&code)
+            .await
+            .context("Error getting GitHub user.")?;
+
+        // The user id is stable, whereas the login can be changed by the user.
+        let user_id = user.id.to_string();
+        let username = user.login;
+
+        session
+            .insert("github_id", &user_id)
+            .context("Cannot serialize user id.")?;
+        session
+            .insert("github_username", &username)
+            .context("Cannot serialize username.")?;
+
+        let params = OauthTemplateParams {
+            id: &user_id,
+            username: &username,
+            dapp_domain: &self.dapp_domain.to_string(),
+            verifier_dapp_domain: &self.verifier_dapp_domain,
+        };
+
+        let output = self
+            .handlebars
+            .render("oauth", &params)
+            .context("Unable to render oauth template with a User.")?;
+        Ok(output)
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Health {
+    version: &'static str,
+    crypto_params_hash: String,
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+async fn health(State(state): State<AppState>) -> Json<Health> {
+    Json(Health {
+        version: env!("CARGO_PKG_VERSION"),
+        crypto_params_hash: state.crypto_params.borrow().hash.clone(),
+    })
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let app = App::parse();
+
+    {
+        use tracing_subscriber::prelude::*;
+        let log_filter = tracing_subscriber::filter::Targets::new()
+            .with_target(module_path!(), app.log_level)
+            .with_target("some_issuer", app.log_level)
+            .with_target("web3id_issuer_engine", app.log_level)
+            .with_target("tower_http", app.log_level);
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer())
+            .with(log_filter)
+            .init();
+    }
+
+    let endpoint = configure_endpoint(
+        app.endpoint,
+        std::time::Duration::from_millis(app.request_timeout),
+    )?;
+    tracing::info!("Connecting to node...");
+
+    let mut node_client = v2::Client::new(endpoint)
+        .await
+        .context("Unable to establish connection to the node.")?;
+
+    let issuer_key = serde_json::from_reader(&std::fs::File::open(&app.issuer_key)?)
+        .context("Unable to read issuer's key.")?;
+    let issuer_account = WalletAccount::from_json_file(app.wallet)?;
+
+    let rate_limiter = RateLimiter::new(RateLimitConfig {
+        window: Duration::from_secs(app.rate_limit_window),
+        max_per_user: app.rate_limit_per_user,
+        max_per_ip: app.rate_limit_per_ip,
+        store: app.rate_limit_store,
+        client_ip_header: app.client_ip_header,
+    })
+    .context("Unable to set up rate limiting.")?;
+
+    let nonce = initial_nonce(&mut node_client, &issuer_account.address).await?;
+
+    anyhow::ensure!(
+        app.params_refresh_interval > 0,
+        "Parameters refresh interval should be positive."
+    );
+    let crypto_params = CryptoParams::query(&mut node_client).await?;
+    tracing::info!(
+        "Using cryptographic parameters with hash {}.",
+        crypto_params.hash
+    );
+    let (params_sender, crypto_params) = tokio::sync::watch::channel(Arc::new(crypto_params));
+    tokio::spawn(refresh_params(
+        node_client.clone(),
+        std::time::Duration::from_secs(app.params_refresh_interval),
+        params_sender,
+    ));
+
+    let mut contract_client = Cis4Contract::create(node_client, app.registry).await?;
+
+    // The GitHub API requires a user agent.
+    let http_client = reqwest::Client::builder()
+        .user_agent(concat!("some-issuer/", env!("CARGO_PKG_VERSION")))
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_millis(app.request_timeout))
+        .build()?;
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_template_string("oauth", OAUTH_TEMPLATE)?;
+
+    let metadata_url = app.url.join("json-schemas/credential-metadata.json")?;
+
+    let registry_metadata = contract_client
+        .registry_metadata(BlockIdentifier::LastFinal)
+        .await
+        .context("Unable to get registry metadata")?;
+
+    let (issuer_worker, issuer) = IssuerWorker::new(
+        IssuerConfig {
+            crypto_params: crypto_params.clone(),
+            contract_client,
+            network: app.network,
+            issuer: issuer_account,
+            issuer_key: Arc::new(issuer_key),
+            credential_type: registry_metadata.credential_type,
+            credential_schema_url: registry_metadata.credential_schema.schema_ref.url().into(),
+            max_register_energy: app.max_register_energy,
+        },
+        SocialMediaPolicy::new(metadata_url),
+        nonce,
+    );
+
+    let github_redirect_uri = app.url.join("github-oauth2")?;
+    let state = AppState {
+        issuer,
+        rate_limiter,
+        crypto_params,
+        github_client_id: app.github_client_id.clone().into(),
+        github_client_secret: app.github_client_secret.into(),
+        github_oauth_url: Arc::new(app.github_oauth_url.clone()),
+        github_api_url: Arc::new(app.github_api_url),
+        http_client,
+        github_redirect_uri: Arc::new(github_redirect_uri),
+        handlebars: Arc::new(handlebars),
+        dapp_domain: Arc::new(app.url),
+        verifier_dapp_domain: Arc::new(app.verifier_dapp_domain.clone()),
+    };
+
+    let session_store = CookieStore::new();
+    let mut session_secret = [0u8; 128];
+    rand::thread_rng().fill(&mut session_secret);
+    let session_layer = SessionLayer::new(session_store, &session_secret)
+        .with_persistence_policy(axum_sessions::PersistencePolicy::ChangedOnly)
+        .with_same_site_policy(axum_sessions::SameSite::None)
+        .with_http_only(true)
+        .with_secure(true);
+
+    let cors = CorsLayer::new()
+        .allow_methods([http::Method::GET, http::Method::POST])
+        .allow_origin(
+            app.verifier_dapp_domain
+                .parse::<HeaderValue>()
+                .context("dApp domain was not valid.")?,
+        )
+        .allow_credentials(true)
+        .allow_headers([http::header::CONTENT_TYPE]);
+
+    // Render index.html with config
+    let index_template = fs::read_to_string(app.frontend_assets.join("index.html"))
+        .context("Frontend was not built.")?;
+    let mut reg = Handlebars::new();
+    // Prevent handlebars from escaping inserted object
+    reg.register_escape_fn(|s| s.into());
+    let frontend_config = FrontendConfig {
+        config_type: "github".into(),
+        github_client_id: app.github_client_id,
+        github_oauth_url: app.github_oauth_url.to_string(),
+        network: app.network,
+        contract: app.registry.into(),
+    };
+    let config_string = serde_json::to_string(&frontend_config)?;
+    let index_html = reg.render_template(
+        &index_template,
+        &json!({ "config": config_string, "title": HTML_TITLE }),
+    )?;
+
+    let serve_dir_service = ServeDir::new(app.frontend_assets.join("assets"));
+    let json_schema_service = ServeDir::new("json-schemas/github");
+
+    let (prometheus_layer, metric_handle) = PrometheusMetricLayerBuilder::new()
+        .with_prefix("github")
+        .with_default_metrics()
+        .build_pair();
+
+    let router = Router::new()
+        .route("/", get(|| async { Html(index_html) }))
+        .nest_service("/assets", serve_dir_service)
+        .route("/credential", post(issue_github_credential))
+        .route("/github-login", get(start_oauth))
+        .route("/github-oauth2", get(handle_oauth_redirect))
+        .route("/health", get(health))
+        .route_layer(session_layer)
+        .nest_service("/json-schemas", json_schema_service)
+        .with_state(state)
+        .layer(cors)
+        .layer(
+            tower_http::trace::TraceLayer::new_for_http()
+                .make_span_with(tower_http::trace::DefaultMakeSpan::new())
+                .on_response(tower_http::trace::DefaultOnResponse::new()),
+        )
+        .layer(tower_http::timeout::TimeoutLayer::new(
+            std::time::Duration::from_millis(app.request_timeout),
+        ))
+        .layer(tower_http::limit::RequestBodyLimitLayer::new(100_000)) // at most 100kB of data
+        .layer(tower_http::compression::CompressionLayer::new())
+        .layer(prometheus_layer);
+
+    start_services(
+        issuer_worker,
+        metric_handle,
+        app.prometheus_address,
+        app.listen_address,
+        router,
+    )
+    .await
+}
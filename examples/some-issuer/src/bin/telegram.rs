@@ -1,7 +1,8 @@
 use anyhow::Context;
 use axum::{
-    extract::State,
-    response::Html,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+    response::{Html, Response},
     routing::{get, post},
     Json, Router,
 };
@@ -22,7 +23,9 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha2::{Digest, Sha256};
 use some_issuer::{
-    configure_endpoint, send_tx, start_services, IssueChannelData, IssuerWorker, SyncState,
+    configure_endpoint, initial_nonce, refresh_params, send_tx, start_services, CryptoParams,
+    IssuerConfig, IssuerHandle, IssuerWorker, ParamsReceiver, PlatformUser, RateLimitConfig,
+    RateLimiter, SocialMediaPolicy,
 };
 use std::{fmt::Write, fs, net::SocketAddr, path::PathBuf, sync::Arc};
 use tower_http::{cors::CorsLayer, services::ServeDir};
@@ -131,30 +134,63 @@ struct App {
     )]
     frontend_assets: std::path::PathBuf,
     #[clap(
-        long = "rate-limit-capacity",
-        help = "The number of issued credentials that we remember for rate limiting.",
-        default_value = "50000",
-        env = "TELEGRAM_ISSUER_RATE_LIMIT_CAPACITY"
+        long = "rate-limit-window",
+        help = "The length in seconds of the sliding window in which credentials count towards \
+                the rate limits.",
+        default_value = "86400",
+        env = "TELEGRAM_ISSUER_RATE_LIMIT_WINDOW"
     )]
-    rate_limit_queue_capacity: usize,
+    rate_limit_window: u64,
     #[clap(
-        long = "rate-limit-repeats",
-        help = "The number of times the same user id can be issued before being rate limited.",
+        long = "rate-limit-per-user",
+        help = "The number of credentials the same user id can be issued within the window.",
         default_value = "5",
-        env = "TELEGRAM_ISSUER_RATE_LIMIT_REPEATS"
+        env = "TELEGRAM_ISSUER_RATE_LIMIT_PER_USER"
     )]
-    rate_limit_max_repeats: usize,
+    rate_limit_per_user: u32,
+    #[clap(
+        long = "rate-limit-per-ip",
+        help = "The number of credentials the same client IP can be issued within the window.",
+        default_value = "20",
+        env = "TELEGRAM_ISSUER_RATE_LIMIT_PER_IP"
+    )]
+    rate_limit_per_ip: u32,
+    #[clap(
+        long = "rate-limit-store",
+        help = "Path to an SQLite database in which issued credentials are recorded, so that the \
+                rate limits survive restarts. If not given they are only kept in memory.",
+        env = "TELEGRAM_ISSUER_RATE_LIMIT_STORE"
+    )]
+    rate_limit_store: Option<PathBuf>,
+    #[clap(
+        long = "client-ip-header",
+        help = "Header set by a trusted reverse proxy with the address of the client, e.g., \
+                x-forwarded-for. The last address in the header is used. If not given, the peer \
+                address of the connection is used.",
+        env = "TELEGRAM_ISSUER_CLIENT_IP_HEADER"
+    )]
+    client_ip_header: Option<http::HeaderName>,
     #[clap(
         long = "prometheus-address",
         help = "If set, a /metrics endpoint will be available on the address.",
         env = "TELEGRAM_ISSUER_PROMETHEUS_ADDRESS"
     )]
     prometheus_address: Option<std::net::SocketAddr>,
+    #[clap(
+        long = "params-refresh-interval",
+        help = "Interval (in seconds) at which to check whether the cryptographic parameters \
+                have changed.",
+        default_value = "60",
+        env = "TELEGRAM_ISSUER_PARAMS_REFRESH_INTERVAL"
+    )]
+    params_refresh_interval: u64,
 }
 
 #[derive(Clone)]
 struct AppState {
-    issuer_sender: tokio::sync::mpsc::Sender<IssueChannelData>,
+    issuer: IssuerHandle<PlatformUser>,
+    rate_limiter: RateLimiter,
+    crypto_params: ParamsReceiver,
     telegram_bot_tokens: Arc<[String]>,
 }
 
@@ -242,8 +278,10 @@ struct FrontendConfig {
 #[tracing::instrument(level = "debug", skip_all, fields(holder_id = %request.credential.holder_id))]
 async fn issue_telegram_credential(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(request): Json<TelegramIssueRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Response, StatusCode> {
     if state
         .telegram_bot_tokens
         .iter()
@@ -258,24 +296,30 @@ async fn issue_telegram_credential(
         return Err(StatusCode::BAD_REQUEST);
     };
 
-    send_tx(
+    let client_ip = state.rate_limiter.client_ip(peer, &headers);
+    Ok(send_tx(
         request.credential,
         request.telegram_user.id.to_string(),
         username,
-        &state.issuer_sender,
+        client_ip,
+        &state.issuer,
+        &state.rate_limiter,
     )
-    .await
+    .await)
 }
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 struct Health {
     version: &'static str,
+    crypto_params_hash: String,
 }
 
-#[tracing::instrument(level = "info")]
-async fn health() -> Json<Health> {
+#[tracing::instrument(level = "info", skip_all)]
+async fn health(State(state): State<AppState>) -> Json<Health> {
     Json(Health {
         version: env!("CARGO_PKG_VERSION"),
+        crypto_params_hash: state.crypto_params.borrow().hash.clone(),
     })
 }
 
@@ -288,6 +332,7 @@ async fn main() -> anyhow::Result<()> {
         let log_filter = tracing_subscriber::filter::Targets::new()
             .with_target(module_path!(), app.log_level)
             .with_target("some_issuer", app.log_level)
+            .with_target("web3id_issuer_engine", app.log_level)
             .with_target("tower_http", app.log_level);
         tracing_subscriber::registry()
             .with(tracing_subscriber::fmt::layer())
@@ -309,24 +354,32 @@ async fn main() -> anyhow::Result<()> {
         .context("Unable to read issuer's key.")?;
     let issuer_account = WalletAccount::from_json_file(app.wallet)?;
 
-    let nonce = node_client
-        .get_next_account_sequence_number(&issuer_account.address)
-        .await?;
+    let rate_limiter = RateLimiter::new(RateLimitConfig {
+        window: std::time::Duration::from_secs(app.rate_limit_window),
+        max_per_user: app.rate_limit_per_user,
+        max_per_ip: app.rate_limit_per_ip,
+        store: app.rate_limit_store,
+        client_ip_header: app.client_ip_header,
+    })
+    .context("Unable to set up rate limiting.")?;
+
+    let nonce = initial_nonce(&mut node_client, &issuer_account.address).await?;
+
     anyhow::ensure!(
-        nonce.all_final,
-        "Not all transactions are finalized. Refusing to start."
+        app.params_refresh_interval > 0,
+        "Parameters refresh interval should be positive."
     );
-
+    let crypto_params = CryptoParams::query(&mut node_client).await?;
     tracing::info!(
-        "Using account {} starting at nonce {}.",
-        issuer_account.address,
-        nonce.nonce
+        "Using cryptographic parameters with hash {}.",
+        crypto_params.hash
     );
-
-    let crypto_params = node_client
-        .get_cryptographic_parameters(BlockIdentifier::LastFinal)
-        .await?
-        .response;
+    let (params_sender, crypto_params) = tokio::sync::watch::channel(Arc::new(crypto_params));
+    tokio::spawn(refresh_params(
+        node_client.clone(),
+        std::time::Duration::from_secs(app.params_refresh_interval),
+        params_sender,
+    ));
 
     let mut contract_client = Cis4Contract::create(node_client, app.registry).await?;
 
@@ -337,10 +390,25 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Unable to get registry metadata")?;
 
-    let (issuer_sender, issuer_receiver) = tokio::sync::mpsc::channel(100);
+    let (issuer_worker, issuer) = IssuerWorker::new(
+        IssuerConfig {
+            crypto_params: crypto_params.clone(),
+            contract_client,
+            network: app.network,
+            issuer: issuer_account,
+            issuer_key: Arc::new(issuer_key),
+            credential_type: registry_metadata.credential_type,
+            credential_schema_url: registry_metadata.credential_schema.schema_ref.url().into(),
+            max_register_energy: app.max_register_energy,
+        },
+        SocialMediaPolicy::new(metadata_url),
+        nonce,
+    );
 
     let state = AppState {
-        issuer_sender,
+        issuer,
+        rate_limiter,
+        crypto_params,
         telegram_bot_tokens: Arc::from(app.telegram_bot_tokens),
     };
 
@@ -400,26 +468,8 @@ async fn main() -> anyhow::Result<()> {
         .layer(tower_http::compression::CompressionLayer::new())
         .layer(prometheus_layer);
 
-    let issuer_state = IssuerWorker {
-        crypto_params: Arc::new(crypto_params),
-        contract_client,
-        network: app.network,
-        issuer: Arc::new(issuer_account),
-        issuer_key: Arc::new(issuer_key),
-        state: SyncState::new(
-            nonce.nonce,
-            app.rate_limit_queue_capacity,
-            app.rate_limit_max_repeats,
-        ),
-        max_register_energy: app.max_register_energy,
-        metadata_url: Arc::new(metadata_url),
-        credential_type: registry_metadata.credential_type,
-        credential_schema_url: registry_metadata.credential_schema.schema_ref.url().into(),
-        receiver: issuer_receiver,
-    };
-
     start_services(
-        issuer_state,
+        issuer_worker,
         metric_handle,
         app.prometheus_address,
         app.listen_address,
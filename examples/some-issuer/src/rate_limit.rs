@@ -0,0 +1,306 @@
+//! Rate limiting of issuance with sliding time windows. A credential can only
+//! be issued if both the user id and the client IP have been issued fewer
+//! credentials than their limit within the window. Issuances are recorded
+//! in memory, or in an SQLite database so that the limits survive restarts.
+use anyhow::Context;
+use axum_sessions::async_session::chrono;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use rusqlite::{params, Connection};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// How often expired issuances of all keys are deleted, in milliseconds.
+const PRUNE_INTERVAL_MILLIS: i64 = 60_000;
+
+/// The limits, and how issuances are recorded.
+pub struct RateLimitConfig {
+    /// The length of the sliding window.
+    pub window: Duration,
+    /// The number of credentials a user id can be issued within the window.
+    pub max_per_user: u32,
+    /// The number of credentials a client IP can be issued within the window.
+    pub max_per_ip: u32,
+    /// The SQLite database to record issuances in. If not given they are only
+    /// kept in memory.
+    pub store: Option<std::path::PathBuf>,
+    /// A header set by a trusted reverse proxy with the address of the
+    /// client, see [`client_ip`](some_verifier_lib::client_ip::client_ip).
+    pub client_ip_header: Option<HeaderName>,
+}
+
+/// The quota of the most limited of the user id and client IP.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+    /// The time until the oldest issuance in the window expires.
+    pub reset: Duration,
+}
+
+impl Quota {
+    /// The `x-ratelimit-*` headers describing the quota.
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(self.reset_secs()));
+        headers
+    }
+
+    /// The headers of a rate limited response, which in addition to the
+    /// quota tell the client when to retry.
+    pub fn limited_headers(&self) -> HeaderMap {
+        let mut headers = self.headers();
+        headers.insert(
+            http::header::RETRY_AFTER,
+            HeaderValue::from(self.reset_secs()),
+        );
+        headers
+    }
+
+    fn reset_secs(&self) -> u64 {
+        // Round up, so that the client does not retry too early.
+        (self.reset.as_millis() as u64 + 999) / 1000
+    }
+}
+
+/// The issuance recorded when a request was allowed. It is released if the
+/// credential is not issued after all.
+#[derive(Debug)]
+pub struct Grant {
+    pub quota: Quota,
+    keys: [String; 2],
+    at: i64,
+}
+
+pub enum Decision {
+    Allowed(Grant),
+    Limited(Quota),
+}
+
+/// Where issuances are recorded, as timestamps in milliseconds per key.
+enum Issuances {
+    Memory(HashMap<String, VecDeque<i64>>),
+    Sqlite(Connection),
+}
+
+struct Inner {
+    issuances: Issuances,
+    last_pruned: i64,
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<Inner>>,
+    window_millis: i64,
+    max_per_user: u32,
+    max_per_ip: u32,
+    client_ip_header: Option<HeaderName>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !config.window.is_zero(),
+            "The rate limit window must be positive."
+        );
+        let issuances = match &config.store {
+            Some(path) => Issuances::Sqlite(open_store(path)?),
+            None => Issuances::Memory(HashMap::new()),
+        };
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner {
+                issuances,
+                last_pruned: 0,
+            })),
+            window_millis: config.window.as_millis() as i64,
+            max_per_user: config.max_per_user,
+            max_per_ip: config.max_per_ip,
+            client_ip_header: config.client_ip_header,
+        })
+    }
+
+    /// The address of the client that sent a request to `peer`.
+    pub fn client_ip(&self, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+        some_verifier_lib::client_ip::client_ip(peer, headers, self.client_ip_header.as_ref())
+    }
+
+    /// Check the limits of the user id and client IP, and record an issuance
+    /// for both if neither is exceeded.
+    pub async fn acquire(&self, user_id: &str, client_ip: IpAddr) -> anyhow::Result<Decision> {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.acquire_at(user_id, client_ip, now).await
+    }
+
+    /// Like [`acquire`](Self::acquire), at `now` in milliseconds since the
+    /// unix epoch.
+    async fn acquire_at(
+        &self,
+        user_id: &str,
+        client_ip: IpAddr,
+        now: i64,
+    ) -> anyhow::Result<Decision> {
+        let keys = [format!("user:{user_id}"), format!("ip:{client_ip}")];
+        let limits = [self.max_per_user, self.max_per_ip];
+        let window = self.window_millis;
+        self.with_inner(move |inner| {
+            let since = now - window;
+            inner.prune(now, since)?;
+            let mut quotas = Vec::with_capacity(keys.len());
+            for (key, limit) in keys.iter().zip(limits) {
+                let (count, oldest) = inner.issuances.window(key, since)?;
+                let reset = oldest.map_or(0, |oldest| oldest + window - now);
+                quotas.push(Quota {
+                    limit,
+                    remaining: limit.saturating_sub(count),
+                    reset: Duration::from_millis(reset.max(0) as u64),
+                });
+            }
+            let quota = quotas
+                .into_iter()
+                .min_by_key(|quota| quota.remaining)
+                .context("No rate limit keys.")?;
+            if quota.remaining == 0 {
+                return Ok(Decision::Limited(quota));
+            }
+            for key in &keys {
+                inner.issuances.record(key, now)?;
+            }
+            let quota = Quota {
+                remaining: quota.remaining - 1,
+                reset: if quota.reset.is_zero() {
+                    Duration::from_millis(window as u64)
+                } else {
+                    quota.reset
+                },
+                ..quota
+            };
+            Ok(Decision::Allowed(Grant {
+                quota,
+                keys,
+                at: now,
+            }))
+        })
+        .await
+    }
+
+    /// Remove the issuance recorded by `grant`.
+    pub async fn release(&self, grant: Grant) -> anyhow::Result<()> {
+        self.with_inner(move |inner| {
+            for key in &grant.keys {
+                inner.issuances.remove(key, grant.at)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Run `f` with the issuances on a blocking thread.
+    async fn with_inner<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Inner) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = inner.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut inner)
+        })
+        .await?
+    }
+}
+
+impl Inner {
+    /// Delete the issuances before `since` of all keys, at most once every
+    /// [`PRUNE_INTERVAL_MILLIS`].
+    fn prune(&mut self, now: i64, since: i64) -> anyhow::Result<()> {
+        if now - self.last_pruned < PRUNE_INTERVAL_MILLIS {
+            return Ok(());
+        }
+        match &mut self.issuances {
+            Issuances::Memory(map) => map.retain(|_, times| {
+                times.retain(|&at| at >= since);
+                !times.is_empty()
+            }),
+            Issuances::Sqlite(connection) => {
+                connection.execute("DELETE FROM issuances WHERE issued_at < ?1", [since])?;
+            }
+        }
+        self.last_pruned = now;
+        Ok(())
+    }
+}
+
+impl Issuances {
+    /// The number of issuances of `key` since `since`, and the oldest of them.
+    fn window(&self, key: &str, since: i64) -> anyhow::Result<(u32, Option<i64>)> {
+        match self {
+            Issuances::Memory(map) => {
+                let times = map
+                    .get(key)
+                    .into_iter()
+                    .flatten()
+                    .filter(|&&at| at >= since);
+                let oldest = times.clone().min().copied();
+                Ok((times.count() as u32, oldest))
+            }
+            Issuances::Sqlite(connection) => Ok(connection.query_row(
+                "SELECT COUNT(*), MIN(issued_at) FROM issuances WHERE key = ?1 AND issued_at >= ?2",
+                params![key, since],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?),
+        }
+    }
+
+    fn record(&mut self, key: &str, at: i64) -> anyhow::Result<()> {
+        match self {
+            Issuances::Memory(map) => map.entry(key.into()).or_default().push_back(at),
+            Issuances::Sqlite(connection) => {
+                connection.execute(
+                    "INSERT INTO issuances (key, issued_at) VALUES (?1, ?2)",
+                    params![key, at],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str, at: i64) -> anyhow::Result<()> {
+        match self {
+            Issuances::Memory(map) => {
+                if let Some(times) = map.get_mut(key) {
+                    if let Some(i) = times.iter().rposition(|&t| t == at) {
+                        times.remove(i);
+                    }
+                }
+            }
+            Issuances::Sqlite(connection) => {
+                connection.execute(
+                    "DELETE FROM issuances WHERE rowid = (SELECT rowid FROM issuances WHERE key \
+                     = ?1 AND issued_at = ?2 LIMIT 1)",
+                    params![key, at],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn open_store(path: &Path) -> anyhow::Result<Connection> {
+    let connection = Connection::open(path)
+        .with_context(|| format!("Unable to open rate limit store {}.", path.display()))?;
+    connection.busy_timeout(Duration::from_secs(5))?;
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS issuances (key TEXT NOT NULL, issued_at INTEGER NOT NULL);
+         CREATE INDEX IF NOT EXISTS issuances_key_issued_at ON issuances (key, issued_at);
+         CREATE INDEX IF NOT EXISTS issuances_issued_at ON issuances (issued_at);",
+    )?;
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod tests;
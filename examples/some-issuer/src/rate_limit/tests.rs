@@ -0,0 +1,173 @@
+//! Tests of the rate limiter. Each test is run with issuances kept in memory,
+//! and in an in-memory SQLite database.
+use super::{Decision, Grant, Issuances, Quota, RateLimitConfig, RateLimiter};
+use std::{net::IpAddr, path::PathBuf, time::Duration};
+
+const WINDOW: Duration = Duration::from_secs(60);
+const WINDOW_MILLIS: i64 = 60_000;
+/// An arbitrary time, in milliseconds since the unix epoch.
+const START: i64 = 1_700_000_000_000;
+
+fn limiters() -> Vec<(&'static str, RateLimiter)> {
+    [
+        ("memory", None),
+        ("sqlite", Some(PathBuf::from(":memory:"))),
+    ]
+    .into_iter()
+    .map(|(name, store)| {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            window: WINDOW,
+            max_per_user: 2,
+            max_per_ip: 3,
+            store,
+            client_ip_header: None,
+        })
+        .unwrap();
+        (name, limiter)
+    })
+    .collect()
+}
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn allowed(decision: Decision, name: &str) -> Grant {
+    match decision {
+        Decision::Allowed(grant) => grant,
+        Decision::Limited(quota) => panic!("{name}: limited with {quota:?}"),
+    }
+}
+
+fn limited(decision: Decision, name: &str) -> Quota {
+    match decision {
+        Decision::Allowed(grant) => panic!("{name}: allowed with {:?}", grant.quota),
+        Decision::Limited(quota) => quota,
+    }
+}
+
+#[tokio::test]
+async fn issuances_expire_after_window() {
+    for (name, limiter) in limiters() {
+        let client = ip("10.0.0.1");
+        let acquire = |now| limiter.acquire_at("alice", client, now);
+        let first = allowed(acquire(START).await.unwrap(), name);
+        assert_eq!(first.quota.remaining, 1, "{name}");
+        assert_eq!(first.quota.reset, WINDOW, "{name}");
+        let second = allowed(acquire(START + 1000).await.unwrap(), name);
+        assert_eq!(second.quota.remaining, 0, "{name}");
+        // The first issuance expires first.
+        assert_eq!(second.quota.reset, Duration::from_secs(59), "{name}");
+
+        // Limited until the first issuance is older than the window.
+        let quota = limited(acquire(START + WINDOW_MILLIS).await.unwrap(), name);
+        assert_eq!(quota.limit, 2, "{name}");
+        assert_eq!(quota.remaining, 0, "{name}");
+        assert_eq!(quota.reset, Duration::ZERO, "{name}");
+        let third = allowed(acquire(START + WINDOW_MILLIS + 1).await.unwrap(), name);
+        assert_eq!(third.quota.remaining, 0, "{name}");
+        // The second issuance is the oldest in the window now.
+        assert_eq!(third.quota.reset, Duration::from_millis(999), "{name}");
+        limited(acquire(START + WINDOW_MILLIS + 2).await.unwrap(), name);
+    }
+}
+
+#[tokio::test]
+async fn users_and_clients_are_limited_separately() {
+    for (name, limiter) in limiters() {
+        let (home, work) = (ip("10.0.0.1"), ip("10.0.0.2"));
+        allowed(
+            limiter.acquire_at("alice", home, START).await.unwrap(),
+            name,
+        );
+        allowed(
+            limiter.acquire_at("alice", home, START).await.unwrap(),
+            name,
+        );
+        // The user is limited from any client.
+        let quota = limited(
+            limiter.acquire_at("alice", work, START).await.unwrap(),
+            name,
+        );
+        assert_eq!(quota.limit, 2, "{name}");
+
+        // Another user can use the same client until the client's limit.
+        let grant = allowed(limiter.acquire_at("bob", home, START).await.unwrap(), name);
+        assert_eq!(grant.quota.limit, 3, "{name}");
+        assert_eq!(grant.quota.remaining, 0, "{name}");
+        let quota = limited(
+            limiter.acquire_at("carol", home, START).await.unwrap(),
+            name,
+        );
+        assert_eq!(quota.limit, 3, "{name}");
+        // Limited requests are not recorded, so the user is not limited from
+        // other clients.
+        let grant = allowed(
+            limiter.acquire_at("carol", work, START).await.unwrap(),
+            name,
+        );
+        assert_eq!(grant.quota.remaining, 1, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn released_issuances_are_not_counted() {
+    for (name, limiter) in limiters() {
+        let client = ip("10.0.0.1");
+        let first = allowed(
+            limiter.acquire_at("alice", client, START).await.unwrap(),
+            name,
+        );
+        allowed(
+            limiter.acquire_at("alice", client, START).await.unwrap(),
+            name,
+        );
+        limited(
+            limiter.acquire_at("alice", client, START).await.unwrap(),
+            name,
+        );
+
+        limiter.release(first).await.unwrap();
+        let grant = allowed(
+            limiter.acquire_at("alice", client, START).await.unwrap(),
+            name,
+        );
+        assert_eq!(grant.quota.remaining, 0, "{name}");
+        // The issuance of the client was released as well.
+        let grant = allowed(
+            limiter.acquire_at("bob", client, START).await.unwrap(),
+            name,
+        );
+        assert_eq!(grant.quota.remaining, 0, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn memory_store_prunes_expired_issuances() {
+    let (_, limiter) = limiters().remove(0);
+    for i in 0..10 {
+        let client = ip(&format!("10.0.0.{i}"));
+        allowed(
+            limiter
+                .acquire_at(&format!("user-{i}"), client, START)
+                .await
+                .unwrap(),
+            "memory",
+        );
+    }
+    let later = START + 2 * WINDOW_MILLIS;
+    allowed(
+        limiter
+            .acquire_at("alice", ip("10.0.1.1"), later)
+            .await
+            .unwrap(),
+        "memory",
+    );
+    let inner = limiter.inner.lock().unwrap();
+    let Issuances::Memory(map) = &inner.issuances else {
+        panic!("Issuances should be kept in memory.");
+    };
+    let mut keys: Vec<_> = map.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, ["ip:10.0.1.1", "user:alice"]);
+}
@@ -0,0 +1,287 @@
+//! Server-side session stores, so that several replicas of an issuer can
+//! share sessions and sessions survive restarts. Sessions are stored under the
+//! SHA-256 hash of their id, so the stored data cannot be used to forge
+//! session cookies.
+use anyhow::Context;
+use axum_sessions::async_session::{self, async_trait, chrono, CookieStore, Session, SessionStore};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio_postgres::NoTls;
+
+/// Where the sessions of an issuer are kept.
+#[derive(Debug, Clone)]
+pub enum SessionBackend {
+    /// The session data is kept in the cookie itself.
+    Cookie(CookieStore),
+    File(FileSessionStore),
+    Postgres(PostgresSessionStore),
+}
+
+impl SessionBackend {
+    /// Delete the expired sessions. The cookie store has nothing to delete.
+    pub async fn remove_expired(&self) -> anyhow::Result<()> {
+        match self {
+            SessionBackend::Cookie(_) => Ok(()),
+            SessionBackend::File(store) => store.remove_expired().await,
+            SessionBackend::Postgres(store) => store.remove_expired().await,
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for SessionBackend {
+    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
+        match self {
+            SessionBackend::Cookie(store) => store.load_session(cookie_value).await,
+            SessionBackend::File(store) => store.load_session(cookie_value).await,
+            SessionBackend::Postgres(store) => store.load_session(cookie_value).await,
+        }
+    }
+
+    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
+        match self {
+            SessionBackend::Cookie(store) => store.store_session(session).await,
+            SessionBackend::File(store) => store.store_session(session).await,
+            SessionBackend::Postgres(store) => store.store_session(session).await,
+        }
+    }
+
+    async fn destroy_session(&self, session: Session) -> async_session::Result {
+        match self {
+            SessionBackend::Cookie(store) => store.destroy_session(session).await,
+            SessionBackend::File(store) => store.destroy_session(session).await,
+            SessionBackend::Postgres(store) => store.destroy_session(session).await,
+        }
+    }
+
+    async fn clear_store(&self) -> async_session::Result {
+        match self {
+            SessionBackend::Cookie(store) => store.clear_store().await,
+            SessionBackend::File(store) => store.clear_store().await,
+            SessionBackend::Postgres(store) => store.clear_store().await,
+        }
+    }
+}
+
+/// The key a session is stored under.
+fn storage_key(session_id: &str) -> String {
+    hex::encode(Sha256::digest(session_id.as_bytes()))
+}
+
+/// Stores each session as a JSON file in a directory, which can be shared by
+/// replicas on a common volume.
+#[derive(Debug, Clone)]
+pub struct FileSessionStore {
+    dir: Arc<PathBuf>,
+}
+
+impl FileSessionStore {
+    /// Use the directory, creating it if it does not exist.
+    pub fn new(dir: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Unable to create session directory {}.", dir.display()))?;
+        Ok(Self { dir: Arc::new(dir) })
+    }
+
+    fn path(&self, session_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", storage_key(session_id)))
+    }
+
+    /// Delete the files of expired sessions.
+    pub async fn remove_expired(&self) -> anyhow::Result<()> {
+        let mut entries = tokio::fs::read_dir(self.dir.as_path()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if is_session_file(&path)
+                && matches!(read_session(&path).await?, Some(session) if session.is_expired())
+            {
+                remove_file(&path).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_session_file(path: &Path) -> bool {
+    path.extension() == Some(std::ffi::OsStr::new("json"))
+}
+
+/// Read a session file, or `None` if it does not exist.
+async fn read_session(path: &Path) -> anyhow::Result<Option<Session>> {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove a file, ignoring that it has already been removed, e.g., by another
+/// replica.
+async fn remove_file(path: &Path) -> anyhow::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
+        let id = Session::id_from_cookie_value(&cookie_value)?;
+        let path = self.path(&id);
+        let Some(session) = read_session(&path).await? else {
+            return Ok(None);
+        };
+        let session = session.validate();
+        if session.is_none() {
+            remove_file(&path).await?;
+        }
+        Ok(session)
+    }
+
+    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
+        let path = self.path(session.id());
+        // Write to a temporary file first, so that concurrent readers never see
+        // a partially written session.
+        let tmp = path.with_extension(format!("tmp-{}", rand::random::<u64>()));
+        tokio::fs::write(&tmp, serde_json::to_vec(&session)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        session.reset_data_changed();
+        Ok(session.into_cookie_value())
+    }
+
+    async fn destroy_session(&self, session: Session) -> async_session::Result {
+        remove_file(&self.path(session.id())).await
+    }
+
+    async fn clear_store(&self) -> async_session::Result {
+        let mut entries = tokio::fs::read_dir(self.dir.as_path()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if is_session_file(&entry.path()) {
+                remove_file(&entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stores sessions in a Postgres table, which is created if it does not exist.
+/// Expiry times are stored as unix times in milliseconds.
+#[derive(Debug, Clone)]
+pub struct PostgresSessionStore {
+    pool: deadpool_postgres::Pool,
+}
+
+impl PostgresSessionStore {
+    pub async fn connect(db_config: tokio_postgres::Config) -> anyhow::Result<Self> {
+        let manager_config = deadpool_postgres::ManagerConfig {
+            recycling_method: deadpool_postgres::RecyclingMethod::Verified,
+        };
+        let manager = deadpool_postgres::Manager::from_config(db_config, NoTls, manager_config);
+        let pool = deadpool_postgres::Pool::builder(manager)
+            .create_timeout(Some(std::time::Duration::from_secs(5)))
+            .recycle_timeout(Some(std::time::Duration::from_secs(5)))
+            .wait_timeout(Some(std::time::Duration::from_secs(5)))
+            .runtime(deadpool_postgres::Runtime::Tokio1)
+            .build()?;
+        pool.get()
+            .await
+            .context("Unable to connect to the session database.")?
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS issuer_sessions (
+                     id TEXT PRIMARY KEY,
+                     session TEXT NOT NULL,
+                     expires_at BIGINT
+                 );
+                 CREATE INDEX IF NOT EXISTS issuer_sessions_expires_at
+                     ON issuer_sessions (expires_at);",
+            )
+            .await
+            .context("Unable to create the session table.")?;
+        Ok(Self { pool })
+    }
+
+    /// Delete the rows of expired sessions.
+    pub async fn remove_expired(&self) -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "DELETE FROM issuer_sessions WHERE expires_at <= $1",
+                &[&now],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for PostgresSessionStore {
+    async fn load_session(&self, cookie_value: String) -> async_session::Result<Option<Session>> {
+        let id = Session::id_from_cookie_value(&cookie_value)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let row = self
+            .pool
+            .get()
+            .await?
+            .query_opt(
+                "SELECT session FROM issuer_sessions
+                 WHERE id = $1 AND (expires_at IS NULL OR expires_at > $2)",
+                &[&storage_key(&id), &now],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let session: Session = serde_json::from_str(row.get(0))?;
+        Ok(session.validate())
+    }
+
+    async fn store_session(&self, session: Session) -> async_session::Result<Option<String>> {
+        let expires_at = session.expiry().map(|expiry| expiry.timestamp_millis());
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "INSERT INTO issuer_sessions (id, session, expires_at) VALUES ($1, $2, $3)
+                 ON CONFLICT (id) DO UPDATE
+                 SET session = EXCLUDED.session, expires_at = EXCLUDED.expires_at",
+                &[
+                    &storage_key(session.id()),
+                    &serde_json::to_string(&session)?,
+                    &expires_at,
+                ],
+            )
+            .await?;
+        session.reset_data_changed();
+        Ok(session.into_cookie_value())
+    }
+
+    async fn destroy_session(&self, session: Session) -> async_session::Result {
+        self.pool
+            .get()
+            .await?
+            .execute(
+                "DELETE FROM issuer_sessions WHERE id = $1",
+                &[&storage_key(session.id())],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn clear_store(&self) -> async_session::Result {
+        self.pool
+            .get()
+            .await?
+            .execute("DELETE FROM issuer_sessions", &[])
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
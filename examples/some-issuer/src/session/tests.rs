@@ -0,0 +1,123 @@
+//! Tests of the file session store. Each test uses its own directory, which
+//! is removed afterwards.
+use super::{storage_key, FileSessionStore};
+use axum_sessions::async_session::{chrono, Session, SessionStore};
+use std::path::PathBuf;
+
+/// A fresh session directory that is removed when dropped.
+struct SessionDir(PathBuf);
+
+impl SessionDir {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("some-issuer-sessions-{}", rand::random::<u64>())))
+    }
+
+    /// The names of the files in the directory, sorted.
+    fn files(&self) -> Vec<String> {
+        let mut files: Vec<String> = std::fs::read_dir(&self.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+impl Drop for SessionDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn session(user: &str, expires_in: chrono::Duration) -> Session {
+    let mut session = Session::new();
+    session.insert("user", user).unwrap();
+    session.set_expiry(chrono::Utc::now() + expires_in);
+    session
+}
+
+/// Store the session and return its id and cookie value.
+async fn store(store: &FileSessionStore, session: Session) -> (String, String) {
+    let id = session.id().to_string();
+    let cookie = store.store_session(session).await.unwrap().unwrap();
+    (id, cookie)
+}
+
+#[tokio::test]
+async fn store_and_load() {
+    let dir = SessionDir::new();
+    let sessions = FileSessionStore::new(dir.0.clone()).unwrap();
+    let (id, cookie) = store(&sessions, session("alice", chrono::Duration::hours(1))).await;
+
+    // The session is stored under the hash of its id, and the temporary file
+    // of the write is gone.
+    assert_eq!(dir.files(), [format!("{}.json", storage_key(&id))]);
+    let loaded = sessions.load_session(cookie).await.unwrap().unwrap();
+    assert_eq!(loaded.id(), id);
+    assert_eq!(loaded.get::<String>("user").as_deref(), Some("alice"));
+}
+
+#[tokio::test]
+async fn overwrite() {
+    let dir = SessionDir::new();
+    let sessions = FileSessionStore::new(dir.0.clone()).unwrap();
+    let mut session = session("alice", chrono::Duration::hours(1));
+    let (_, cookie) = store(&sessions, session.clone()).await;
+    session.insert("user", "bob").unwrap();
+    store(&sessions, session).await;
+
+    // The file is replaced, without temporary files left behind.
+    assert_eq!(dir.files().len(), 1);
+    let loaded = sessions.load_session(cookie).await.unwrap().unwrap();
+    assert_eq!(loaded.get::<String>("user").as_deref(), Some("bob"));
+}
+
+#[tokio::test]
+async fn expired_session() {
+    let dir = SessionDir::new();
+    let sessions = FileSessionStore::new(dir.0.clone()).unwrap();
+    let (_, cookie) = store(&sessions, session("alice", chrono::Duration::seconds(-1))).await;
+
+    // Loading an expired session deletes it.
+    assert!(sessions.load_session(cookie).await.unwrap().is_none());
+    assert!(dir.files().is_empty());
+}
+
+#[tokio::test]
+async fn unknown_session() {
+    let dir = SessionDir::new();
+    let sessions = FileSessionStore::new(dir.0.clone()).unwrap();
+    let cookie = Session::new().into_cookie_value().unwrap();
+    assert!(sessions.load_session(cookie).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn destroy_session() {
+    let dir = SessionDir::new();
+    let sessions = FileSessionStore::new(dir.0.clone()).unwrap();
+    let session = session("alice", chrono::Duration::hours(1));
+    let (_, cookie) = store(&sessions, session.clone()).await;
+    sessions.destroy_session(session.clone()).await.unwrap();
+    assert!(sessions.load_session(cookie).await.unwrap().is_none());
+    // Destroying a session that is already gone, e.g., by another replica, is
+    // not an error.
+    sessions.destroy_session(session).await.unwrap();
+}
+
+#[tokio::test]
+async fn remove_expired() {
+    let dir = SessionDir::new();
+    let sessions = FileSessionStore::new(dir.0.clone()).unwrap();
+    let (live, cookie) = store(&sessions, session("alice", chrono::Duration::hours(1))).await;
+    store(&sessions, session("bob", chrono::Duration::seconds(-1))).await;
+    store(&sessions, session("carol", chrono::Duration::seconds(-60))).await;
+    // Other files in the directory are left alone.
+    std::fs::write(dir.0.join("notes.txt"), "not a session").unwrap();
+
+    sessions.remove_expired().await.unwrap();
+    assert_eq!(
+        dir.files(),
+        [format!("{}.json", storage_key(&live)), "notes.txt".into()]
+    );
+    assert!(sessions.load_session(cookie).await.unwrap().is_some());
+}
@@ -0,0 +1,29 @@
+//! The address of the client of a request to a service behind a reverse
+//! proxy, used by the verifier and the issuers to rate limit clients.
+use http::{HeaderMap, HeaderName};
+use std::net::{IpAddr, SocketAddr};
+
+/// The address of the client that sent a request to `peer`. The `header` is
+/// set by a trusted reverse proxy with the address of the client, e.g.,
+/// `X-Forwarded-For`, and the last address in it is used. If no header is
+/// given, or the request does not carry a valid address in it, the peer
+/// address of the connection is used.
+pub fn client_ip(peer: SocketAddr, headers: &HeaderMap, header: Option<&HeaderName>) -> IpAddr {
+    let Some(header) = header else {
+        return peer.ip();
+    };
+    let forwarded = headers
+        .get_all(header)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .next_back()
+        .and_then(|ip| ip.trim().parse().ok());
+    forwarded.unwrap_or_else(|| {
+        tracing::warn!("Missing or invalid {header} header, using the peer address.");
+        peer.ip()
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,42 @@
+use super::client_ip;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use std::net::{IpAddr, SocketAddr};
+
+#[test]
+fn forwarded_addresses() {
+    let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    let header = HeaderName::from_static("x-forwarded-for");
+    let cases: [(&str, &[&str], &str); 6] = [
+        ("no header", &[], "10.0.0.1"),
+        ("single address", &["203.0.113.7"], "203.0.113.7"),
+        (
+            "last address",
+            &["198.51.100.1, 203.0.113.7"],
+            "203.0.113.7",
+        ),
+        (
+            "last header",
+            &["198.51.100.1", "203.0.113.7"],
+            "203.0.113.7",
+        ),
+        ("ipv6", &["2001:db8::1"], "2001:db8::1"),
+        ("invalid", &["203.0.113.7, unknown"], "10.0.0.1"),
+    ];
+    for (name, values, expected) in cases {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(&header, HeaderValue::from_static(value));
+        }
+        let expected: IpAddr = expected.parse().unwrap();
+        assert_eq!(client_ip(peer, &headers, Some(&header)), expected, "{name}");
+    }
+}
+
+#[test]
+fn without_header() {
+    let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.7"));
+    // The header is not trusted unless it is configured.
+    assert_eq!(client_ip(peer, &headers, None), peer.ip());
+}
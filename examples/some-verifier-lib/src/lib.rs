@@ -1,25 +1,49 @@
+pub mod client_ip;
+pub mod pseudonym;
+pub mod webhook;
+
 use concordium_rust_sdk::contract_client::CredentialStatus;
 use serde::{Deserialize, Serialize};
-use std::fmt::{self, Display, Formatter};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+};
 
-/// Represents a social media platform.
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum Platform {
-    Telegram,
-    Discord,
-}
+/// Represents a social media platform, identified by its name, e.g.,
+/// `telegram`. The platforms supported by a verifier are configured when the
+/// verifier is started.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[serde(transparent)]
+pub struct Platform(Cow<'static, str>);
 
 impl Platform {
-    pub const SUPPORTED_PLATFORMS: [Self; 2] = [Self::Telegram, Self::Discord];
+    pub const TELEGRAM: Self = Self(Cow::Borrowed("telegram"));
+    pub const DISCORD: Self = Self(Cow::Borrowed("discord"));
+    pub const GITHUB: Self = Self(Cow::Borrowed("github"));
+
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(Cow::Owned(name.into()))
+    }
+
+    /// The name of the platform as used in the API and in the database.
+    pub fn name(&self) -> &str {
+        &self.0
+    }
 }
 
 impl Display for Platform {
+    /// Displays the name with the first letter capitalized, e.g., `Telegram`.
+    /// Known platforms with other capitalization, such as `GitHub`, are
+    /// displayed by their proper name.
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Self::Telegram => write!(f, "Telegram"),
-            Self::Discord => write!(f, "Discord"),
+        if *self == Self::GITHUB {
+            return f.write_str("GitHub");
+        }
+        let mut chars = self.0.chars();
+        if let Some(first) = chars.next() {
+            write!(f, "{}{}", first.to_uppercase(), chars.as_str())?;
         }
+        Ok(())
     }
 }
 
@@ -34,7 +58,7 @@ pub struct Account {
 }
 
 /// A full name from a Concordium identity.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct FullName {
     pub first_name: String,
@@ -47,11 +71,25 @@ impl Display for FullName {
     }
 }
 
+/// A claim about the identity of a user that was proven without revealing
+/// the underlying attribute, e.g., that the user is at least 18 years old.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Claim {
+    /// The name of the claim, as configured in the verifier.
+    pub name: String,
+    /// A human readable description of the claim.
+    pub description: String,
+}
+
 /// A "verification" of a user. This type includes all confirmed
-/// accounts of a user and, optinally, their full name.
+/// accounts of a user and, optinally, their full name and proven claims
+/// about their identity.
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Verification {
     pub accounts: Vec<Account>,
     pub full_name: Option<FullName>,
+    #[serde(default)]
+    pub claims: Vec<Claim>,
 }
@@ -0,0 +1,18 @@
+//! Pseudonyms of platform user IDs. A verifier configured with a hashing key
+//! stores and reports these instead of the user IDs, so that its database does
+//! not reveal who is verified. Services that know the key, e.g., the bots, can
+//! compute the pseudonyms of their users with [`hash_id`].
+use crate::Platform;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// The hex encoded HMAC-SHA256 of `"{platform}:{id}"`, keyed with `key`. The
+/// platform is included so that the same user ID on different platforms has
+/// unrelated pseudonyms.
+pub fn hash_id(key: &[u8], platform: &Platform, id: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(platform.name().as_bytes());
+    mac.update(b":");
+    mac.update(id.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
@@ -0,0 +1,94 @@
+//! Webhooks sent by the some-verifier when verifications change, and helpers
+//! for receivers to check their signatures.
+//!
+//! Each webhook is a `POST` request with a JSON encoded [`Webhook`] as body.
+//! The request carries the unix time in seconds at which it was sent in the
+//! [`TIMESTAMP_HEADER`] and an HMAC-SHA256 of `"{timestamp}.{body}"`, keyed
+//! with the secret shared with the receiver, in the [`SIGNATURE_HEADER`].
+use crate::Platform;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Header carrying the hex encoded signature, prefixed by `sha256=`.
+pub const SIGNATURE_HEADER: &str = "X-Some-Verifier-Signature";
+/// Header carrying the unix time in seconds at which the webhook was sent.
+pub const TIMESTAMP_HEADER: &str = "X-Some-Verifier-Timestamp";
+
+/// An account on a platform, identified by its platform specific user ID.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountId {
+    pub platform: Platform,
+    pub id: String,
+}
+
+/// A change to a verification.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WebhookEvent {
+    /// A verification linking the accounts was added.
+    #[serde(rename_all = "camelCase")]
+    VerificationAdded { accounts: Vec<AccountId> },
+    /// The verification linking the accounts was removed, either by the user,
+    /// by an administrator, or because a credential became inactive.
+    #[serde(rename_all = "camelCase")]
+    VerificationRemoved { accounts: Vec<AccountId> },
+    /// The credential of `platform` in the verification linking the accounts
+    /// is no longer active. The verification is removed as well, which is
+    /// reported by a separate `verificationRemoved` event.
+    #[serde(rename_all = "camelCase")]
+    CredentialInactive {
+        platform: Platform,
+        accounts: Vec<AccountId>,
+    },
+}
+
+/// The body of a webhook.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    /// A unique ID of the webhook. Deliveries are retried on failure, so
+    /// receivers may see the same ID more than once.
+    pub id: String,
+    /// The tenant of the verifier whose verification changed, or `default`
+    /// for verifications made outside of any tenant.
+    pub tenant: String,
+    #[serde(flatten)]
+    pub event: WebhookEvent,
+}
+
+fn mac(secret: &[u8], timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Compute the value of the [`SIGNATURE_HEADER`] for a webhook with the given
+/// timestamp and body.
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(mac(secret, timestamp, body).finalize().into_bytes())
+    )
+}
+
+/// Check the value of the [`SIGNATURE_HEADER`] of a received webhook. The
+/// comparison is done in constant time. Receivers should also reject webhooks
+/// whose timestamp is too far from the current time, to prevent replays.
+pub fn verify(secret: &[u8], timestamp: i64, body: &[u8], signature: &str) -> bool {
+    let Some(signature) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex| hex::decode(hex).ok())
+    else {
+        return false;
+    };
+    mac(secret, timestamp, body)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests;
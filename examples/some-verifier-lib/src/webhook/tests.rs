@@ -0,0 +1,58 @@
+use super::{sign, verify};
+
+const SECRET: &[u8] = b"secret";
+const TIMESTAMP: i64 = 1_700_000_000;
+const BODY: &[u8] = br#"{"id":"1"}"#;
+
+#[test]
+fn known_signature() {
+    // HMAC-SHA256 of `1700000000.{"id":"1"}` with the key `secret`.
+    assert_eq!(
+        sign(SECRET, TIMESTAMP, BODY),
+        "sha256=086f6aff7bd084c98679825129c5a64dbad88c760016d6d2c0fb123f27951d54"
+    );
+}
+
+#[test]
+fn round_trip() {
+    let signature = sign(SECRET, TIMESTAMP, BODY);
+    assert!(verify(SECRET, TIMESTAMP, BODY, &signature));
+    // The hex encoding is case insensitive.
+    assert!(verify(
+        SECRET,
+        TIMESTAMP,
+        BODY,
+        &signature
+            .to_ascii_uppercase()
+            .replacen("SHA256=", "sha256=", 1)
+    ));
+}
+
+#[test]
+fn tampered() {
+    let signature = sign(SECRET, TIMESTAMP, BODY);
+    assert!(!verify(SECRET, TIMESTAMP, br#"{"id":"2"}"#, &signature));
+    assert!(!verify(SECRET, TIMESTAMP, b"", &signature));
+    assert!(!verify(SECRET, TIMESTAMP + 1, BODY, &signature));
+    assert!(!verify(b"other secret", TIMESTAMP, BODY, &signature));
+}
+
+#[test]
+fn malformed_header() {
+    let signature = sign(SECRET, TIMESTAMP, BODY);
+    let hex = signature.strip_prefix("sha256=").unwrap();
+    let cases = [
+        ("empty", String::new()),
+        ("missing prefix", hex.to_string()),
+        ("other algorithm", format!("sha512={hex}")),
+        ("prefix only", "sha256=".to_string()),
+        ("not hex", format!("sha256={}", "zz".repeat(32))),
+        ("odd length", format!("sha256={}", &hex[1..])),
+        ("truncated", format!("sha256={}", &hex[..32])),
+        ("extended", format!("sha256={hex}00")),
+        ("whitespace", format!(" {signature}")),
+    ];
+    for (name, header) in cases {
+        assert!(!verify(SECRET, TIMESTAMP, BODY, &header), "{name}");
+    }
+}
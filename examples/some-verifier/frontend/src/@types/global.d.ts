@@ -1,12 +1,15 @@
-import { Platform, Issuer } from '../lib/types';
+import { Platform, Issuer, Claim } from '../lib/types';
 
 declare global {
   declare const config: {
     discordClientId: string;
     telegramBotName: string;
     network: string;
+    basePath: string;
+    requireName: boolean;
     telegramInviteLink: string;
     discordInviteLink: string;
     issuers: Record<Platform, Issuer>;
+    claims: Claim[];
   };
 }
@@ -154,6 +154,18 @@ function Issuer({
           </div>
         )}
       </ListGroupItem>
+      {Platform.Github in config.issuers && (
+        <ListGroupItem>
+          <ListGroupItemHeading>GitHub</ListGroupItemHeading>
+          <a
+            href={config.issuers[Platform.Github].url}
+            target="_blank"
+            rel="noreferrer"
+          >
+            Get a GitHub credential from the GitHub issuer
+          </a>
+        </ListGroupItem>
+      )}
     </ListGroup>
   );
 }
@@ -14,7 +14,7 @@ import {
   Row,
 } from 'reactstrap';
 import { Platform } from '../lib/types';
-import { hash, requestProof } from '../lib/util';
+import { getChallenge, requestProof } from '../lib/util';
 import { WalletApi } from '@concordium/browser-wallet-api-helpers';
 import { appState } from '../lib/app-state';
 
@@ -46,9 +46,6 @@ export default function RemoveVerification({
 
     setPending(true);
 
-    const timestamp = new Date().toISOString();
-    const challenge = await hash(timestamp);
-
     let api: WalletApi;
     try {
       api = await concordiumProvider();
@@ -59,14 +56,15 @@ export default function RemoveVerification({
     }
 
     try {
+      const challenge = await getChallenge('remove');
       const proof = await requestProof(
         api,
         [config.issuers[platform as Platform]],
         challenge,
       );
 
-      const body = { proof, timestamp };
-      const response = await fetch('/verifications', {
+      const body = { proof };
+      const response = await fetch(`${config.basePath}/verifications`, {
         method: 'PATCH',
         headers: {
           'Content-Type': 'application/json',
@@ -137,6 +135,9 @@ export default function RemoveVerification({
                   >
                     <option value={Platform.Discord}>Discord</option>
                     <option value={Platform.Telegram}>Telegram</option>
+                    {Platform.Github in config.issuers && (
+                      <option value={Platform.Github}>GitHub</option>
+                    )}
                   </Input>
                   {error && <FormFeedback>{error}</FormFeedback>}
                 </FormGroup>
@@ -22,6 +22,7 @@ import telegram from 'bootstrap-icons/icons/telegram.svg';
 import discord from 'bootstrap-icons/icons/discord.svg';
 import telegramColor from '../assets/telegram-logo-color.svg';
 import discordColor from '../assets/discord-logo-color.svg';
+import githubLogo from '../assets/github-logo.svg';
 import { Platform } from '../lib/types';
 import ccdLogo from '../assets/ccd-logo.svg';
 import Issuer from './Issuer';
@@ -33,7 +34,7 @@ import {
   useState,
 } from 'react';
 import RemoveVerification from './RemoveVerification';
-import { hash, requestProof } from '../lib/util';
+import { getChallenge, requestProof } from '../lib/util';
 import { appState } from '../lib/app-state';
 import { WalletApi } from '@concordium/browser-wallet-api-helpers';
 import manifest from '../../package.json';
@@ -116,16 +117,39 @@ export default function Verify() {
 
   const [telegramChecked, setTelegramChecked] = useState(telegramIssued);
   const [discordChecked, setDiscordChecked] = useState(discordIssued);
-  const [fullNameChecked, setFullNameChecked] = useState(false);
+  const [githubChecked, setGithubChecked] = useState(false);
+  const [fullNameChecked, setFullNameChecked] = useState(config.requireName);
+  const [publicNameChecked, setPublicNameChecked] = useState(false);
+  const [checkedClaims, setCheckedClaims] = useState<string[]>([]);
+  const selectedClaims = useMemo(
+    () =>
+      config.claims.filter(
+        (c) => c.required || checkedClaims.includes(c.name),
+      ),
+    [checkedClaims],
+  );
+  const setClaimChecked = (name: string, checked: boolean) =>
+    setCheckedClaims((cs) =>
+      checked ? [...cs, name] : cs.filter((c) => c !== name),
+    );
 
   const [showPrivacyNotice, setShowPrivacyNotice] = useState(false);
   const togglePrivacyNotice = () => setShowPrivacyNotice((o) => !o);
 
   const checkedCount = useMemo(() => {
-    const count = +telegramChecked + +discordChecked + +fullNameChecked;
+    // The full name and claims are proven by a single identity credential.
+    const identityChecked = fullNameChecked || selectedClaims.length > 0;
+    const count =
+      +telegramChecked + +discordChecked + +githubChecked + +identityChecked;
     if (count >= 2) setProofError('');
     return count;
-  }, [telegramChecked, discordChecked, fullNameChecked]);
+  }, [
+    telegramChecked,
+    discordChecked,
+    githubChecked,
+    fullNameChecked,
+    selectedClaims,
+  ]);
 
   const issueTelegram = () => {
     setTelegramChecked(true);
@@ -156,6 +180,7 @@ export default function Verify() {
     const issuers = [];
     if (telegramChecked) issuers.push(config.issuers[Platform.Telegram]);
     if (discordChecked) issuers.push(config.issuers[Platform.Discord]);
+    if (githubChecked) issuers.push(config.issuers[Platform.Github]);
 
     let api: WalletApi;
     try {
@@ -166,17 +191,16 @@ export default function Verify() {
       return;
     }
 
-    const timestamp = new Date().toISOString();
-    const challenge = await hash(timestamp);
-
     try {
+      const challenge = await getChallenge('add');
       const proof = await requestProof(api, issuers, challenge, {
         revealName: fullNameChecked,
         revealUsername: true,
+        claims: selectedClaims,
       });
-      const body = { proof, timestamp };
+      const body = { proof, publicName: fullNameChecked && publicNameChecked };
 
-      const response = await fetch('/verifications', {
+      const response = await fetch(`${config.basePath}/verifications`, {
         method: 'POST',
         headers: {
           'Content-Type': 'application/json',
@@ -258,7 +282,8 @@ export default function Verify() {
                 Select the platforms you want to verify, essentially proving
                 ownership of the accounts referenced by the credentials in your
                 wallet. Additionally, you can also choose to reveal your full
-                name from an identity in your wallet.
+                name from an identity in your wallet, and prove claims about
+                the identity without revealing the underlying attributes.
               </p>
               <p>
                 <strong>
@@ -298,15 +323,53 @@ export default function Verify() {
                     <SVG className="me-1" src={discordColor} />
                     Discord
                   </PlatformOption>
+                  {Platform.Github in config.issuers && (
+                    <PlatformOption
+                      id={Platform.Github}
+                      checked={githubChecked}
+                      setChecked={setGithubChecked}
+                    >
+                      <SVG className="me-1" src={githubLogo} />
+                      GitHub
+                    </PlatformOption>
+                  )}
                   <PlatformOption
                     id="name"
                     checked={fullNameChecked}
-                    setChecked={setFullNameChecked}
+                    setChecked={(checked) =>
+                      !config.requireName && setFullNameChecked(checked)
+                    }
                   >
                     <SVG className="me-1" src={ccdLogo} />
                     Full name - Requires Concordium {config.network}&nbsp;{' '}
                     <strong>identity and account</strong>
+                    {config.requireName && <>&nbsp;(required)</>}
                   </PlatformOption>
+                  {fullNameChecked && (
+                    <PlatformOption
+                      id="public-name"
+                      checked={publicNameChecked}
+                      setChecked={setPublicNameChecked}
+                    >
+                      Show full name on my public profile and badge
+                    </PlatformOption>
+                  )}
+                  {config.claims.map((claim) => (
+                    <PlatformOption
+                      key={claim.name}
+                      id={`claim-${claim.name}`}
+                      checked={
+                        claim.required || checkedClaims.includes(claim.name)
+                      }
+                      setChecked={(checked) =>
+                        !claim.required && setClaimChecked(claim.name, checked)
+                      }
+                    >
+                      <SVG className="me-1" src={ccdLogo} />
+                      {claim.description}
+                      {claim.required && <>&nbsp;(required)</>}
+                    </PlatformOption>
+                  ))}
                 </ListGroup>
               </Col>
               {proofError && (
@@ -1,8 +1,20 @@
 export enum Platform {
   Telegram = 'telegram',
   Discord = 'discord',
+  Github = 'github',
 }
 
+/** A claim about the identity that the verifier accepts. */
+export type Claim = {
+  name: string;
+  description: string;
+  required: boolean;
+} & (
+  | { type: 'ageAtLeast'; years: number }
+  | { type: 'nationalityIn'; countries: string[] }
+  | { type: 'residenceNotIn'; countries: string[] }
+);
+
 export interface Issuer {
   url: string;
   index: string;
@@ -4,7 +4,7 @@ import {
   VerifiablePresentation,
   Web3StatementBuilder,
 } from '@concordium/web-sdk';
-import { Issuer } from './types';
+import { Claim, Issuer } from './types';
 import {
   WalletApi,
   detectConcordiumProvider,
@@ -61,14 +61,28 @@ export async function connectWallet(): Promise<WalletApi> {
   }
 }
 
-export async function hash(message: string): Promise<string> {
-  const msgUint8 = new TextEncoder().encode(message);
-  const hashBuffer = await crypto.subtle.digest('SHA-256', msgUint8);
-  const hashArray = Array.from(new Uint8Array(hashBuffer));
-  const hashHex = hashArray
-    .map((b) => b.toString(16).padStart(2, '0'))
-    .join('');
-  return hashHex;
+/** The operation a challenge can be used for. */
+export type ChallengeOperation = 'add' | 'remove';
+
+/**
+ * Get a single-use challenge from the verifier for the given operation.
+ * Returns the challenge as a hex string.
+ */
+export async function getChallenge(
+  operation: ChallengeOperation,
+): Promise<string> {
+  const response = await fetch(`${config.basePath}/challenge`, {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify({ operation }),
+  });
+  if (!response.ok) {
+    throw new Error(`Unable to get challenge: ${await response.text()}`);
+  }
+  const { challenge } = (await response.json()) as { challenge: string };
+  return challenge;
 }
 
 interface ProofOptions {
@@ -76,13 +90,15 @@ interface ProofOptions {
   revealName?: boolean;
   /** Whether request user to reveal its username for each platform. Defaults to false */
   revealUsername?: boolean;
+  /** Claims about the identity to prove. Defaults to none */
+  claims?: Claim[];
 }
 
 export async function requestProof(
   walletApi: WalletApi,
   issuers: Issuer[],
   challenge: string,
-  { revealName = false, revealUsername = false }: ProofOptions = {},
+  { revealName = false, revealUsername = false, claims = [] }: ProofOptions = {},
 ): Promise<VerifiablePresentation> {
   let builder = new Web3StatementBuilder();
 
@@ -101,12 +117,34 @@ export async function requestProof(
     );
   }
 
-  if (revealName) {
-    builder = builder.addForIdentityCredentials([0, 1, 2, 3], (b) =>
-      b
-        .revealAttribute(AttributeKeyString.firstName)
-        .revealAttribute(AttributeKeyString.lastName),
-    );
+  if (revealName || claims.length > 0) {
+    builder = builder.addForIdentityCredentials([0, 1, 2, 3], (b) => {
+      if (revealName) {
+        b = b
+          .revealAttribute(AttributeKeyString.firstName)
+          .revealAttribute(AttributeKeyString.lastName);
+      }
+      for (const claim of claims) {
+        switch (claim.type) {
+          case 'ageAtLeast':
+            b = b.addMinimumAge(claim.years);
+            break;
+          case 'nationalityIn':
+            b = b.addMembership(
+              AttributeKeyString.nationality,
+              claim.countries,
+            );
+            break;
+          case 'residenceNotIn':
+            b = b.addNonMembership(
+              AttributeKeyString.countryOfResidence,
+              claim.countries,
+            );
+            break;
+        }
+      }
+      return b;
+    });
   }
 
   const statements = builder.getStatements();
@@ -7,6 +7,8 @@ const config = {
   discordClientId: process.env.DISCORD_CLIENT_ID,
   telegramBotName: process.env.TELEGRAM_BOT_NAME,
   network: process.env.SOME_VERIFIER_NETWORK ?? 'testnet',
+  basePath: '',
+  requireName: false,
   issuers: {
     [Platform.Telegram]: {
       url: process.env.TELEGRAM_ISSUER_URL ?? '127.0.0.1:8080',
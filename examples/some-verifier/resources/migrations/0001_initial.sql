@@ -1,3 +1,5 @@
+-- Initial schema. Tables are created only if they do not exist, since databases
+-- created before migrations were introduced already contain them.
 CREATE TABLE IF NOT EXISTS verifications (
 	id SERIAL8 PRIMARY KEY,
 	presentation JSONB NOT NULL, -- presentation from generated by concordium wallet, which can be used to check the verification
@@ -0,0 +1,24 @@
+-- Store accounts of all platforms in a single table, and move accounts from the
+-- per-platform tables used by earlier versions.
+CREATE TABLE IF NOT EXISTS accounts (
+	platform VARCHAR NOT NULL, -- name of the platform, as configured in the verifier
+	id VARCHAR NOT NULL, -- user ID on the platform
+	cred_id BYTEA NOT NULL, -- ID of credential on chain.
+	verification_id INT8 NOT NULL REFERENCES verifications(id) ON DELETE CASCADE,
+	username VARCHAR NOT NULL, -- username on the platform at the time of verification
+	CONSTRAINT accounts_pkey PRIMARY KEY (platform, id),
+	CONSTRAINT accounts_cred_id_key UNIQUE (platform, cred_id),
+	CONSTRAINT accounts_verification_id_key UNIQUE (platform, verification_id)
+);
+
+DO $$
+DECLARE
+	platform_name VARCHAR;
+BEGIN
+	FOREACH platform_name IN ARRAY ARRAY['telegram', 'discord'] LOOP
+		IF EXISTS (SELECT FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = platform_name) THEN
+			EXECUTE format('INSERT INTO accounts (platform, id, cred_id, verification_id, username) SELECT %L, id, cred_id, verification_id, username FROM %I WHERE verification_id IS NOT NULL ON CONFLICT DO NOTHING', platform_name, platform_name);
+			EXECUTE format('DROP TABLE %I', platform_name);
+		END IF;
+	END LOOP;
+END $$;
@@ -0,0 +1,10 @@
+-- Usernames looked up using the APIs of the platforms, so that the APIs are not
+-- called on every lookup of a verification.
+CREATE TABLE IF NOT EXISTS username_cache (
+	platform VARCHAR NOT NULL,
+	id VARCHAR NOT NULL,
+	username VARCHAR NOT NULL, -- username returned by the platform's API
+	fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(), -- when the username was looked up
+	CONSTRAINT username_cache_pkey PRIMARY KEY (platform, id),
+	CONSTRAINT username_cache_account_fkey FOREIGN KEY (platform, id) REFERENCES accounts (platform, id) ON DELETE CASCADE
+);
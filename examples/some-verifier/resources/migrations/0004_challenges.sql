@@ -0,0 +1,6 @@
+-- Single-use challenges issued by the verifier for presentations.
+CREATE TABLE IF NOT EXISTS challenges (
+	challenge BYTEA PRIMARY KEY, -- the presentation context expected in the presentation
+	operation VARCHAR NOT NULL, -- the operation the challenge can be used for, e.g. "add" or "remove"
+	expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Names of the claims about the identity that a verification proves.
+CREATE TABLE IF NOT EXISTS claims (
+	verification_id INT8 NOT NULL REFERENCES verifications(id) ON DELETE CASCADE,
+	name VARCHAR NOT NULL, -- the name of the claim in the claims configuration
+	PRIMARY KEY (verification_id, name)
+);
@@ -0,0 +1,5 @@
+-- Record when verifications were created, so that their presentations can be
+-- purged after the retention period. Purged presentations are set to NULL,
+-- while the names, claims and accounts of the verification are kept.
+ALTER TABLE verifications ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE verifications ALTER COLUMN presentation DROP NOT NULL;
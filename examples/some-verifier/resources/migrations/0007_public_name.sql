@@ -0,0 +1,3 @@
+-- Whether the user agreed to show their full name on their public profile and
+-- badge. Existing verifications keep their names private.
+ALTER TABLE verifications ADD COLUMN IF NOT EXISTS public_name BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,19 @@
+-- Every row belongs to a tenant, i.e. a community with its own configuration.
+-- Existing rows belong to the default tenant. Accounts and cached usernames are
+-- unique per tenant, so the same account can be verified by several tenants.
+ALTER TABLE verifications ADD COLUMN IF NOT EXISTS tenant VARCHAR NOT NULL DEFAULT 'default';
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS tenant VARCHAR NOT NULL DEFAULT 'default';
+ALTER TABLE username_cache ADD COLUMN IF NOT EXISTS tenant VARCHAR NOT NULL DEFAULT 'default';
+ALTER TABLE challenges ADD COLUMN IF NOT EXISTS tenant VARCHAR NOT NULL DEFAULT 'default';
+
+ALTER TABLE username_cache DROP CONSTRAINT username_cache_account_fkey;
+ALTER TABLE username_cache DROP CONSTRAINT username_cache_pkey;
+ALTER TABLE accounts DROP CONSTRAINT accounts_pkey;
+ALTER TABLE accounts DROP CONSTRAINT accounts_cred_id_key;
+
+ALTER TABLE accounts ADD CONSTRAINT accounts_pkey PRIMARY KEY (tenant, platform, id);
+ALTER TABLE accounts ADD CONSTRAINT accounts_cred_id_key UNIQUE (tenant, platform, cred_id);
+ALTER TABLE username_cache ADD CONSTRAINT username_cache_pkey PRIMARY KEY (tenant, platform, id);
+ALTER TABLE username_cache ADD CONSTRAINT username_cache_account_fkey FOREIGN KEY (tenant, platform, id) REFERENCES accounts (tenant, platform, id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS verifications_tenant_idx ON verifications (tenant, id);
@@ -0,0 +1,42 @@
+-- Schema of the embedded SQLite database. It has the same tables as the
+-- Postgres schema, with timestamps as unix times in microseconds.
+CREATE TABLE verifications (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	presentation TEXT NULL, -- presentation as JSON, NULL once purged after the retention period
+	first_name TEXT NULL, -- from concordium identity
+	last_name TEXT NULL, -- from concordium identity
+	created_at INTEGER NOT NULL,
+	CONSTRAINT verifications_first_name_iff_last_name CHECK ((first_name IS NULL) = (last_name IS NULL))
+);
+
+CREATE TABLE accounts (
+	platform TEXT NOT NULL, -- name of the platform, as configured in the verifier
+	id TEXT NOT NULL, -- user ID on the platform
+	cred_id BLOB NOT NULL, -- ID of credential on chain.
+	verification_id INTEGER NOT NULL REFERENCES verifications(id) ON DELETE CASCADE,
+	username TEXT NOT NULL, -- username on the platform at the time of verification
+	PRIMARY KEY (platform, id),
+	UNIQUE (platform, cred_id),
+	UNIQUE (platform, verification_id)
+);
+
+CREATE TABLE username_cache (
+	platform TEXT NOT NULL,
+	id TEXT NOT NULL,
+	username TEXT NOT NULL, -- username returned by the platform's API
+	fetched_at INTEGER NOT NULL, -- when the username was looked up
+	PRIMARY KEY (platform, id),
+	FOREIGN KEY (platform, id) REFERENCES accounts (platform, id) ON DELETE CASCADE
+);
+
+CREATE TABLE challenges (
+	challenge BLOB PRIMARY KEY, -- the presentation context expected in the presentation
+	operation TEXT NOT NULL, -- the operation the challenge can be used for, e.g. "add" or "remove"
+	expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE claims (
+	verification_id INTEGER NOT NULL REFERENCES verifications(id) ON DELETE CASCADE,
+	name TEXT NOT NULL, -- the name of the claim in the claims configuration
+	PRIMARY KEY (verification_id, name)
+);
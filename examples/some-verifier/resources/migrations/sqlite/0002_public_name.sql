@@ -0,0 +1,3 @@
+-- Whether the user agreed to show their full name on their public profile and
+-- badge. Existing verifications keep their names private.
+ALTER TABLE verifications ADD COLUMN public_name INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,37 @@
+-- Every row belongs to a tenant, i.e. a community with its own configuration.
+-- Existing rows belong to the default tenant. Accounts and cached usernames are
+-- unique per tenant, so the same account can be verified by several tenants.
+-- SQLite cannot change primary keys, so the accounts table is rebuilt, and the
+-- username cache is recreated empty.
+ALTER TABLE verifications ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE challenges ADD COLUMN tenant TEXT NOT NULL DEFAULT 'default';
+
+DROP TABLE username_cache;
+
+CREATE TABLE accounts_new (
+	tenant TEXT NOT NULL,
+	platform TEXT NOT NULL, -- name of the platform, as configured in the verifier
+	id TEXT NOT NULL, -- user ID on the platform
+	cred_id BLOB NOT NULL, -- ID of credential on chain.
+	verification_id INTEGER NOT NULL REFERENCES verifications(id) ON DELETE CASCADE,
+	username TEXT NOT NULL, -- username on the platform at the time of verification
+	PRIMARY KEY (tenant, platform, id),
+	UNIQUE (tenant, platform, cred_id),
+	UNIQUE (platform, verification_id)
+);
+INSERT INTO accounts_new (tenant, platform, id, cred_id, verification_id, username)
+	SELECT 'default', platform, id, cred_id, verification_id, username FROM accounts;
+DROP TABLE accounts;
+ALTER TABLE accounts_new RENAME TO accounts;
+
+CREATE TABLE username_cache (
+	tenant TEXT NOT NULL,
+	platform TEXT NOT NULL,
+	id TEXT NOT NULL,
+	username TEXT NOT NULL, -- username returned by the platform's API
+	fetched_at INTEGER NOT NULL, -- when the username was looked up
+	PRIMARY KEY (tenant, platform, id),
+	FOREIGN KEY (tenant, platform, id) REFERENCES accounts (tenant, platform, id) ON DELETE CASCADE
+);
+
+CREATE INDEX verifications_tenant_idx ON verifications (tenant, id);
@@ -0,0 +1,3 @@
+-- The result of the last integrity check of each verification, i.e. 'valid',
+-- 'mismatch' or 'unverifiable'. NULL if the verification has not been checked.
+ALTER TABLE verifications ADD COLUMN integrity_status TEXT;
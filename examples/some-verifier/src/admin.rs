@@ -0,0 +1,183 @@
+//! Endpoints for administrators to inspect and manage stored verifications.
+//! All endpoints require the admin token as a bearer token.
+use crate::{
+    db::{AdminVerification, VerificationFilter},
+    integrity::{self, ProvenColumns},
+    AppState, Error,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use some_verifier_lib::{webhook::WebhookEvent, Platform};
+
+/// Default number of verifications in a page.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+/// Maximum number of verifications in a page.
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// The admin routes. These are only served if an admin token is configured.
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/verifications", get(list_verifications))
+        .route(
+            "/verifications/:id",
+            get(get_verification).delete(delete_verification),
+        )
+        .route_layer(axum::middleware::from_fn_with_state(state, authenticate))
+}
+
+/// Reject requests that do not carry the admin token of the tenant, which is
+/// its own token if it has one and `--admin-token` otherwise. The tokens are
+/// compared by their hashes, so that the comparison does not leak the token
+/// through its timing.
+async fn authenticate<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(admin_token) = state.admin_token.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match provided {
+        Some(token) if Sha256::digest(token) == Sha256::digest(admin_token) => {
+            next.run(request).await
+        }
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListQuery {
+    platform: Option<String>,
+    id: Option<String>,
+    username: Option<String>,
+    name: Option<String>,
+    /// Hex encoded credential holder id.
+    cred_id: Option<String>,
+    /// Only return verifications with ids larger than this.
+    after: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListResponse {
+    verifications: Vec<AdminVerification>,
+    /// The value of `after` for the next page, if there may be more results.
+    next: Option<i64>,
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+async fn list_verifications(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse>, Error> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    // Without the platform, the pseudonym of the id is unknown, so the id is
+    // matched as given, which finds nothing if ids are hashed.
+    let id = match (&query.platform, query.id) {
+        (Some(platform), Some(id)) => Some(state.stored_id(&Platform::new(platform.clone()), &id)),
+        (_, id) => id,
+    };
+    let filter = VerificationFilter {
+        platform: query.platform,
+        id,
+        username: query.username,
+        name: query.name,
+        cred_id: query
+            .cred_id
+            .map(|c| hex::decode(c).map_err(|_| Error::InvalidCredentialId))
+            .transpose()?,
+    };
+    let verifications = state
+        .database
+        .search_verifications(&filter, query.after, limit)
+        .await
+        .map_err(Error::Database)?;
+    let next = if verifications.len() as i64 == limit {
+        verifications.last().map(|v| v.id)
+    } else {
+        None
+    };
+    Ok(Json(ListResponse {
+        verifications,
+        next,
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationDetails {
+    #[serde(flatten)]
+    verification: AdminVerification,
+    /// The presentation, unless it has been purged after the retention period.
+    presentation: Option<serde_json::Value>,
+    /// The result of verifying the presentation, if it has not been purged.
+    reverification: Option<Reverification>,
+}
+
+#[tracing::instrument(level = "info", skip(state))]
+async fn get_verification(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Response, Error> {
+    let Some((verification, presentation)) = state
+        .database
+        .get_verification_by_id(id)
+        .await
+        .map_err(Error::Database)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let reverification = match &presentation {
+        Some(presentation) => {
+            let columns = ProvenColumns::of_admin_verification(&verification);
+            Some(integrity::reverify(&state, presentation, &columns).await)
+        }
+        None => None,
+    };
+    Ok(Json(VerificationDetails {
+        verification,
+        presentation,
+        reverification,
+    })
+    .into_response())
+}
+
+#[tracing::instrument(level = "info", skip(state))]
+async fn delete_verification(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, Error> {
+    let deleted = state
+        .database
+        .delete_verification(id)
+        .await
+        .map_err(Error::Database)?;
+    if let Some(accounts) = deleted {
+        tracing::info!("Administrator deleted verification {id}.");
+        state
+            .webhooks
+            .send(WebhookEvent::VerificationRemoved { accounts });
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
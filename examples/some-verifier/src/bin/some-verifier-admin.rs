@@ -0,0 +1,119 @@
+//! A command line client for the admin endpoints of the some-verifier. The
+//! responses are printed as JSON.
+use anyhow::Context;
+use clap::Parser;
+use reqwest::Url;
+
+#[derive(clap::Parser, Debug)]
+#[clap(version, author)]
+struct App {
+    #[clap(
+        long = "url",
+        help = "URL of the verifier.",
+        default_value = "http://127.0.0.1/",
+        env = "SOME_VERIFIER_ADMIN_URL"
+    )]
+    url: Url,
+    #[clap(
+        long = "admin-token",
+        help = "The admin token configured for the verifier.",
+        env = "SOME_VERIFIER_ADMIN_TOKEN"
+    )]
+    admin_token: String,
+    #[clap(
+        long = "tenant",
+        help = "The tenant whose verifications are managed. If not set, the default tenant is \
+                used.",
+        env = "SOME_VERIFIER_ADMIN_TENANT"
+    )]
+    tenant: Option<String>,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// List verifications matching all the given criteria.
+    List {
+        #[clap(long, help = "Platform that the verification has an account on.")]
+        platform: Option<String>,
+        #[clap(long, help = "User id of an account on the platform.")]
+        id: Option<String>,
+        #[clap(long, help = "Part of the username of an account.")]
+        username: Option<String>,
+        #[clap(long, help = "Part of the full name.")]
+        name: Option<String>,
+        #[clap(
+            long = "cred-id",
+            help = "Hex encoded credential holder id of an account."
+        )]
+        cred_id: Option<String>,
+        #[clap(long, help = "Only list verifications with ids larger than this.")]
+        after: Option<i64>,
+        #[clap(long, help = "Maximum number of verifications to list.")]
+        limit: Option<i64>,
+    },
+    /// Show a verification, including its presentation verified against the
+    /// current state of the chain.
+    Show { id: i64 },
+    /// Delete a verification.
+    Delete { id: i64 },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let app = App::parse();
+    let client = reqwest::Client::new();
+    let admin_url = match &app.tenant {
+        Some(tenant) => app.url.join(&format!("tenants/{tenant}/admin/"))?,
+        None => app.url.join("admin/")?,
+    };
+
+    let request = match app.command {
+        Command::List {
+            platform,
+            id,
+            username,
+            name,
+            cred_id,
+            after,
+            limit,
+        } => {
+            let query = [
+                ("platform", platform),
+                ("id", id),
+                ("username", username),
+                ("name", name),
+                ("credId", cred_id),
+                ("after", after.map(|a| a.to_string())),
+                ("limit", limit.map(|l| l.to_string())),
+            ];
+            let query: Vec<_> = query
+                .into_iter()
+                .filter_map(|(k, v)| v.map(|v| (k, v)))
+                .collect();
+            client.get(admin_url.join("verifications")?).query(&query)
+        }
+        Command::Show { id } => client.get(admin_url.join(&format!("verifications/{id}"))?),
+        Command::Delete { id } => client.delete(admin_url.join(&format!("verifications/{id}"))?),
+    };
+
+    let response = request
+        .bearer_auth(&app.admin_token)
+        .send()
+        .await
+        .context("Unable to reach the verifier.")?;
+    let status = response.status();
+    anyhow::ensure!(
+        status.is_success(),
+        "Request failed with status {status}: {}",
+        response.text().await.unwrap_or_default()
+    );
+    if status == reqwest::StatusCode::NO_CONTENT {
+        println!("Done.");
+    } else {
+        let body: serde_json::Value = response.json().await?;
+        println!("{}", serde_json::to_string_pretty(&body)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Claims about the identity of users that communities can require or accept,
+//! e.g., that a user is at least 18 years old. A claim is proven by a range or
+//! set membership statement about an identity attribute, so the attribute
+//! itself is not revealed. Only the names of the proven claims are stored.
+use anyhow::Context;
+use chrono::{Days, Months, NaiveDate};
+use concordium_rust_sdk::{
+    id::{
+        constants::{ArCurve, AttributeKind},
+        id_proof_types::{
+            AtomicStatement, AttributeInRangeStatement, AttributeInSetStatement,
+            AttributeNotInSetStatement,
+        },
+        types::AttributeTag,
+    },
+    smart_contracts::common::attributes,
+    web3id::Web3IdAttribute,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::Path,
+};
+
+/// What a claim proves about the identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ClaimKind {
+    /// The user is at least the given number of years old.
+    #[serde(rename_all = "camelCase")]
+    AgeAtLeast { years: u32 },
+    /// The nationality of the user is one of the given countries, as ISO
+    /// 3166-1 alpha-2 codes.
+    #[serde(rename_all = "camelCase")]
+    NationalityIn { countries: BTreeSet<String> },
+    /// The country of residence of the user is not one of the given
+    /// countries, as ISO 3166-1 alpha-2 codes.
+    #[serde(rename_all = "camelCase")]
+    ResidenceNotIn { countries: BTreeSet<String> },
+}
+
+/// Configuration of a single claim.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimConfig {
+    /// The name of the claim. This is stored in the database and used by the
+    /// frontend to identify the claim.
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ClaimKind,
+    /// Whether verifications without a proof of the claim are rejected.
+    #[serde(default)]
+    pub required: bool,
+    /// Description shown to users. If not given, one is derived from the
+    /// claim.
+    pub description: Option<String>,
+}
+
+/// Read the claims configuration from a JSON file, and check that names are
+/// unique.
+pub fn read_config(path: &Path) -> anyhow::Result<Vec<ClaimConfig>> {
+    let file = std::fs::File::open(path).context("Unable to open claims file.")?;
+    let claims: Vec<ClaimConfig> =
+        serde_json::from_reader(file).context("Unable to parse claims file.")?;
+    validate(&claims)?;
+    Ok(claims)
+}
+
+/// Check that the names of the claims are unique.
+pub fn validate(claims: &[ClaimConfig]) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    for claim in claims {
+        anyhow::ensure!(
+            names.insert(claim.name.as_str()),
+            "Claim {} is configured more than once.",
+            claim.name
+        );
+    }
+    Ok(())
+}
+
+impl ClaimConfig {
+    /// The description shown to users.
+    pub fn description(&self) -> String {
+        if let Some(description) = &self.description {
+            return description.clone();
+        }
+        match &self.kind {
+            ClaimKind::AgeAtLeast { years } => format!("Age at least {years}"),
+            ClaimKind::NationalityIn { countries } => format!(
+                "Nationality in {}",
+                countries.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            ClaimKind::ResidenceNotIn { countries } => format!(
+                "Residence not in {}",
+                countries.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    /// Whether the statement proves the claim on `today`.
+    pub fn is_proven_by(
+        &self,
+        statement: &AtomicStatement<ArCurve, AttributeTag, Web3IdAttribute>,
+        today: NaiveDate,
+    ) -> bool {
+        match (&self.kind, statement) {
+            (
+                ClaimKind::AgeAtLeast { years },
+                AtomicStatement::AttributeInRange {
+                    statement:
+                        AttributeInRangeStatement {
+                            attribute_tag,
+                            upper,
+                            ..
+                        },
+                },
+            ) if attribute_tag.0 == attributes::DOB.0 => {
+                // The statement proves that the date of birth is before `upper`.
+                // Wallets set `upper` to the day after the date `years` ago. One
+                // more day is allowed since the wallet may be in a different
+                // time zone.
+                let Some(latest) = today
+                    .checked_sub_months(Months::new(12 * years))
+                    .and_then(|d| d.checked_add_days(Days::new(2)))
+                else {
+                    return false;
+                };
+                as_string(upper)
+                    .and_then(parse_date)
+                    .is_some_and(|upper| upper <= latest)
+            }
+            (
+                ClaimKind::NationalityIn { countries },
+                AtomicStatement::AttributeInSet {
+                    statement:
+                        AttributeInSetStatement {
+                            attribute_tag, set, ..
+                        },
+                },
+            ) if attribute_tag.0 == attributes::NATIONALITY.0 => {
+                // Membership of a subset of the allowed countries proves the claim.
+                set.iter()
+                    .all(|c| as_string(c).is_some_and(|c| countries.contains(c)))
+            }
+            (
+                ClaimKind::ResidenceNotIn { countries },
+                AtomicStatement::AttributeNotInSet {
+                    statement:
+                        AttributeNotInSetStatement {
+                            attribute_tag, set, ..
+                        },
+                },
+            ) if attribute_tag.0 == attributes::COUNTRY_OF_RESIDENCE.0 => {
+                // Non-membership of a superset of the countries proves the claim.
+                let set: BTreeSet<&str> = set.iter().filter_map(as_string).collect();
+                countries.iter().all(|c| set.contains(c.as_str()))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse a date attribute, which is formatted as `YYYYMMDD`.
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+fn as_string(attribute: &Web3IdAttribute) -> Option<&str> {
+    match attribute {
+        Web3IdAttribute::String(AttributeKind(s)) => Some(s),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
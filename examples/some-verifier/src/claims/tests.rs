@@ -0,0 +1,151 @@
+//! Table-driven tests of which statements prove a claim.
+use super::{ClaimConfig, ClaimKind};
+use chrono::NaiveDate;
+use concordium_rust_sdk::{
+    id::{
+        constants::{ArCurve, AttributeKind},
+        id_proof_types::{
+            AtomicStatement, AttributeInRangeStatement, AttributeInSetStatement,
+            AttributeNotInSetStatement,
+        },
+        types::AttributeTag,
+    },
+    smart_contracts::common::attributes,
+    web3id::Web3IdAttribute,
+};
+
+type Statement = AtomicStatement<ArCurve, AttributeTag, Web3IdAttribute>;
+
+fn string(s: &str) -> Web3IdAttribute {
+    Web3IdAttribute::String(AttributeKind(s.into()))
+}
+
+fn claim(kind: ClaimKind) -> ClaimConfig {
+    ClaimConfig {
+        name: "claim".into(),
+        kind,
+        required: false,
+        description: None,
+    }
+}
+
+fn countries(codes: &[&str]) -> std::collections::BTreeSet<String> {
+    codes.iter().map(|c| c.to_string()).collect()
+}
+
+fn dob_range(tag: u8, upper: &str) -> Statement {
+    AtomicStatement::AttributeInRange {
+        statement: AttributeInRangeStatement {
+            attribute_tag: AttributeTag(tag),
+            lower: string("18000101"),
+            upper: string(upper),
+            _phantom: Default::default(),
+        },
+    }
+}
+
+fn in_set(tag: u8, set: &[&str]) -> Statement {
+    AtomicStatement::AttributeInSet {
+        statement: AttributeInSetStatement {
+            attribute_tag: AttributeTag(tag),
+            set: set.iter().map(|s| string(s)).collect(),
+            _phantom: Default::default(),
+        },
+    }
+}
+
+fn not_in_set(tag: u8, set: &[&str]) -> Statement {
+    AtomicStatement::AttributeNotInSet {
+        statement: AttributeNotInSetStatement {
+            attribute_tag: AttributeTag(tag),
+            set: set.iter().map(|s| string(s)).collect(),
+            _phantom: Default::default(),
+        },
+    }
+}
+
+#[test]
+fn age_at_least() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+    let adult = claim(ClaimKind::AgeAtLeast { years: 18 });
+    let dob = attributes::DOB.0;
+    let cases = [
+        // Wallets prove that the date of birth is before the day after the
+        // date 18 years ago.
+        ("wallet bound", dob_range(dob, "20060316"), true),
+        ("older", dob_range(dob, "19900101"), true),
+        // One extra day is allowed for wallets in other time zones.
+        ("exactly on the boundary", dob_range(dob, "20060317"), true),
+        ("one day short", dob_range(dob, "20060318"), false),
+        (
+            "wrong attribute tag",
+            dob_range(attributes::ID_DOC_EXPIRES_AT.0, "20060316"),
+            false,
+        ),
+        ("not a date", dob_range(dob, "2006031"), false),
+        ("separators", dob_range(dob, "2006-3-16"), false),
+        ("sign", dob_range(dob, "+2006031"), false),
+        ("invalid day", dob_range(dob, "20060230"), false),
+        ("set statement", in_set(dob, &["20060316"]), false),
+    ];
+    for (name, statement, expected) in cases {
+        assert_eq!(adult.is_proven_by(&statement, today), expected, "{name}");
+    }
+}
+
+#[test]
+fn age_at_least_leap_day() {
+    // There is no 29 February 18 years back, so the date 18 years ago is 28
+    // February.
+    let today = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+    let adult = claim(ClaimKind::AgeAtLeast { years: 18 });
+    let dob = attributes::DOB.0;
+    assert!(adult.is_proven_by(&dob_range(dob, "20060302"), today));
+    assert!(!adult.is_proven_by(&dob_range(dob, "20060303"), today));
+}
+
+#[test]
+fn nationality_in() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+    let eu = claim(ClaimKind::NationalityIn {
+        countries: countries(&["DE", "DK", "FR"]),
+    });
+    let nationality = attributes::NATIONALITY.0;
+    let cases = [
+        ("same set", in_set(nationality, &["DE", "DK", "FR"]), true),
+        ("subset", in_set(nationality, &["DK"]), true),
+        ("too broad", in_set(nationality, &["DK", "US"]), false),
+        (
+            "wrong attribute tag",
+            in_set(attributes::COUNTRY_OF_RESIDENCE.0, &["DK"]),
+            false,
+        ),
+        ("not in set", not_in_set(nationality, &["US"]), false),
+    ];
+    for (name, statement, expected) in cases {
+        assert_eq!(eu.is_proven_by(&statement, today), expected, "{name}");
+    }
+}
+
+#[test]
+fn residence_not_in() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+    let allowed = claim(ClaimKind::ResidenceNotIn {
+        countries: countries(&["KP", "US"]),
+    });
+    let residence = attributes::COUNTRY_OF_RESIDENCE.0;
+    let cases = [
+        ("same set", not_in_set(residence, &["KP", "US"]), true),
+        ("superset", not_in_set(residence, &["IR", "KP", "US"]), true),
+        ("too narrow", not_in_set(residence, &["US"]), false),
+        (
+            "wrong attribute tag",
+            not_in_set(attributes::NATIONALITY.0, &["KP", "US"]),
+            false,
+        ),
+        ("in set", in_set(residence, &["DK"]), false),
+    ];
+    for (name, statement, expected) in cases {
+        assert_eq!(allowed.is_proven_by(&statement, today), expected, "{name}");
+    }
+}
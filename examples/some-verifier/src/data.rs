@@ -0,0 +1,63 @@
+//! Endpoints for users to export and erase the data stored about them. Users
+//! authenticate with a presentation of one of their social media credentials,
+//! with a challenge issued for the `export` or `erase` operation.
+use crate::{db::DataExport, AppState, Error, Operation, Request};
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use reqwest::StatusCode;
+use some_verifier_lib::webhook::WebhookEvent;
+
+/// Respond with all data stored about the user of the credential, or
+/// `404 Not Found` if there is none.
+#[tracing::instrument(level = "info", skip_all)]
+pub async fn export_data(
+    State(mut state): State<AppState>,
+    request: Result<Json<Request>, JsonRejection>,
+) -> Result<Response, Error> {
+    let Json(request) = request?;
+    let (platform, credential) = state
+        .verify_single_credential(&request, Operation::Export)
+        .await?;
+
+    let export: Option<DataExport> = state
+        .database
+        .export_data(&credential, &platform)
+        .await
+        .map_err(Error::Database)?;
+    match export {
+        Some(export) => Ok(Json(export).into_response()),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+/// Erase all data stored about the user of the credential. Responds with
+/// `204 No Content` if data was erased, and `404 Not Found` if there was none.
+#[tracing::instrument(level = "info", skip_all)]
+pub async fn erase_data(
+    State(mut state): State<AppState>,
+    request: Result<Json<Request>, JsonRejection>,
+) -> Result<StatusCode, Error> {
+    let Json(request) = request?;
+    let (platform, credential) = state
+        .verify_single_credential(&request, Operation::Erase)
+        .await?;
+
+    // Accounts, claims and cached usernames are removed with the verification.
+    let erased = state
+        .database
+        .remove_verification(&credential, &platform)
+        .await
+        .map_err(Error::Database)?;
+    if let Some(accounts) = erased {
+        tracing::info!("Erased the data of a {platform} user on request.");
+        state
+            .webhooks
+            .send(WebhookEvent::VerificationRemoved { accounts });
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
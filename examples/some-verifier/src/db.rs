@@ -1,11 +1,29 @@
+//! Storage of verifications. The verifier only uses the [`Storage`] trait,
+//! which is implemented for Postgres by [`PostgresStorage`] and for an embedded
+//! SQLite database by [`SqliteStorage`].
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use concordium_rust_sdk::{
     id::constants::ArCurve,
     web3id::{CredentialHolderId, Presentation, Web3IdAttribute},
 };
-use some_verifier_lib::{FullName, Platform};
-use tokio_postgres::{types::ToSql, NoTls};
+use some_verifier_lib::{webhook::AccountId, FullName, Platform};
+use std::sync::Arc;
+
+mod postgres;
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub use postgres::{migrate, PostgresStorage};
+pub use sqlite::SqliteStorage;
+
+/// The tenant of storages that are not scoped with [`Storage::for_tenant`],
+/// and of all rows created before tenants existed.
+pub const DEFAULT_TENANT: &str = "default";
 
 const VERIFICATIONS_TABLE: &str = "verifications";
+const ACCOUNTS_TABLE: &str = "accounts";
 const PRESENTATION_COLUMN: &str = "presentation";
 const FIRST_NAME_COLUMN: &str = "first_name";
 const LAST_NAME_COLUMN: &str = "last_name";
@@ -13,33 +31,19 @@ const ID_COLUMN: &str = "id";
 const CRED_ID_COLUMN: &str = "cred_id";
 const VERIFICATION_ID_COLUMN: &str = "verification_id";
 const USERNAME_COLUMN: &str = "username";
-
-/// A trait that is implemented for the Platform enum to give some utility
-/// functons. This is a trait because of orphan rules. It is only implemented
-/// for a single type.
-trait DbName {
-    /// The name of the corresponding table.
-    fn table_name(&self) -> &'static str;
-    fn insert_statement(&self) -> String;
-}
-
-impl DbName for Platform {
-    fn table_name(&self) -> &'static str {
-        match self {
-            Platform::Telegram => "telegram",
-            Platform::Discord => "discord",
-        }
-    }
-
-    fn insert_statement(&self) -> String {
-        format!(
-            "INSERT INTO {0} ({ID_COLUMN}, {CRED_ID_COLUMN}, {VERIFICATION_ID_COLUMN}, \
-             {USERNAME_COLUMN}) VALUES ($1, $2, $3, $4) ON CONFLICT ON CONSTRAINT {0}_pkey DO \
-             NOTHING RETURNING {ID_COLUMN}",
-            self.table_name()
-        )
-    }
-}
+const PLATFORM_COLUMN: &str = "platform";
+const USERNAME_CACHE_TABLE: &str = "username_cache";
+const FETCHED_AT_COLUMN: &str = "fetched_at";
+const CHALLENGES_TABLE: &str = "challenges";
+const CHALLENGE_COLUMN: &str = "challenge";
+const OPERATION_COLUMN: &str = "operation";
+const EXPIRES_AT_COLUMN: &str = "expires_at";
+const CLAIMS_TABLE: &str = "claims";
+const NAME_COLUMN: &str = "name";
+const CREATED_AT_COLUMN: &str = "created_at";
+const PUBLIC_NAME_COLUMN: &str = "public_name";
+const TENANT_COLUMN: &str = "tenant";
+const INTEGRITY_STATUS_COLUMN: &str = "integrity_status";
 
 /// A platform and user id + username for that platform.
 #[derive(Debug)]
@@ -47,56 +51,159 @@ pub struct DbAccount {
     pub platform: Platform,
     pub id: String,
     pub username: String,
+    pub cred_id: CredentialHolderId,
+}
+
+/// An account together with the credential it was verified with.
+#[derive(Debug)]
+pub struct DbAccountCredential {
+    pub platform: Platform,
+    pub id: String,
+    pub cred_id: CredentialHolderId,
 }
 
 /// The output from querying a line in the verifications table.
 pub struct DbVerification {
+    pub id: i64,
     pub accounts: Vec<DbAccount>,
     pub full_name: Option<FullName>,
-    pub presentation: Presentation<ArCurve, Web3IdAttribute>,
+    /// Names of the claims proven by the verification.
+    pub claims: Vec<String>,
+    /// Whether the full name may be shown on the public profile and badge.
+    pub public_name: bool,
+    /// The presentation, unless it has been purged after the retention period.
+    pub presentation: Option<serde_json::Value>,
 }
 
-/// Initializer for verification entries, including the entries of the platform
-/// tables.
-pub struct VerificationsEntry {
-    pub telegram: Option<PlatformEntry>,
-    pub discord: Option<PlatformEntry>,
-    pub presentation: serde_json::Value,
+/// Criteria for searching verifications. All given criteria must match.
+#[derive(Debug, Default)]
+pub struct VerificationFilter {
+    /// A platform that the verification has an account on.
+    pub platform: Option<String>,
+    /// The user id of an account of the verification.
+    pub id: Option<String>,
+    /// Part of the username of an account, case-insensitive.
+    pub username: Option<String>,
+    /// Part of the full name, case-insensitive.
+    pub name: Option<String>,
+    /// The credential holder id of an account.
+    pub cred_id: Option<Vec<u8>>,
+}
+
+/// An account as seen by administrators.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminAccount {
+    pub platform: Platform,
+    pub id: String,
+    pub username: String,
+    /// The credential holder id, hex encoded.
+    pub cred_id: String,
+}
+
+/// A verification as seen by administrators.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminVerification {
+    pub id: i64,
     pub full_name: Option<FullName>,
+    pub accounts: Vec<AdminAccount>,
+    /// The result of the last integrity check, if the verification has been
+    /// checked.
+    pub integrity_status: Option<IntegrityStatus>,
 }
 
-impl VerificationsEntry {
-    fn platform_entry(&self, platform: Platform) -> Option<&PlatformEntry> {
-        match platform {
-            Platform::Telegram => self.telegram.as_ref(),
-            Platform::Discord => self.discord.as_ref(),
+/// The result of checking that a stored verification matches its
+/// presentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrityStatus {
+    /// The verification matches its presentation.
+    Valid,
+    /// The verification does not match its presentation, so it has been
+    /// changed outside of the verifier.
+    Mismatch,
+    /// The presentation has been purged or was not stored, so the
+    /// verification cannot be checked.
+    Unverifiable,
+}
+
+impl IntegrityStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            IntegrityStatus::Valid => "valid",
+            IntegrityStatus::Mismatch => "mismatch",
+            IntegrityStatus::Unverifiable => "unverifiable",
+        }
+    }
+
+    /// Parse a stored status. Unknown statuses are treated as not checked.
+    fn from_stored(status: Option<String>) -> Option<Self> {
+        match status.as_deref()? {
+            "valid" => Some(IntegrityStatus::Valid),
+            "mismatch" => Some(IntegrityStatus::Mismatch),
+            "unverifiable" => Some(IntegrityStatus::Unverifiable),
+            _ => None,
         }
     }
 }
 
-pub struct Database {
-    pool: deadpool_postgres::Pool,
+/// A username looked up using the API of a platform.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedUsername {
+    pub platform: Platform,
+    pub id: String,
+    pub username: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// All data stored about a user.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataExport {
+    #[serde(flatten)]
+    pub verification: AdminVerification,
+    pub created_at: DateTime<Utc>,
+    /// Names of the claims proven by the verification.
+    pub claims: Vec<String>,
+    pub public_name: bool,
+    pub cached_usernames: Vec<CachedUsername>,
+    /// The presentation of the verification, unless it has been purged.
+    pub presentation: Option<serde_json::Value>,
+}
+
+/// Initializer for verification entries, including the entries of the
+/// accounts table.
+pub struct VerificationsEntry {
+    /// The accounts of the verification, at most one for each platform.
+    pub accounts: Vec<PlatformEntry>,
+    /// The presentation the verification was made with. It is not stored if
+    /// user ids are hashed, since it reveals them.
+    pub presentation: Option<serde_json::Value>,
+    pub full_name: Option<FullName>,
+    /// Names of the claims proven by the presentation.
+    pub claims: Vec<String>,
+    /// Whether the full name may be shown on the public profile and badge.
+    pub public_name: bool,
 }
 
 impl VerificationsEntry {
     pub fn from_presentation(proof: &Presentation<ArCurve, Web3IdAttribute>) -> Self {
         Self {
-            telegram: None,
-            discord: None,
-            presentation: serde_json::to_value(proof).expect("Presentations can be serialized"),
+            accounts: Vec::new(),
+            presentation: Some(
+                serde_json::to_value(proof).expect("Presentations can be serialized"),
+            ),
             full_name: None,
+            claims: Vec::new(),
+            public_name: false,
         }
     }
-
-    fn insert_statement() -> String {
-        format!(
-            "INSERT INTO {VERIFICATIONS_TABLE} ({FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
-             {PRESENTATION_COLUMN}) VALUES ($1, $2, $3) RETURNING id"
-        )
-    }
 }
 
 pub struct PlatformEntry {
+    pub platform: Platform,
     pub id: String,
     pub cred_id: CredentialHolderId,
     pub username: String,
@@ -104,253 +211,136 @@ pub struct PlatformEntry {
 
 pub type DbResult<T> = anyhow::Result<T>;
 
-impl Database {
-    pub async fn connect(db_config: tokio_postgres::Config, pool_size: usize) -> DbResult<Self> {
-        let (client, connection) = db_config.connect(NoTls).await?;
-
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                tracing::error!("connection error: {}", e);
-            }
-        });
-
-        client
-            .batch_execute(include_str!("../resources/schema.sql"))
-            .await?;
-
-        let manager_config = deadpool_postgres::ManagerConfig {
-            recycling_method: deadpool_postgres::RecyclingMethod::Verified,
-        };
-
-        let manager = deadpool_postgres::Manager::from_config(db_config, NoTls, manager_config);
-        let pool = deadpool_postgres::Pool::builder(manager)
-            .create_timeout(Some(std::time::Duration::from_secs(5)))
-            .recycle_timeout(Some(std::time::Duration::from_secs(5)))
-            .wait_timeout(Some(std::time::Duration::from_secs(5)))
-            .max_size(pool_size)
-            .runtime(deadpool_postgres::Runtime::Tokio1)
-            .build()?;
-        Ok(Self { pool })
-    }
+/// The outcome of [`Storage::add_verification`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum AddOutcome {
+    /// The verification was added. Contains the accounts of each verification
+    /// that was replaced because it shared a credential with the new one.
+    Added { replaced: Vec<Vec<AccountId>> },
+    /// The user ID is already verified with a different credential, so
+    /// nothing was changed.
+    DuplicateUser(String),
+}
+
+/// Storage of verifications, challenges and cached usernames. All
+/// implementations must have the same semantics, which is checked by the
+/// conformance tests in `db/tests.rs`.
+///
+/// Every row belongs to a tenant, and a storage only sees the rows of its own
+/// tenant, so the same account can be verified independently by each tenant.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Returns a storage sharing the same database that reads and writes the
+    /// rows of the tenant.
+    fn for_tenant(&self, tenant: &str) -> Arc<dyn Storage>;
 
     /// Returns the verification for a given social media account if it exists.
-    #[tracing::instrument(level = "debug", skip(self))]
-    pub async fn get_verification(
+    async fn get_verification(
         &self,
         id: &str,
-        platform: Platform,
-    ) -> DbResult<Option<DbVerification>> {
-        tracing::debug!("Looking up verifications.");
-        let mut client = self.pool.get().await?;
-        let tx = client
-            .build_transaction()
-            .isolation_level(tokio_postgres::IsolationLevel::RepeatableRead)
-            .read_only(true)
-            .start()
-            .await?;
-
-        let table_name = platform.table_name();
-        let select_verification_id = format!(
-            "SELECT {VERIFICATION_ID_COLUMN}, {ID_COLUMN}, {USERNAME_COLUMN} FROM {table_name} \
-             WHERE {table_name}.id = $1"
-        );
-
-        let Some(platform_row) = tx.query_opt(&select_verification_id, &[&id]).await? else {
-            return Ok(None);
-        };
-        let ver_id: i64 = platform_row.try_get(VERIFICATION_ID_COLUMN)?;
-
-        // The base statement
-        let name_statement = format!(
-            "SELECT {PRESENTATION_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN} FROM \
-             {VERIFICATIONS_TABLE} WHERE {ID_COLUMN} = $1"
-        );
-        let Some(name_row) = tx.query_opt(&name_statement, &[&ver_id]).await? else {
-            return Ok(None);
-        };
-        let first_name: Option<String> = name_row.try_get(FIRST_NAME_COLUMN)?;
-        let full_name = if let Some(first_name) = first_name {
-            let last_name: String = name_row.try_get(LAST_NAME_COLUMN)?;
-            Some(FullName {
-                first_name,
-                last_name,
-            })
-        } else {
-            None
-        };
-
-        let id = platform_row.try_get(ID_COLUMN)?;
-        let username = platform_row.try_get(USERNAME_COLUMN)?;
-        let acc = DbAccount {
-            platform,
-            id,
-            username,
-        };
-
-        let mut accounts = vec![acc];
-
-        for p in Platform::SUPPORTED_PLATFORMS {
-            if p != platform {
-                let Some(row) = tx
-                    .query_opt(
-                        &format!(
-                            "SELECT {ID_COLUMN}, {USERNAME_COLUMN} FROM {} WHERE \
-                         {VERIFICATION_ID_COLUMN}=$1",
-                            p.table_name()
-                        ),
-                        &[&ver_id],
-                    )
-                    .await?
-                else {
-                    continue;
-                };
-                let id = row.try_get(ID_COLUMN)?;
-                let username = row.try_get(USERNAME_COLUMN)?;
-                let acc = DbAccount {
-                    platform: p,
-                    id,
-                    username,
-                };
-                accounts.push(acc);
-            }
-        }
-
-        let presentation = name_row.get::<_, serde_json::Value>(PRESENTATION_COLUMN);
-        let presentation =
-            serde_json::from_value(presentation).expect("presentations can be deserialized");
+        platform: &Platform,
+    ) -> DbResult<Option<DbVerification>>;
+
+    /// Attempt to add a verification. Existing verifications with any of the
+    /// credentials of the entry are replaced. In case the user already exists
+    /// and is identified by a different credential holder ID this will return
+    /// the user ID of the clashing user, and will not do any updates.
+    async fn add_verification(&self, entry: VerificationsEntry) -> DbResult<AddOutcome>;
+
+    /// Remove the verification with the credential, together with its
+    /// accounts, claims and cached usernames. Return the accounts of the
+    /// removed verification, or `None` if there was none.
+    async fn remove_verification(
+        &self,
+        cred_id: &CredentialHolderId,
+        platform: &Platform,
+    ) -> DbResult<Option<Vec<AccountId>>>;
+
+    /// Store a new challenge that can be used once for the operation until
+    /// `ttl` has passed. Expired challenges are removed at the same time.
+    /// Returns `false`, and does not store the challenge, if the tenant
+    /// already has `max_outstanding` unexpired challenges.
+    async fn add_challenge(
+        &self,
+        challenge: &[u8],
+        operation: &str,
+        ttl: std::time::Duration,
+        max_outstanding: u64,
+    ) -> DbResult<bool>;
+
+    /// Use a challenge for the operation. Returns whether the challenge
+    /// existed and had not expired. A challenge can only be used once.
+    async fn use_challenge(&self, challenge: &[u8], operation: &str) -> DbResult<bool>;
+
+    /// Get the cached username of an account, and whether it was looked up
+    /// less than `ttl` ago.
+    async fn get_cached_username(
+        &self,
+        platform: &Platform,
+        id: &str,
+        ttl: std::time::Duration,
+    ) -> DbResult<Option<(String, bool)>>;
 
-        Ok(Some(DbVerification {
-            accounts,
-            full_name,
-            presentation,
-        }))
-    }
+    /// Store a username that was looked up for an account.
+    async fn cache_username(&self, platform: &Platform, id: &str, username: &str) -> DbResult<()>;
 
-    /// Attempt to add a verification. In case the user already exist and is
-    /// identified by a different credential holder ID this will return the
-    /// user ID of the clashing user, and will not do any updates.
-    pub async fn add_verification(&self, entry: VerificationsEntry) -> DbResult<Option<String>> {
-        let mut client = self.pool.get().await?;
-        let transaction = client.transaction().await?;
-
-        // Clear pre-existing verifications with overlapping credentials;
-        for platform in Platform::SUPPORTED_PLATFORMS {
-            let Some(entry) = entry.platform_entry(platform) else {
-                continue;
-            };
-            let table_name = platform.table_name();
-            let delete_statement = format!(
-                "DELETE FROM {VERIFICATIONS_TABLE} WHERE {VERIFICATIONS_TABLE}.{ID_COLUMN} IN \
-                 (SELECT {table_name}.{VERIFICATION_ID_COLUMN} FROM {table_name} WHERE \
-                 {table_name}.{CRED_ID_COLUMN} = $1)"
-            );
-            tracing::debug!(
-                "Will attempt to remove credential with id {} ({table_name}) from the database.",
-                entry.cred_id
-            );
-            let rows = transaction
-                .execute(
-                    &delete_statement,
-                    &[entry.cred_id.public_key.as_bytes() as &(dyn ToSql + Sync)],
-                )
-                .await?;
-            if rows > 0 {
-                tracing::debug!("Deleted {rows} rows from {VERIFICATIONS_TABLE}");
-            }
-        }
+    /// List accounts in order of platform and user id, starting after the
+    /// given account. This is used to go through all accounts in batches.
+    async fn list_accounts(
+        &self,
+        after: Option<(&Platform, &str)>,
+        limit: i64,
+    ) -> DbResult<Vec<DbAccountCredential>>;
 
-        let insert_statement = VerificationsEntry::insert_statement();
-
-        let values: [&(dyn ToSql + Sync); 3] = [
-            &entry.full_name.as_ref().map(|n| &n.first_name),
-            &entry.full_name.as_ref().map(|n| &n.last_name),
-            &entry.presentation,
-        ];
-
-        // Run an insert, retrieve new verification id
-        let verification_id: i64 = transaction
-            .query_one(&insert_statement, &values)
-            .await?
-            .try_get(0)?;
-
-        if let Some(telegram) = entry.telegram {
-            if let Some(user_id) =
-                add_platform_entry(&transaction, Platform::Telegram, telegram, verification_id)
-                    .await?
-            {
-                tracing::debug!(
-                    "Refusing to add new Telegram verification due to clash of user id {}.",
-                    user_id
-                );
-                transaction.rollback().await?;
-                return Ok(Some(user_id));
-            }
-        }
-        if let Some(discord) = entry.discord {
-            if let Some(user_id) =
-                add_platform_entry(&transaction, Platform::Discord, discord, verification_id)
-                    .await?
-            {
-                tracing::debug!(
-                    "Refusing to add new Discord verification due to clash of user id {}.",
-                    user_id
-                );
-                transaction.rollback().await?;
-                return Ok(Some(user_id));
-            }
-        }
+    /// Search verifications, in order of their id, starting after the given
+    /// id.
+    async fn search_verifications(
+        &self,
+        filter: &VerificationFilter,
+        after: Option<i64>,
+        limit: i64,
+    ) -> DbResult<Vec<AdminVerification>>;
+
+    /// Get a verification by its id, together with its stored presentation
+    /// unless it has been purged.
+    async fn get_verification_by_id(
+        &self,
+        id: i64,
+    ) -> DbResult<Option<(AdminVerification, Option<serde_json::Value>)>>;
 
-        transaction.commit().await?;
-        Ok(None)
-    }
+    /// Delete a verification by its id. Returns its accounts, or `None` if it
+    /// did not exist.
+    async fn delete_verification(&self, id: i64) -> DbResult<Option<Vec<AccountId>>>;
 
-    /// Remove the verification. Return if anything was removed.
-    pub async fn remove_verification(
+    /// Export all data stored about the user of the given credential, i.e., the
+    /// verification with the account of the credential, if any.
+    async fn export_data(
         &self,
         cred_id: &CredentialHolderId,
-        platform: Platform,
-    ) -> DbResult<bool> {
-        let mut client = self.pool.get().await?;
-        let transaction = client.transaction().await?;
-
-        // Then delete the verification row.
-        let statement = format!(
-            "DELETE FROM {VERIFICATIONS_TABLE} WHERE {ID_COLUMN} IN (SELECT \
-             {VERIFICATION_ID_COLUMN} FROM {} WHERE {CRED_ID_COLUMN} = $1) RETURNING {ID_COLUMN}",
-            platform.table_name()
-        );
-        let cred_id = cred_id.public_key.as_bytes();
-        // The column VERIFICATION_ID_COLUMN is unique so at most one will be returned
-        let r = transaction.query_opt(&statement, &[cred_id]).await?;
-        transaction.commit().await?;
-        Ok(r.is_some())
-    }
+        platform: &Platform,
+    ) -> DbResult<Option<DataExport>>;
+
+    /// Remove the presentations of verifications created more than `retention`
+    /// ago. The rest of the verifications is kept. Returns the number of
+    /// purged presentations.
+    async fn purge_presentations(&self, retention: std::time::Duration) -> DbResult<u64>;
+
+    /// Whether an account of any tenant has a user id that is not a
+    /// pseudonym, i.e., the hex encoded HMAC-SHA256 of the id. Such accounts
+    /// were stored without an id hashing key.
+    async fn has_unhashed_ids(&self) -> DbResult<bool>;
+
+    /// Record the result of the integrity check of the verification with the
+    /// given id.
+    async fn set_integrity_status(&self, id: i64, status: IntegrityStatus) -> DbResult<()>;
 }
 
-/// Attempt to add a platform entry. If an entry already exists return the
-/// `user_id` and do no updates.
-async fn add_platform_entry(
-    transaction: &tokio_postgres::Transaction<'_>,
-    platform: Platform,
-    entry: PlatformEntry,
-    verification_id: i64,
-) -> DbResult<Option<String>> {
-    let statement = platform.insert_statement();
-
-    let values = [
-        &entry.id as &(dyn ToSql + Sync),
-        entry.cred_id.public_key.as_bytes() as &(dyn ToSql + Sync),
-        &verification_id as &(dyn ToSql + Sync),
-        &entry.username,
-    ];
-
-    if transaction
-        .query_opt(statement.as_str(), &values)
-        .await?
-        .is_some()
-    {
-        Ok(None)
-    } else {
-        Ok(Some(entry.id))
-    }
+/// A pattern for `LIKE` and `ILIKE`, with `\` as the escape character, that
+/// matches strings containing `s`.
+fn contains_pattern(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
 }
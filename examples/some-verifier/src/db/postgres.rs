@@ -0,0 +1,707 @@
+//! The Postgres implementation of [`Storage`]. The schema is evolved by the
+//! migrations in [`crate::migrations`].
+use super::{
+    contains_pattern, AddOutcome, AdminAccount, AdminVerification, CachedUsername, DataExport,
+    DbAccount, DbAccountCredential, DbResult, DbVerification, IntegrityStatus, PlatformEntry,
+    Storage, VerificationFilter, VerificationsEntry, ACCOUNTS_TABLE, CHALLENGES_TABLE,
+    CHALLENGE_COLUMN, CLAIMS_TABLE, CREATED_AT_COLUMN, CRED_ID_COLUMN, DEFAULT_TENANT,
+    EXPIRES_AT_COLUMN, FETCHED_AT_COLUMN, FIRST_NAME_COLUMN, ID_COLUMN, INTEGRITY_STATUS_COLUMN,
+    LAST_NAME_COLUMN, NAME_COLUMN, OPERATION_COLUMN, PLATFORM_COLUMN, PRESENTATION_COLUMN,
+    PUBLIC_NAME_COLUMN, TENANT_COLUMN, USERNAME_CACHE_TABLE, USERNAME_COLUMN, VERIFICATIONS_TABLE,
+    VERIFICATION_ID_COLUMN,
+};
+use async_trait::async_trait;
+use concordium_rust_sdk::{common, web3id::CredentialHolderId};
+use some_verifier_lib::{webhook::AccountId, FullName, Platform};
+use std::sync::Arc;
+use tokio_postgres::{types::ToSql, NoTls};
+
+pub struct PostgresStorage {
+    pool: deadpool_postgres::Pool,
+    /// The tenant whose rows are read and written.
+    tenant: Arc<str>,
+}
+
+/// Open a single connection to the database, outside of the pool.
+async fn connect_client(db_config: &tokio_postgres::Config) -> DbResult<tokio_postgres::Client> {
+    let (client, connection) = db_config.connect(NoTls).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::error!("connection error: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+/// Apply all pending migrations to the database.
+pub async fn migrate(db_config: &tokio_postgres::Config) -> DbResult<()> {
+    let mut client = connect_client(db_config).await?;
+    crate::migrations::migrate(&mut client).await
+}
+
+impl PostgresStorage {
+    /// Connect to the database. This fails if the schema is not at the version
+    /// expected by the verifier.
+    pub async fn connect(db_config: tokio_postgres::Config, pool_size: usize) -> DbResult<Self> {
+        let client = connect_client(&db_config).await?;
+        crate::migrations::check(&client).await?;
+
+        let manager_config = deadpool_postgres::ManagerConfig {
+            recycling_method: deadpool_postgres::RecyclingMethod::Verified,
+        };
+
+        let manager = deadpool_postgres::Manager::from_config(db_config, NoTls, manager_config);
+        let pool = deadpool_postgres::Pool::builder(manager)
+            .create_timeout(Some(std::time::Duration::from_secs(5)))
+            .recycle_timeout(Some(std::time::Duration::from_secs(5)))
+            .wait_timeout(Some(std::time::Duration::from_secs(5)))
+            .max_size(pool_size)
+            .runtime(deadpool_postgres::Runtime::Tokio1)
+            .build()?;
+        Ok(Self {
+            pool,
+            tenant: DEFAULT_TENANT.into(),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    fn for_tenant(&self, tenant: &str) -> Arc<dyn Storage> {
+        Arc::new(Self {
+            pool: self.pool.clone(),
+            tenant: tenant.into(),
+        })
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn get_verification(
+        &self,
+        id: &str,
+        platform: &Platform,
+    ) -> DbResult<Option<DbVerification>> {
+        tracing::debug!("Looking up verifications.");
+        let mut client = self.pool.get().await?;
+        let tx = client
+            .build_transaction()
+            .isolation_level(tokio_postgres::IsolationLevel::RepeatableRead)
+            .read_only(true)
+            .start()
+            .await?;
+
+        let select_verification_id = format!(
+            "SELECT {VERIFICATION_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE {TENANT_COLUMN} = $1 AND \
+             {PLATFORM_COLUMN} = $2 AND {ID_COLUMN} = $3"
+        );
+
+        let tenant: &str = &self.tenant;
+        let Some(platform_row) = tx
+            .query_opt(&select_verification_id, &[&tenant, &platform.name(), &id])
+            .await?
+        else {
+            return Ok(None);
+        };
+        let ver_id: i64 = platform_row.try_get(VERIFICATION_ID_COLUMN)?;
+
+        // The base statement
+        let name_statement = format!(
+            "SELECT {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, {PUBLIC_NAME_COLUMN}, \
+             {PRESENTATION_COLUMN} FROM {VERIFICATIONS_TABLE} WHERE {ID_COLUMN} = $1"
+        );
+        let Some(name_row) = tx.query_opt(&name_statement, &[&ver_id]).await? else {
+            return Ok(None);
+        };
+        let first_name: Option<String> = name_row.try_get(FIRST_NAME_COLUMN)?;
+        let full_name = if let Some(first_name) = first_name {
+            let last_name: String = name_row.try_get(LAST_NAME_COLUMN)?;
+            Some(FullName {
+                first_name,
+                last_name,
+            })
+        } else {
+            None
+        };
+        let public_name: bool = name_row.try_get(PUBLIC_NAME_COLUMN)?;
+        let presentation: Option<serde_json::Value> = name_row.try_get(PRESENTATION_COLUMN)?;
+
+        // All accounts of the verification, with the account that was looked up
+        // first.
+        let accounts_statement = format!(
+            "SELECT {PLATFORM_COLUMN}, {ID_COLUMN}, {USERNAME_COLUMN}, {CRED_ID_COLUMN} FROM \
+             {ACCOUNTS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = $1 ORDER BY {PLATFORM_COLUMN} = $2 \
+             DESC, {PLATFORM_COLUMN}"
+        );
+        let accounts = tx
+            .query(&accounts_statement, &[&ver_id, &platform.name()])
+            .await?
+            .into_iter()
+            .map(|row| {
+                let cred_id: Vec<u8> = row.try_get(CRED_ID_COLUMN)?;
+                Ok(DbAccount {
+                    platform: Platform::new(row.try_get::<_, String>(PLATFORM_COLUMN)?),
+                    id: row.try_get(ID_COLUMN)?,
+                    username: row.try_get(USERNAME_COLUMN)?,
+                    cred_id: common::from_bytes(&mut cred_id.as_slice())?,
+                })
+            })
+            .collect::<DbResult<Vec<_>>>()?;
+
+        let claims_statement = format!(
+            "SELECT {NAME_COLUMN} FROM {CLAIMS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = $1 ORDER \
+             BY {NAME_COLUMN}"
+        );
+        let claims = tx
+            .query(&claims_statement, &[&ver_id])
+            .await?
+            .into_iter()
+            .map(|row| row.try_get(NAME_COLUMN))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(Some(DbVerification {
+            id: ver_id,
+            accounts,
+            full_name,
+            claims,
+            public_name,
+            presentation,
+        }))
+    }
+
+    async fn add_verification(&self, entry: VerificationsEntry) -> DbResult<AddOutcome> {
+        let mut client = self.pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let tenant: &str = &self.tenant;
+        // Clear pre-existing verifications with overlapping credentials.
+        let mut replaced = Vec::new();
+        for entry in &entry.accounts {
+            tracing::debug!(
+                "Will attempt to remove credential with id {} ({}) from the database.",
+                entry.cred_id,
+                entry.platform
+            );
+            let Some(id) =
+                find_verification_id(&transaction, tenant, &entry.cred_id, &entry.platform).await?
+            else {
+                continue;
+            };
+            // The verification is already gone if it had another credential of the entry.
+            if let Some(accounts) = remove_by_id(&transaction, tenant, id).await? {
+                tracing::debug!("Deleted verification {id} from {VERIFICATIONS_TABLE}");
+                replaced.push(accounts);
+            }
+        }
+
+        let insert_statement = insert_statement();
+
+        let values: [&(dyn ToSql + Sync); 5] = [
+            &entry.full_name.as_ref().map(|n| &n.first_name),
+            &entry.full_name.as_ref().map(|n| &n.last_name),
+            &entry.presentation,
+            &entry.public_name,
+            &tenant,
+        ];
+
+        // Run an insert, retrieve new verification id
+        let verification_id: i64 = transaction
+            .query_one(&insert_statement, &values)
+            .await?
+            .try_get(0)?;
+
+        let claim_statement = format!(
+            "INSERT INTO {CLAIMS_TABLE} ({VERIFICATION_ID_COLUMN}, {NAME_COLUMN}) VALUES ($1, $2)"
+        );
+        for claim in &entry.claims {
+            transaction
+                .execute(&claim_statement, &[&verification_id, claim])
+                .await?;
+        }
+
+        for account in entry.accounts {
+            let platform = account.platform.clone();
+            if let Some(user_id) =
+                add_platform_entry(&transaction, tenant, account, verification_id).await?
+            {
+                tracing::debug!(
+                    "Refusing to add new {platform} verification due to clash of user id {}.",
+                    user_id
+                );
+                transaction.rollback().await?;
+                return Ok(AddOutcome::DuplicateUser(user_id));
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(AddOutcome::Added { replaced })
+    }
+
+    async fn add_challenge(
+        &self,
+        challenge: &[u8],
+        operation: &str,
+        ttl: std::time::Duration,
+        max_outstanding: u64,
+    ) -> DbResult<bool> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                &format!("DELETE FROM {CHALLENGES_TABLE} WHERE {EXPIRES_AT_COLUMN} < now()"),
+                &[],
+            )
+            .await?;
+        let statement = format!(
+            "INSERT INTO {CHALLENGES_TABLE} ({CHALLENGE_COLUMN}, {OPERATION_COLUMN}, \
+             {EXPIRES_AT_COLUMN}, {TENANT_COLUMN}) SELECT $1, $2, now() + make_interval(secs => \
+             $3), $4 WHERE (SELECT COUNT(*) FROM {CHALLENGES_TABLE} WHERE {TENANT_COLUMN} = $4) < \
+             $5"
+        );
+        let tenant: &str = &self.tenant;
+        let max_outstanding = i64::try_from(max_outstanding).unwrap_or(i64::MAX);
+        let inserted = client
+            .execute(
+                &statement,
+                &[
+                    &challenge,
+                    &operation,
+                    &ttl.as_secs_f64(),
+                    &tenant,
+                    &max_outstanding,
+                ],
+            )
+            .await?;
+        Ok(inserted == 1)
+    }
+
+    async fn use_challenge(&self, challenge: &[u8], operation: &str) -> DbResult<bool> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "DELETE FROM {CHALLENGES_TABLE} WHERE {CHALLENGE_COLUMN} = $1 AND {OPERATION_COLUMN} \
+             = $2 AND {TENANT_COLUMN} = $3 RETURNING {EXPIRES_AT_COLUMN} > now() AS valid"
+        );
+        let tenant: &str = &self.tenant;
+        let row = client
+            .query_opt(&statement, &[&challenge, &operation, &tenant])
+            .await?;
+        Ok(match row {
+            Some(row) => row.try_get("valid")?,
+            None => false,
+        })
+    }
+
+    async fn get_cached_username(
+        &self,
+        platform: &Platform,
+        id: &str,
+        ttl: std::time::Duration,
+    ) -> DbResult<Option<(String, bool)>> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "SELECT {USERNAME_COLUMN}, {FETCHED_AT_COLUMN} > now() - make_interval(secs => $3) AS \
+             fresh FROM {USERNAME_CACHE_TABLE} WHERE {PLATFORM_COLUMN} = $1 AND {ID_COLUMN} = $2 \
+             AND {TENANT_COLUMN} = $4"
+        );
+        let tenant: &str = &self.tenant;
+        let row = client
+            .query_opt(
+                &statement,
+                &[&platform.name(), &id, &ttl.as_secs_f64(), &tenant],
+            )
+            .await?;
+        row.map(|row| Ok((row.try_get(USERNAME_COLUMN)?, row.try_get("fresh")?)))
+            .transpose()
+    }
+
+    async fn cache_username(&self, platform: &Platform, id: &str, username: &str) -> DbResult<()> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "INSERT INTO {USERNAME_CACHE_TABLE} ({PLATFORM_COLUMN}, {ID_COLUMN}, \
+             {USERNAME_COLUMN}, {TENANT_COLUMN}) VALUES ($1, $2, $3, $4) ON CONFLICT ON CONSTRAINT \
+             {USERNAME_CACHE_TABLE}_pkey DO UPDATE SET {USERNAME_COLUMN} = EXCLUDED.{USERNAME_COLUMN}, \
+             {FETCHED_AT_COLUMN} = now()"
+        );
+        let tenant: &str = &self.tenant;
+        client
+            .execute(&statement, &[&platform.name(), &id, &username, &tenant])
+            .await?;
+        Ok(())
+    }
+
+    async fn list_accounts(
+        &self,
+        after: Option<(&Platform, &str)>,
+        limit: i64,
+    ) -> DbResult<Vec<DbAccountCredential>> {
+        let client = self.pool.get().await?;
+        let (platform, id) = after.map_or(("", ""), |(p, id)| (p.name(), id));
+        let statement = format!(
+            "SELECT {PLATFORM_COLUMN}, {ID_COLUMN}, {CRED_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE \
+             {TENANT_COLUMN} = $4 AND ({PLATFORM_COLUMN}, {ID_COLUMN}) > ($1, $2) ORDER BY \
+             {PLATFORM_COLUMN}, {ID_COLUMN} LIMIT $3"
+        );
+        let tenant: &str = &self.tenant;
+        client
+            .query(&statement, &[&platform, &id, &limit, &tenant])
+            .await?
+            .into_iter()
+            .map(|row| {
+                let cred_id: Vec<u8> = row.try_get(CRED_ID_COLUMN)?;
+                Ok(DbAccountCredential {
+                    platform: Platform::new(row.try_get::<_, String>(PLATFORM_COLUMN)?),
+                    id: row.try_get(ID_COLUMN)?,
+                    cred_id: common::from_bytes(&mut cred_id.as_slice())?,
+                })
+            })
+            .collect()
+    }
+
+    async fn search_verifications(
+        &self,
+        filter: &VerificationFilter,
+        after: Option<i64>,
+        limit: i64,
+    ) -> DbResult<Vec<AdminVerification>> {
+        let client = self.pool.get().await?;
+        let has_account_filter = filter.platform.is_some()
+            || filter.id.is_some()
+            || filter.username.is_some()
+            || filter.cred_id.is_some();
+        let statement = format!(
+            "SELECT {ID_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+             {INTEGRITY_STATUS_COLUMN} FROM {VERIFICATIONS_TABLE} v WHERE ($1::INT8 IS NULL OR v.{ID_COLUMN} > $1) AND (NOT \
+             $2::BOOL OR EXISTS (SELECT FROM {ACCOUNTS_TABLE} a WHERE a.{VERIFICATION_ID_COLUMN} \
+             = v.{ID_COLUMN} AND ($3::VARCHAR IS NULL OR a.{PLATFORM_COLUMN} = $3) AND \
+             ($4::VARCHAR IS NULL OR a.{ID_COLUMN} = $4) AND ($5::VARCHAR IS NULL OR \
+             a.{USERNAME_COLUMN} ILIKE $5) AND ($6::BYTEA IS NULL OR a.{CRED_ID_COLUMN} = $6))) \
+             AND ($7::VARCHAR IS NULL OR (v.{FIRST_NAME_COLUMN} || ' ' || v.{LAST_NAME_COLUMN}) \
+             ILIKE $7) AND v.{TENANT_COLUMN} = $9 ORDER BY v.{ID_COLUMN} LIMIT $8"
+        );
+        let username = filter.username.as_deref().map(contains_pattern);
+        let name = filter.name.as_deref().map(contains_pattern);
+        let tenant: &str = &self.tenant;
+        let rows = client
+            .query(
+                &statement,
+                &[
+                    &after,
+                    &has_account_filter,
+                    &filter.platform,
+                    &filter.id,
+                    &username,
+                    &filter.cred_id,
+                    &name,
+                    &limit,
+                    &tenant,
+                ],
+            )
+            .await?;
+
+        let mut verifications = rows
+            .iter()
+            .map(admin_verification_from_row)
+            .collect::<DbResult<Vec<_>>>()?;
+        load_accounts(&client, &mut verifications).await?;
+        Ok(verifications)
+    }
+
+    async fn get_verification_by_id(
+        &self,
+        id: i64,
+    ) -> DbResult<Option<(AdminVerification, Option<serde_json::Value>)>> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "SELECT {ID_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+             {INTEGRITY_STATUS_COLUMN}, {PRESENTATION_COLUMN} FROM {VERIFICATIONS_TABLE} WHERE \
+             {ID_COLUMN} = $1 AND {TENANT_COLUMN} = $2"
+        );
+        let tenant: &str = &self.tenant;
+        let Some(row) = client.query_opt(&statement, &[&id, &tenant]).await? else {
+            return Ok(None);
+        };
+        let presentation: Option<serde_json::Value> = row.try_get(PRESENTATION_COLUMN)?;
+        let mut verification = [admin_verification_from_row(&row)?];
+        load_accounts(&client, &mut verification).await?;
+        let [verification] = verification;
+        Ok(Some((verification, presentation)))
+    }
+
+    async fn delete_verification(&self, id: i64) -> DbResult<Option<Vec<AccountId>>> {
+        let mut client = self.pool.get().await?;
+        let transaction = client.transaction().await?;
+        let accounts = remove_by_id(&transaction, &self.tenant, id).await?;
+        transaction.commit().await?;
+        Ok(accounts)
+    }
+
+    async fn export_data(
+        &self,
+        cred_id: &CredentialHolderId,
+        platform: &Platform,
+    ) -> DbResult<Option<DataExport>> {
+        let client = self.pool.get().await?;
+
+        let statement = format!(
+            "SELECT {ID_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+             {INTEGRITY_STATUS_COLUMN}, {PRESENTATION_COLUMN}, {CREATED_AT_COLUMN}, \
+             {PUBLIC_NAME_COLUMN} FROM {VERIFICATIONS_TABLE} WHERE \
+             {TENANT_COLUMN} = $3 AND {ID_COLUMN} IN (SELECT {VERIFICATION_ID_COLUMN} FROM \
+             {ACCOUNTS_TABLE} WHERE {PLATFORM_COLUMN} = $1 AND {CRED_ID_COLUMN} = $2)"
+        );
+        let tenant: &str = &self.tenant;
+        let Some(row) = client
+            .query_opt(
+                &statement,
+                &[
+                    &platform.name() as &(dyn ToSql + Sync),
+                    cred_id.public_key.as_bytes() as &(dyn ToSql + Sync),
+                    &tenant,
+                ],
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let mut verification = [admin_verification_from_row(&row)?];
+        load_accounts(&client, &mut verification).await?;
+        let [verification] = verification;
+
+        let claims_statement = format!(
+            "SELECT {NAME_COLUMN} FROM {CLAIMS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = $1 ORDER \
+             BY {NAME_COLUMN}"
+        );
+        let claims = client
+            .query(&claims_statement, &[&verification.id])
+            .await?
+            .into_iter()
+            .map(|row| row.try_get(NAME_COLUMN))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        let cache_statement = format!(
+            "SELECT c.{PLATFORM_COLUMN}, c.{ID_COLUMN}, c.{USERNAME_COLUMN}, \
+             c.{FETCHED_AT_COLUMN} FROM {USERNAME_CACHE_TABLE} c JOIN {ACCOUNTS_TABLE} a USING \
+             ({TENANT_COLUMN}, {PLATFORM_COLUMN}, {ID_COLUMN}) WHERE a.{VERIFICATION_ID_COLUMN} = \
+             $1 ORDER BY c.{PLATFORM_COLUMN}"
+        );
+        let cached_usernames = client
+            .query(&cache_statement, &[&verification.id])
+            .await?
+            .into_iter()
+            .map(|row| {
+                Ok(CachedUsername {
+                    platform: Platform::new(row.try_get::<_, String>(PLATFORM_COLUMN)?),
+                    id: row.try_get(ID_COLUMN)?,
+                    username: row.try_get(USERNAME_COLUMN)?,
+                    fetched_at: row.try_get(FETCHED_AT_COLUMN)?,
+                })
+            })
+            .collect::<DbResult<Vec<_>>>()?;
+
+        Ok(Some(DataExport {
+            created_at: row.try_get(CREATED_AT_COLUMN)?,
+            presentation: row.try_get(PRESENTATION_COLUMN)?,
+            public_name: row.try_get(PUBLIC_NAME_COLUMN)?,
+            verification,
+            claims,
+            cached_usernames,
+        }))
+    }
+
+    async fn purge_presentations(&self, retention: std::time::Duration) -> DbResult<u64> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "UPDATE {VERIFICATIONS_TABLE} SET {PRESENTATION_COLUMN} = NULL WHERE \
+             {PRESENTATION_COLUMN} IS NOT NULL AND {CREATED_AT_COLUMN} < now() - \
+             make_interval(secs => $1) AND {TENANT_COLUMN} = $2"
+        );
+        let tenant: &str = &self.tenant;
+        Ok(client
+            .execute(&statement, &[&retention.as_secs_f64(), &tenant])
+            .await?)
+    }
+
+    async fn has_unhashed_ids(&self) -> DbResult<bool> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "SELECT EXISTS (SELECT 1 FROM {ACCOUNTS_TABLE} WHERE {ID_COLUMN} !~ \
+             '^[0-9a-f]{{64}}$')"
+        );
+        Ok(client.query_one(&statement, &[]).await?.try_get(0)?)
+    }
+
+    async fn set_integrity_status(&self, id: i64, status: IntegrityStatus) -> DbResult<()> {
+        let client = self.pool.get().await?;
+        let statement = format!(
+            "UPDATE {VERIFICATIONS_TABLE} SET {INTEGRITY_STATUS_COLUMN} = $1 WHERE {ID_COLUMN} = \
+             $2 AND {TENANT_COLUMN} = $3"
+        );
+        let tenant: &str = &self.tenant;
+        client
+            .execute(&statement, &[&status.as_str(), &id, &tenant])
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_verification(
+        &self,
+        cred_id: &CredentialHolderId,
+        platform: &Platform,
+    ) -> DbResult<Option<Vec<AccountId>>> {
+        let mut client = self.pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let tenant: &str = &self.tenant;
+        let accounts = match find_verification_id(&transaction, tenant, cred_id, platform).await? {
+            Some(id) => remove_by_id(&transaction, tenant, id).await?,
+            None => None,
+        };
+        transaction.commit().await?;
+        Ok(accounts)
+    }
+}
+
+fn insert_statement() -> String {
+    format!(
+        "INSERT INTO {VERIFICATIONS_TABLE} ({FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+         {PRESENTATION_COLUMN}, {PUBLIC_NAME_COLUMN}, {TENANT_COLUMN}) VALUES ($1, $2, $3, $4, $5) \
+         RETURNING id"
+    )
+}
+
+/// Attempt to add a platform entry. If an entry already exists return the
+/// `user_id` and do no updates.
+async fn add_platform_entry(
+    transaction: &tokio_postgres::Transaction<'_>,
+    tenant: &str,
+    entry: PlatformEntry,
+    verification_id: i64,
+) -> DbResult<Option<String>> {
+    let statement = format!(
+        "INSERT INTO {ACCOUNTS_TABLE} ({PLATFORM_COLUMN}, {ID_COLUMN}, {CRED_ID_COLUMN}, \
+         {VERIFICATION_ID_COLUMN}, {USERNAME_COLUMN}, {TENANT_COLUMN}) VALUES ($1, $2, $3, $4, $5, \
+         $6) ON CONFLICT ON CONSTRAINT {ACCOUNTS_TABLE}_pkey DO NOTHING RETURNING {ID_COLUMN}"
+    );
+
+    let values = [
+        &entry.platform.name() as &(dyn ToSql + Sync),
+        &entry.id as &(dyn ToSql + Sync),
+        entry.cred_id.public_key.as_bytes() as &(dyn ToSql + Sync),
+        &verification_id as &(dyn ToSql + Sync),
+        &entry.username,
+        &tenant,
+    ];
+
+    if transaction
+        .query_opt(statement.as_str(), &values)
+        .await?
+        .is_some()
+    {
+        Ok(None)
+    } else {
+        Ok(Some(entry.id))
+    }
+}
+
+fn admin_verification_from_row(row: &tokio_postgres::Row) -> DbResult<AdminVerification> {
+    let first_name: Option<String> = row.try_get(FIRST_NAME_COLUMN)?;
+    let last_name: Option<String> = row.try_get(LAST_NAME_COLUMN)?;
+    Ok(AdminVerification {
+        id: row.try_get(ID_COLUMN)?,
+        full_name: first_name
+            .zip(last_name)
+            .map(|(first_name, last_name)| FullName {
+                first_name,
+                last_name,
+            }),
+        accounts: Vec::new(),
+        integrity_status: IntegrityStatus::from_stored(row.try_get(INTEGRITY_STATUS_COLUMN)?),
+    })
+}
+
+/// Add the accounts of the verifications.
+async fn load_accounts(
+    client: &tokio_postgres::Client,
+    verifications: &mut [AdminVerification],
+) -> DbResult<()> {
+    let ids: Vec<i64> = verifications.iter().map(|v| v.id).collect();
+    let statement = format!(
+        "SELECT {VERIFICATION_ID_COLUMN}, {PLATFORM_COLUMN}, {ID_COLUMN}, {USERNAME_COLUMN}, \
+         {CRED_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = ANY($1) ORDER BY \
+         {PLATFORM_COLUMN}"
+    );
+    for row in client.query(&statement, &[&ids]).await? {
+        let verification_id: i64 = row.try_get(VERIFICATION_ID_COLUMN)?;
+        let cred_id: Vec<u8> = row.try_get(CRED_ID_COLUMN)?;
+        let account = AdminAccount {
+            platform: Platform::new(row.try_get::<_, String>(PLATFORM_COLUMN)?),
+            id: row.try_get(ID_COLUMN)?,
+            username: row.try_get(USERNAME_COLUMN)?,
+            cred_id: hex::encode(cred_id),
+        };
+        if let Some(v) = verifications.iter_mut().find(|v| v.id == verification_id) {
+            v.accounts.push(account);
+        }
+    }
+    Ok(())
+}
+
+/// Find the id of the tenant's verification with the credential on the
+/// platform.
+async fn find_verification_id(
+    transaction: &deadpool_postgres::Transaction<'_>,
+    tenant: &str,
+    cred_id: &CredentialHolderId,
+    platform: &Platform,
+) -> DbResult<Option<i64>> {
+    // The credential ID is unique for each platform so at most one will be returned
+    let statement = format!(
+        "SELECT {VERIFICATION_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE {TENANT_COLUMN} = $1 AND \
+         {PLATFORM_COLUMN} = $2 AND {CRED_ID_COLUMN} = $3"
+    );
+    let row = transaction
+        .query_opt(
+            &statement,
+            &[
+                &tenant,
+                &platform.name() as &(dyn ToSql + Sync),
+                cred_id.public_key.as_bytes() as &(dyn ToSql + Sync),
+            ],
+        )
+        .await?;
+    row.map(|row| row.try_get(VERIFICATION_ID_COLUMN))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Delete the tenant's verification with the id, and return its accounts, or
+/// `None` if it did not exist. The accounts are collected first, since they
+/// are removed with the verification.
+async fn remove_by_id(
+    transaction: &deadpool_postgres::Transaction<'_>,
+    tenant: &str,
+    id: i64,
+) -> DbResult<Option<Vec<AccountId>>> {
+    let statement = format!(
+        "SELECT {PLATFORM_COLUMN}, {ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE \
+         {VERIFICATION_ID_COLUMN} = $1 ORDER BY {PLATFORM_COLUMN} FOR UPDATE"
+    );
+    let accounts = transaction
+        .query(&statement, &[&id])
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(AccountId {
+                platform: Platform::new(row.try_get::<_, String>(PLATFORM_COLUMN)?),
+                id: row.try_get(ID_COLUMN)?,
+            })
+        })
+        .collect::<DbResult<Vec<_>>>()?;
+
+    let statement = format!(
+        "DELETE FROM {VERIFICATIONS_TABLE} WHERE {ID_COLUMN} = $1 AND {TENANT_COLUMN} = $2"
+    );
+    if transaction.execute(&statement, &[&id, &tenant]).await? > 0 {
+        Ok(Some(accounts))
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,797 @@
+//! The SQLite implementation of [`Storage`], for running the verifier without
+//! a database server. Timestamps are stored as unix times in microseconds,
+//! and presentations as JSON text. Statements are run on a blocking thread,
+//! one at a time.
+use super::{
+    contains_pattern, AddOutcome, AdminAccount, AdminVerification, CachedUsername, DataExport,
+    DbAccount, DbAccountCredential, DbResult, DbVerification, IntegrityStatus, PlatformEntry,
+    Storage, VerificationFilter, VerificationsEntry, ACCOUNTS_TABLE, CHALLENGES_TABLE,
+    CHALLENGE_COLUMN, CLAIMS_TABLE, CREATED_AT_COLUMN, CRED_ID_COLUMN, DEFAULT_TENANT,
+    EXPIRES_AT_COLUMN, FETCHED_AT_COLUMN, FIRST_NAME_COLUMN, ID_COLUMN, INTEGRITY_STATUS_COLUMN,
+    LAST_NAME_COLUMN, NAME_COLUMN, OPERATION_COLUMN, PLATFORM_COLUMN, PRESENTATION_COLUMN,
+    PUBLIC_NAME_COLUMN, TENANT_COLUMN, USERNAME_CACHE_TABLE, USERNAME_COLUMN, VERIFICATIONS_TABLE,
+    VERIFICATION_ID_COLUMN,
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use concordium_rust_sdk::{common, web3id::CredentialHolderId};
+use rusqlite::{params, Connection, OptionalExtension};
+use some_verifier_lib::{webhook::AccountId, FullName, Platform};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Migrations of the SQLite schema, in order. The version of the schema is
+/// recorded in `PRAGMA user_version`. Existing migrations must never be
+/// changed, only new ones added.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../resources/migrations/sqlite/0001_initial.sql"),
+    include_str!("../../resources/migrations/sqlite/0002_public_name.sql"),
+    include_str!("../../resources/migrations/sqlite/0003_tenants.sql"),
+    include_str!("../../resources/migrations/sqlite/0004_integrity_status.sql"),
+];
+
+pub struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+    /// The tenant whose rows are read and written.
+    tenant: Arc<str>,
+}
+
+impl SqliteStorage {
+    /// Open the database file. This fails if the schema is not at the version
+    /// expected by the verifier.
+    pub fn open(path: &Path) -> DbResult<Self> {
+        let connection = open_connection(path)?;
+        let current = current_version(&connection)?;
+        let latest = MIGRATIONS.len() as i64;
+        anyhow::ensure!(
+            current <= latest,
+            "The database schema is at version {current}, which is newer than the latest version \
+             {latest} known by this verifier. Refusing to start."
+        );
+        anyhow::ensure!(
+            current == latest,
+            "The database schema is at version {current}, but version {latest} is required. Run \
+             the verifier with --migrate to apply the pending migrations."
+        );
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            tenant: DEFAULT_TENANT.into(),
+        })
+    }
+
+    /// Create the database file if it does not exist, and apply all pending
+    /// migrations.
+    pub fn migrate(path: &Path) -> DbResult<()> {
+        let mut connection = open_connection(path)?;
+        migrate_connection(&mut connection)
+    }
+
+    /// An empty in-memory database.
+    #[cfg(test)]
+    pub fn in_memory() -> DbResult<Self> {
+        let mut connection = Connection::open_in_memory()?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate_connection(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            tenant: DEFAULT_TENANT.into(),
+        })
+    }
+
+    /// Run `f` with the connection on a blocking thread.
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> DbResult<T> + Send + 'static,
+    ) -> DbResult<T> {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            // A panic while holding the lock cannot leave the database in an
+            // inconsistent state, since transactions are rolled back on drop.
+            let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut connection)
+        })
+        .await?
+    }
+}
+
+fn open_connection(path: &Path) -> DbResult<Connection> {
+    let connection = Connection::open(path)
+        .with_context(|| format!("Unable to open SQLite database {}.", path.display()))?;
+    connection.busy_timeout(std::time::Duration::from_secs(5))?;
+    // Foreign keys, and thereby cascading deletes, are off by default.
+    connection.pragma_update(None, "foreign_keys", true)?;
+    Ok(connection)
+}
+
+fn current_version(connection: &Connection) -> DbResult<i64> {
+    Ok(connection.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+fn migrate_connection(connection: &mut Connection) -> DbResult<()> {
+    let tx = connection.transaction()?;
+    let current = current_version(&tx)?;
+    anyhow::ensure!(
+        current <= MIGRATIONS.len() as i64,
+        "The database schema is at version {current}, which is newer than the latest version {} \
+         known by this verifier.",
+        MIGRATIONS.len()
+    );
+    for (version, sql) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = version as i64 + 1;
+        tracing::info!("Applying SQLite migration {version}.");
+        tx.execute_batch(sql)
+            .with_context(|| format!("Migration {version} failed."))?;
+        tx.pragma_update(None, "user_version", version)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// The current time as a unix time in microseconds.
+fn now() -> i64 {
+    Utc::now().timestamp_micros()
+}
+
+fn micros(duration: std::time::Duration) -> i64 {
+    duration.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    fn for_tenant(&self, tenant: &str) -> Arc<dyn Storage> {
+        Arc::new(Self {
+            connection: self.connection.clone(),
+            tenant: tenant.into(),
+        })
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn get_verification(
+        &self,
+        id: &str,
+        platform: &Platform,
+    ) -> DbResult<Option<DbVerification>> {
+        let id = id.to_string();
+        let platform = platform.name().to_string();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let tx = connection.transaction()?;
+            let select_verification_id = format!(
+                "SELECT {VERIFICATION_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE {PLATFORM_COLUMN} = \
+                 ?1 AND {ID_COLUMN} = ?2 AND {TENANT_COLUMN} = ?3"
+            );
+            let Some(ver_id) = tx
+                .query_row(
+                    &select_verification_id,
+                    params![platform, id, tenant],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()?
+            else {
+                return Ok(None);
+            };
+
+            let name_statement = format!(
+                "SELECT {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, {PUBLIC_NAME_COLUMN}, \
+                 {PRESENTATION_COLUMN} FROM {VERIFICATIONS_TABLE} WHERE {ID_COLUMN} = ?1"
+            );
+            let Some((full_name, public_name, presentation)) = tx
+                .query_row(&name_statement, params![ver_id], |row| {
+                    Ok((
+                        full_name(row.get(0)?, row.get(1)?),
+                        row.get(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                })
+                .optional()?
+            else {
+                return Ok(None);
+            };
+
+            // All accounts of the verification, with the account that was
+            // looked up first.
+            let accounts_statement = format!(
+                "SELECT {PLATFORM_COLUMN}, {ID_COLUMN}, {USERNAME_COLUMN}, {CRED_ID_COLUMN} FROM \
+                 {ACCOUNTS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = ?1 ORDER BY {PLATFORM_COLUMN} \
+                 = ?2 DESC, {PLATFORM_COLUMN}"
+            );
+            let mut accounts = Vec::new();
+            let mut statement = tx.prepare(&accounts_statement)?;
+            let mut rows = statement.query(params![ver_id, platform])?;
+            while let Some(row) = rows.next()? {
+                let cred_id: Vec<u8> = row.get(3)?;
+                accounts.push(DbAccount {
+                    platform: Platform::new(row.get::<_, String>(0)?),
+                    id: row.get(1)?,
+                    username: row.get(2)?,
+                    cred_id: common::from_bytes(&mut cred_id.as_slice())?,
+                });
+            }
+            drop(rows);
+            drop(statement);
+
+            let claims = load_claims(&tx, ver_id)?;
+            tx.commit()?;
+            Ok(Some(DbVerification {
+                id: ver_id,
+                accounts,
+                full_name,
+                claims,
+                public_name,
+                presentation: parse_presentation(presentation)?,
+            }))
+        })
+        .await
+    }
+
+    async fn add_verification(&self, entry: VerificationsEntry) -> DbResult<AddOutcome> {
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let tx = connection.transaction()?;
+
+            // Clear pre-existing verifications with overlapping credentials.
+            let mut replaced = Vec::new();
+            for account in &entry.accounts {
+                let Some(id) =
+                    find_verification_id(&tx, &tenant, &account.cred_id, &account.platform)?
+                else {
+                    continue;
+                };
+                // The verification is already gone if it had another credential of the entry.
+                if let Some(accounts) = remove_by_id(&tx, &tenant, id)? {
+                    tracing::debug!("Deleted verification {id} from {VERIFICATIONS_TABLE}");
+                    replaced.push(accounts);
+                }
+            }
+
+            let insert_statement = format!(
+                "INSERT INTO {VERIFICATIONS_TABLE} ({FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+                 {PRESENTATION_COLUMN}, {CREATED_AT_COLUMN}, {PUBLIC_NAME_COLUMN}, {TENANT_COLUMN}) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+            );
+            tx.execute(
+                &insert_statement,
+                params![
+                    entry.full_name.as_ref().map(|n| &n.first_name),
+                    entry.full_name.as_ref().map(|n| &n.last_name),
+                    entry.presentation.as_ref().map(|p| p.to_string()),
+                    now(),
+                    entry.public_name,
+                    tenant,
+                ],
+            )?;
+            let verification_id = tx.last_insert_rowid();
+
+            let claim_statement = format!(
+                "INSERT INTO {CLAIMS_TABLE} ({VERIFICATION_ID_COLUMN}, {NAME_COLUMN}) VALUES (?1, \
+                 ?2)"
+            );
+            for claim in &entry.claims {
+                tx.execute(&claim_statement, params![verification_id, claim])?;
+            }
+
+            for account in entry.accounts {
+                let platform = account.platform.clone();
+                if let Some(user_id) = add_platform_entry(&tx, &tenant, account, verification_id)? {
+                    tracing::debug!(
+                        "Refusing to add new {platform} verification due to clash of user id {}.",
+                        user_id
+                    );
+                    // Dropping the transaction rolls it back.
+                    return Ok(AddOutcome::DuplicateUser(user_id));
+                }
+            }
+
+            tx.commit()?;
+            Ok(AddOutcome::Added { replaced })
+        })
+        .await
+    }
+
+    async fn remove_verification(
+        &self,
+        cred_id: &CredentialHolderId,
+        platform: &Platform,
+    ) -> DbResult<Option<Vec<AccountId>>> {
+        let cred_id = *cred_id;
+        let platform = platform.clone();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let tx = connection.transaction()?;
+            let accounts = match find_verification_id(&tx, &tenant, &cred_id, &platform)? {
+                Some(id) => remove_by_id(&tx, &tenant, id)?,
+                None => None,
+            };
+            tx.commit()?;
+            Ok(accounts)
+        })
+        .await
+    }
+
+    async fn add_challenge(
+        &self,
+        challenge: &[u8],
+        operation: &str,
+        ttl: std::time::Duration,
+        max_outstanding: u64,
+    ) -> DbResult<bool> {
+        let challenge = challenge.to_vec();
+        let operation = operation.to_string();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let now = now();
+            connection.execute(
+                &format!("DELETE FROM {CHALLENGES_TABLE} WHERE {EXPIRES_AT_COLUMN} < ?1"),
+                params![now],
+            )?;
+            let statement = format!(
+                "INSERT INTO {CHALLENGES_TABLE} ({CHALLENGE_COLUMN}, {OPERATION_COLUMN}, \
+                 {EXPIRES_AT_COLUMN}, {TENANT_COLUMN}) SELECT ?1, ?2, ?3, ?4 WHERE (SELECT \
+                 COUNT(*) FROM {CHALLENGES_TABLE} WHERE {TENANT_COLUMN} = ?4) < ?5"
+            );
+            let inserted = connection.execute(
+                &statement,
+                params![
+                    challenge,
+                    operation,
+                    now.saturating_add(micros(ttl)),
+                    tenant,
+                    i64::try_from(max_outstanding).unwrap_or(i64::MAX)
+                ],
+            )?;
+            Ok(inserted == 1)
+        })
+        .await
+    }
+
+    async fn use_challenge(&self, challenge: &[u8], operation: &str) -> DbResult<bool> {
+        let challenge = challenge.to_vec();
+        let operation = operation.to_string();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "DELETE FROM {CHALLENGES_TABLE} WHERE {CHALLENGE_COLUMN} = ?1 AND \
+                 {OPERATION_COLUMN} = ?2 AND {TENANT_COLUMN} = ?4 RETURNING {EXPIRES_AT_COLUMN} > \
+                 ?3"
+            );
+            let valid = connection
+                .query_row(
+                    &statement,
+                    params![challenge, operation, now(), tenant],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(valid.unwrap_or(false))
+        })
+        .await
+    }
+
+    async fn get_cached_username(
+        &self,
+        platform: &Platform,
+        id: &str,
+        ttl: std::time::Duration,
+    ) -> DbResult<Option<(String, bool)>> {
+        let platform = platform.name().to_string();
+        let id = id.to_string();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "SELECT {USERNAME_COLUMN}, {FETCHED_AT_COLUMN} > ?3 FROM {USERNAME_CACHE_TABLE} \
+                 WHERE {PLATFORM_COLUMN} = ?1 AND {ID_COLUMN} = ?2 AND {TENANT_COLUMN} = ?4"
+            );
+            Ok(connection
+                .query_row(
+                    &statement,
+                    params![platform, id, now().saturating_sub(micros(ttl)), tenant],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn cache_username(&self, platform: &Platform, id: &str, username: &str) -> DbResult<()> {
+        let platform = platform.name().to_string();
+        let id = id.to_string();
+        let username = username.to_string();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "INSERT INTO {USERNAME_CACHE_TABLE} ({PLATFORM_COLUMN}, {ID_COLUMN}, \
+                 {USERNAME_COLUMN}, {FETCHED_AT_COLUMN}, {TENANT_COLUMN}) VALUES (?1, ?2, ?3, ?4, \
+                 ?5) ON CONFLICT ({TENANT_COLUMN}, {PLATFORM_COLUMN}, {ID_COLUMN}) DO UPDATE SET \
+                 {USERNAME_COLUMN} = excluded.{USERNAME_COLUMN}, {FETCHED_AT_COLUMN} = \
+                 excluded.{FETCHED_AT_COLUMN}"
+            );
+            connection.execute(&statement, params![platform, id, username, now(), tenant])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_accounts(
+        &self,
+        after: Option<(&Platform, &str)>,
+        limit: i64,
+    ) -> DbResult<Vec<DbAccountCredential>> {
+        let (platform, id) = after.map_or((String::new(), String::new()), |(p, id)| {
+            (p.name().to_string(), id.to_string())
+        });
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "SELECT {PLATFORM_COLUMN}, {ID_COLUMN}, {CRED_ID_COLUMN} FROM {ACCOUNTS_TABLE} \
+                 WHERE {TENANT_COLUMN} = ?4 AND ({PLATFORM_COLUMN}, {ID_COLUMN}) > (?1, ?2) ORDER \
+                 BY {PLATFORM_COLUMN}, {ID_COLUMN} LIMIT ?3"
+            );
+            let mut statement = connection.prepare(&statement)?;
+            let mut rows = statement.query(params![platform, id, limit, tenant])?;
+            let mut accounts = Vec::new();
+            while let Some(row) = rows.next()? {
+                let cred_id: Vec<u8> = row.get(2)?;
+                accounts.push(DbAccountCredential {
+                    platform: Platform::new(row.get::<_, String>(0)?),
+                    id: row.get(1)?,
+                    cred_id: common::from_bytes(&mut cred_id.as_slice())?,
+                });
+            }
+            Ok(accounts)
+        })
+        .await
+    }
+
+    async fn search_verifications(
+        &self,
+        filter: &VerificationFilter,
+        after: Option<i64>,
+        limit: i64,
+    ) -> DbResult<Vec<AdminVerification>> {
+        let has_account_filter = filter.platform.is_some()
+            || filter.id.is_some()
+            || filter.username.is_some()
+            || filter.cred_id.is_some();
+        let platform = filter.platform.clone();
+        let id = filter.id.clone();
+        let username = filter.username.as_deref().map(contains_pattern);
+        let cred_id = filter.cred_id.clone();
+        let name = filter.name.as_deref().map(contains_pattern);
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            // LIKE is case-insensitive for ASCII characters, like ILIKE.
+            let statement = format!(
+                "SELECT {ID_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+                 {INTEGRITY_STATUS_COLUMN} FROM {VERIFICATIONS_TABLE} v WHERE (?1 IS NULL OR v.{ID_COLUMN} > ?1) AND (NOT ?2 OR \
+                 EXISTS (SELECT 1 FROM {ACCOUNTS_TABLE} a WHERE a.{VERIFICATION_ID_COLUMN} = \
+                 v.{ID_COLUMN} AND (?3 IS NULL OR a.{PLATFORM_COLUMN} = ?3) AND (?4 IS NULL OR \
+                 a.{ID_COLUMN} = ?4) AND (?5 IS NULL OR a.{USERNAME_COLUMN} LIKE ?5 ESCAPE '\\') \
+                 AND (?6 IS NULL OR a.{CRED_ID_COLUMN} = ?6))) AND (?7 IS NULL OR \
+                 (v.{FIRST_NAME_COLUMN} || ' ' || v.{LAST_NAME_COLUMN}) LIKE ?7 ESCAPE '\\') AND \
+                 v.{TENANT_COLUMN} = ?9 ORDER BY v.{ID_COLUMN} LIMIT ?8"
+            );
+            let mut verifications = Vec::new();
+            let mut statement = connection.prepare(&statement)?;
+            let mut rows = statement.query(params![
+                after,
+                has_account_filter,
+                platform,
+                id,
+                username,
+                cred_id,
+                name,
+                limit,
+                tenant
+            ])?;
+            while let Some(row) = rows.next()? {
+                verifications.push(admin_verification_from_row(row)?);
+            }
+            drop(rows);
+            drop(statement);
+            load_accounts(connection, &mut verifications)?;
+            Ok(verifications)
+        })
+        .await
+    }
+
+    async fn get_verification_by_id(
+        &self,
+        id: i64,
+    ) -> DbResult<Option<(AdminVerification, Option<serde_json::Value>)>> {
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "SELECT {ID_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+                 {INTEGRITY_STATUS_COLUMN}, {PRESENTATION_COLUMN} FROM {VERIFICATIONS_TABLE} WHERE \
+                 {ID_COLUMN} = ?1 AND {TENANT_COLUMN} = ?2"
+            );
+            let Some((verification, presentation)) = connection
+                .query_row(&statement, params![id, tenant], |row| {
+                    Ok((
+                        admin_verification_from_row(row)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            let mut verification = [verification];
+            load_accounts(connection, &mut verification)?;
+            let [verification] = verification;
+            Ok(Some((verification, parse_presentation(presentation)?)))
+        })
+        .await
+    }
+
+    async fn delete_verification(&self, id: i64) -> DbResult<Option<Vec<AccountId>>> {
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let tx = connection.transaction()?;
+            let accounts = remove_by_id(&tx, &tenant, id)?;
+            tx.commit()?;
+            Ok(accounts)
+        })
+        .await
+    }
+
+    async fn export_data(
+        &self,
+        cred_id: &CredentialHolderId,
+        platform: &Platform,
+    ) -> DbResult<Option<DataExport>> {
+        let cred_id = cred_id.public_key.as_bytes().to_vec();
+        let platform = platform.name().to_string();
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let tx = connection.transaction()?;
+            let statement = format!(
+                "SELECT {ID_COLUMN}, {FIRST_NAME_COLUMN}, {LAST_NAME_COLUMN}, \
+                 {INTEGRITY_STATUS_COLUMN}, {PRESENTATION_COLUMN}, {CREATED_AT_COLUMN}, \
+                 {PUBLIC_NAME_COLUMN} FROM {VERIFICATIONS_TABLE} WHERE {TENANT_COLUMN} = ?3 AND {ID_COLUMN} IN (SELECT \
+                 {VERIFICATION_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE {PLATFORM_COLUMN} = ?1 AND \
+                 {CRED_ID_COLUMN} = ?2)"
+            );
+            let Some((verification, presentation, created_at, public_name)) = tx
+                .query_row(&statement, params![platform, cred_id, tenant], |row| {
+                    Ok((
+                        admin_verification_from_row(row)?,
+                        row.get::<_, Option<String>>(4)?,
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, bool>(6)?,
+                    ))
+                })
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            let mut verification = [verification];
+            load_accounts(&tx, &mut verification)?;
+            let [verification] = verification;
+            let claims = load_claims(&tx, verification.id)?;
+
+            let cache_statement = format!(
+                "SELECT c.{PLATFORM_COLUMN}, c.{ID_COLUMN}, c.{USERNAME_COLUMN}, \
+                 c.{FETCHED_AT_COLUMN} FROM {USERNAME_CACHE_TABLE} c JOIN {ACCOUNTS_TABLE} a \
+                 USING ({TENANT_COLUMN}, {PLATFORM_COLUMN}, {ID_COLUMN}) WHERE \
+                 a.{VERIFICATION_ID_COLUMN} = ?1 ORDER BY c.{PLATFORM_COLUMN}"
+            );
+            let mut cached_usernames = Vec::new();
+            let mut statement = tx.prepare(&cache_statement)?;
+            let mut rows = statement.query(params![verification.id])?;
+            while let Some(row) = rows.next()? {
+                cached_usernames.push(CachedUsername {
+                    platform: Platform::new(row.get::<_, String>(0)?),
+                    id: row.get(1)?,
+                    username: row.get(2)?,
+                    fetched_at: from_micros(row.get(3)?)?,
+                });
+            }
+            drop(rows);
+            drop(statement);
+            tx.commit()?;
+
+            Ok(Some(DataExport {
+                verification,
+                created_at: from_micros(created_at)?,
+                claims,
+                public_name,
+                cached_usernames,
+                presentation: parse_presentation(presentation)?,
+            }))
+        })
+        .await
+    }
+
+    async fn purge_presentations(&self, retention: std::time::Duration) -> DbResult<u64> {
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "UPDATE {VERIFICATIONS_TABLE} SET {PRESENTATION_COLUMN} = NULL WHERE \
+                 {PRESENTATION_COLUMN} IS NOT NULL AND {CREATED_AT_COLUMN} < ?1 AND \
+                 {TENANT_COLUMN} = ?2"
+            );
+            let purged = connection.execute(
+                &statement,
+                params![now().saturating_sub(micros(retention)), tenant],
+            )?;
+            Ok(purged as u64)
+        })
+        .await
+    }
+
+    async fn has_unhashed_ids(&self) -> DbResult<bool> {
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "SELECT EXISTS (SELECT 1 FROM {ACCOUNTS_TABLE} WHERE length({ID_COLUMN}) != 64 \
+                 OR {ID_COLUMN} GLOB '*[^0-9a-f]*')"
+            );
+            Ok(connection.query_row(&statement, [], |row| row.get(0))?)
+        })
+        .await
+    }
+
+    async fn set_integrity_status(&self, id: i64, status: IntegrityStatus) -> DbResult<()> {
+        let tenant = self.tenant.to_string();
+        self.with_connection(move |connection| {
+            let statement = format!(
+                "UPDATE {VERIFICATIONS_TABLE} SET {INTEGRITY_STATUS_COLUMN} = ?1 WHERE \
+                 {ID_COLUMN} = ?2 AND {TENANT_COLUMN} = ?3"
+            );
+            connection.execute(&statement, params![status.as_str(), id, tenant])?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Attempt to add a platform entry. If an entry already exists return the
+/// `user_id` and do no updates.
+fn add_platform_entry(
+    tx: &rusqlite::Transaction<'_>,
+    tenant: &str,
+    entry: PlatformEntry,
+    verification_id: i64,
+) -> DbResult<Option<String>> {
+    let statement = format!(
+        "INSERT INTO {ACCOUNTS_TABLE} ({PLATFORM_COLUMN}, {ID_COLUMN}, {CRED_ID_COLUMN}, \
+         {VERIFICATION_ID_COLUMN}, {USERNAME_COLUMN}, {TENANT_COLUMN}) VALUES (?1, ?2, ?3, ?4, ?5, \
+         ?6) ON CONFLICT ({TENANT_COLUMN}, {PLATFORM_COLUMN}, {ID_COLUMN}) DO NOTHING"
+    );
+    let inserted = tx.execute(
+        &statement,
+        params![
+            entry.platform.name(),
+            entry.id,
+            entry.cred_id.public_key.as_bytes().as_slice(),
+            verification_id,
+            entry.username,
+            tenant
+        ],
+    )?;
+    if inserted > 0 {
+        Ok(None)
+    } else {
+        Ok(Some(entry.id))
+    }
+}
+
+fn full_name(first_name: Option<String>, last_name: Option<String>) -> Option<FullName> {
+    first_name
+        .zip(last_name)
+        .map(|(first_name, last_name)| FullName {
+            first_name,
+            last_name,
+        })
+}
+
+/// Read a verification from a row starting with the id, first name, last
+/// name and integrity status.
+fn admin_verification_from_row(row: &rusqlite::Row) -> rusqlite::Result<AdminVerification> {
+    Ok(AdminVerification {
+        id: row.get(0)?,
+        full_name: full_name(row.get(1)?, row.get(2)?),
+        accounts: Vec::new(),
+        integrity_status: IntegrityStatus::from_stored(row.get(3)?),
+    })
+}
+
+/// Add the accounts of the verifications.
+fn load_accounts(connection: &Connection, verifications: &mut [AdminVerification]) -> DbResult<()> {
+    let statement = format!(
+        "SELECT {PLATFORM_COLUMN}, {ID_COLUMN}, {USERNAME_COLUMN}, {CRED_ID_COLUMN} FROM \
+         {ACCOUNTS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = ?1 ORDER BY {PLATFORM_COLUMN}"
+    );
+    let mut statement = connection.prepare(&statement)?;
+    for verification in verifications {
+        let mut rows = statement.query(params![verification.id])?;
+        while let Some(row) = rows.next()? {
+            let cred_id: Vec<u8> = row.get(3)?;
+            verification.accounts.push(AdminAccount {
+                platform: Platform::new(row.get::<_, String>(0)?),
+                id: row.get(1)?,
+                username: row.get(2)?,
+                cred_id: hex::encode(cred_id),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn load_claims(connection: &Connection, verification_id: i64) -> DbResult<Vec<String>> {
+    let statement = format!(
+        "SELECT {NAME_COLUMN} FROM {CLAIMS_TABLE} WHERE {VERIFICATION_ID_COLUMN} = ?1 ORDER BY \
+         {NAME_COLUMN}"
+    );
+    let mut statement = connection.prepare(&statement)?;
+    let claims = statement
+        .query_map(params![verification_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(claims)
+}
+
+fn parse_presentation(presentation: Option<String>) -> DbResult<Option<serde_json::Value>> {
+    Ok(presentation.map(|p| serde_json::from_str(&p)).transpose()?)
+}
+
+fn from_micros(micros: i64) -> DbResult<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(micros).context("Timestamp out of range.")
+}
+
+/// Find the id of the verification with the credential on the platform.
+fn find_verification_id(
+    tx: &rusqlite::Transaction,
+    tenant: &str,
+    cred_id: &CredentialHolderId,
+    platform: &Platform,
+) -> DbResult<Option<i64>> {
+    let statement = format!(
+        "SELECT {VERIFICATION_ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE {PLATFORM_COLUMN} = ?1 AND \
+         {CRED_ID_COLUMN} = ?2 AND {TENANT_COLUMN} = ?3"
+    );
+    Ok(tx
+        .query_row(
+            &statement,
+            params![
+                platform.name(),
+                cred_id.public_key.as_bytes().as_slice(),
+                tenant
+            ],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Delete the tenant's verification with the id, and return its accounts, or
+/// `None` if it did not exist. The accounts are collected first, since they
+/// are removed with the verification.
+fn remove_by_id(
+    tx: &rusqlite::Transaction,
+    tenant: &str,
+    id: i64,
+) -> DbResult<Option<Vec<AccountId>>> {
+    let statement = format!(
+        "SELECT {PLATFORM_COLUMN}, {ID_COLUMN} FROM {ACCOUNTS_TABLE} WHERE \
+         {VERIFICATION_ID_COLUMN} = ?1 ORDER BY {PLATFORM_COLUMN}"
+    );
+    let accounts = tx
+        .prepare(&statement)?
+        .query_map(params![id], |row| {
+            Ok(AccountId {
+                platform: Platform::new(row.get::<_, String>(0)?),
+                id: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let statement = format!(
+        "DELETE FROM {VERIFICATIONS_TABLE} WHERE {ID_COLUMN} = ?1 AND {TENANT_COLUMN} = ?2"
+    );
+    if tx.execute(&statement, params![id, tenant])? > 0 {
+        Ok(Some(accounts))
+    } else {
+        Ok(None)
+    }
+}
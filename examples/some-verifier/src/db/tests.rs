@@ -0,0 +1,669 @@
+//! Conformance tests for the [`Storage`] implementations. Every test is run
+//! against an in-memory SQLite database, and against Postgres if the
+//! connection string of a test database is given in
+//! `SOME_VERIFIER_TEST_DB_STRING`. The Postgres database is migrated, and
+//! tests use random user ids so that they can share it.
+use super::{
+    AddOutcome, IntegrityStatus, PlatformEntry, PostgresStorage, SqliteStorage, Storage,
+    VerificationFilter, VerificationsEntry,
+};
+use concordium_rust_sdk::{common, web3id::CredentialHolderId};
+use some_verifier_lib::{webhook::AccountId, FullName, Platform};
+use std::time::Duration;
+
+async fn postgres() -> Option<PostgresStorage> {
+    let db_string = std::env::var("SOME_VERIFIER_TEST_DB_STRING").ok()?;
+    let config = db_string.parse().expect("Invalid test database string.");
+    super::migrate(&config)
+        .await
+        .expect("Unable to migrate test database.");
+    Some(
+        PostgresStorage::connect(config, 4)
+            .await
+            .expect("Unable to connect to test database."),
+    )
+}
+
+/// Generate a `#[tokio::test]` for each storage implementation, running the
+/// given check.
+macro_rules! conformance_test {
+    ($name:ident) => {
+        mod $name {
+            #[tokio::test]
+            async fn sqlite() {
+                let storage = super::SqliteStorage::in_memory().unwrap();
+                super::$name(&storage).await;
+            }
+
+            #[tokio::test]
+            async fn postgres() {
+                let Some(storage) = super::postgres().await else {
+                    eprintln!("SOME_VERIFIER_TEST_DB_STRING is not set, skipping.");
+                    return;
+                };
+                super::$name(&storage).await;
+            }
+        }
+    };
+}
+
+conformance_test!(add_and_get);
+conformance_test!(duplicate_user);
+conformance_test!(replace_credential);
+conformance_test!(remove_cascades);
+conformance_test!(challenges);
+conformance_test!(challenge_cap);
+conformance_test!(username_cache);
+conformance_test!(export_and_purge);
+conformance_test!(search);
+conformance_test!(tenant_isolation);
+conformance_test!(unhashed_ids);
+conformance_test!(integrity_status);
+
+/// A random credential holder id.
+fn cred_id() -> CredentialHolderId {
+    // Not all byte strings are valid public keys, so try until one is.
+    loop {
+        let bytes: [u8; 32] = rand::random();
+        if let Ok(id) = common::from_bytes(&mut &bytes[..]) {
+            return id;
+        }
+    }
+}
+
+/// A random user id.
+fn user_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+fn account(platform: Platform, cred_id: CredentialHolderId) -> PlatformEntry {
+    let id = user_id();
+    PlatformEntry {
+        platform,
+        username: format!("user-{id}"),
+        id,
+        cred_id,
+    }
+}
+
+fn entry(accounts: Vec<PlatformEntry>) -> VerificationsEntry {
+    VerificationsEntry {
+        accounts,
+        presentation: Some(serde_json::json!({ "test": true })),
+        full_name: None,
+        claims: Vec::new(),
+        public_name: false,
+    }
+}
+
+async fn add_and_get(storage: &dyn Storage) {
+    let telegram = account(Platform::TELEGRAM, cred_id());
+    let discord = account(Platform::DISCORD, cred_id());
+    let (telegram_id, discord_id) = (telegram.id.clone(), discord.id.clone());
+    let mut entry = entry(vec![telegram, discord]);
+    entry.full_name = Some(FullName {
+        first_name: "John".into(),
+        last_name: "Doe".into(),
+    });
+    entry.claims = vec!["b".into(), "a".into()];
+    entry.public_name = true;
+    assert_eq!(
+        storage.add_verification(entry).await.unwrap(),
+        AddOutcome::Added { replaced: vec![] }
+    );
+
+    let verification = storage
+        .get_verification(&discord_id, &Platform::DISCORD)
+        .await
+        .unwrap()
+        .expect("The verification exists.");
+    let full_name = verification.full_name.expect("The full name is stored.");
+    assert_eq!(full_name.first_name, "John");
+    assert_eq!(full_name.last_name, "Doe");
+    assert_eq!(verification.claims, ["a", "b"]);
+    assert!(verification.public_name);
+    assert_eq!(
+        verification.presentation,
+        Some(serde_json::json!({ "test": true }))
+    );
+    // The account that was looked up comes first.
+    let platforms: Vec<_> = verification.accounts.iter().map(|a| &a.platform).collect();
+    assert_eq!(platforms, [&Platform::DISCORD, &Platform::TELEGRAM]);
+    assert_eq!(verification.accounts[1].id, telegram_id);
+
+    assert!(storage
+        .get_verification(&telegram_id, &Platform::DISCORD)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn duplicate_user(storage: &dyn Storage) {
+    let telegram = account(Platform::TELEGRAM, cred_id());
+    let telegram_id = telegram.id.clone();
+    let discord = account(Platform::DISCORD, cred_id());
+    assert_eq!(
+        storage
+            .add_verification(entry(vec![telegram, discord]))
+            .await
+            .unwrap(),
+        AddOutcome::Added { replaced: vec![] }
+    );
+
+    // The same Telegram user with a different credential is rejected.
+    let mut clashing = account(Platform::TELEGRAM, cred_id());
+    clashing.id = telegram_id.clone();
+    let other = account(Platform::DISCORD, cred_id());
+    let other_id = other.id.clone();
+    assert_eq!(
+        storage
+            .add_verification(entry(vec![clashing, other]))
+            .await
+            .unwrap(),
+        AddOutcome::DuplicateUser(telegram_id.clone())
+    );
+
+    // Nothing was changed.
+    let verification = storage
+        .get_verification(&telegram_id, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .expect("The original verification still exists.");
+    assert_eq!(verification.accounts.len(), 2);
+    assert!(storage
+        .get_verification(&other_id, &Platform::DISCORD)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn replace_credential(storage: &dyn Storage) {
+    let telegram_cred = cred_id();
+    let telegram = account(Platform::TELEGRAM, telegram_cred);
+    let telegram_id = telegram.id.clone();
+    let discord = account(Platform::DISCORD, cred_id());
+    let discord_id = discord.id.clone();
+    storage
+        .add_verification(entry(vec![telegram, discord]))
+        .await
+        .unwrap();
+
+    // A new verification with the same Telegram credential replaces the old.
+    let mut telegram = account(Platform::TELEGRAM, telegram_cred);
+    telegram.id = telegram_id.clone();
+    let github = account(Platform::GITHUB, cred_id());
+    assert_eq!(
+        storage
+            .add_verification(entry(vec![telegram, github]))
+            .await
+            .unwrap(),
+        AddOutcome::Added {
+            replaced: vec![vec![
+                AccountId {
+                    platform: Platform::DISCORD,
+                    id: discord_id.clone(),
+                },
+                AccountId {
+                    platform: Platform::TELEGRAM,
+                    id: telegram_id.clone(),
+                },
+            ]]
+        }
+    );
+    assert!(storage
+        .get_verification(&discord_id, &Platform::DISCORD)
+        .await
+        .unwrap()
+        .is_none());
+    let verification = storage
+        .get_verification(&telegram_id, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .expect("The new verification exists.");
+    assert_eq!(verification.accounts[1].platform, Platform::GITHUB);
+}
+
+async fn remove_cascades(storage: &dyn Storage) {
+    let telegram_cred = cred_id();
+    let telegram = account(Platform::TELEGRAM, telegram_cred);
+    let telegram_id = telegram.id.clone();
+    let discord = account(Platform::DISCORD, cred_id());
+    let discord_id = discord.id.clone();
+    let mut entry = entry(vec![telegram, discord]);
+    entry.claims = vec!["adult".into()];
+    storage.add_verification(entry).await.unwrap();
+    storage
+        .cache_username(&Platform::DISCORD, &discord_id, "cached")
+        .await
+        .unwrap();
+
+    assert!(storage
+        .remove_verification(&telegram_cred, &Platform::DISCORD)
+        .await
+        .unwrap()
+        .is_none());
+    let removed = storage
+        .remove_verification(&telegram_cred, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .expect("The verification is removed.");
+    assert_eq!(
+        removed,
+        vec![
+            AccountId {
+                platform: Platform::DISCORD,
+                id: discord_id.clone(),
+            },
+            AccountId {
+                platform: Platform::TELEGRAM,
+                id: telegram_id.clone(),
+            },
+        ]
+    );
+    assert!(storage
+        .remove_verification(&telegram_cred, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .is_none());
+
+    // All accounts and the cached username are removed with the verification.
+    for (id, platform) in [
+        (&telegram_id, Platform::TELEGRAM),
+        (&discord_id, Platform::DISCORD),
+    ] {
+        assert!(storage
+            .get_verification(id, &platform)
+            .await
+            .unwrap()
+            .is_none());
+    }
+    assert!(storage
+        .get_cached_username(&Platform::DISCORD, &discord_id, Duration::from_secs(3600))
+        .await
+        .unwrap()
+        .is_none());
+
+    // The user can verify again with new credentials.
+    let mut telegram = account(Platform::TELEGRAM, cred_id());
+    telegram.id = telegram_id;
+    let mut discord = account(Platform::DISCORD, cred_id());
+    discord.id = discord_id;
+    assert_eq!(
+        storage
+            .add_verification(self::entry(vec![telegram, discord]))
+            .await
+            .unwrap(),
+        AddOutcome::Added { replaced: vec![] }
+    );
+}
+
+async fn challenges(storage: &dyn Storage) {
+    let challenge: [u8; 32] = rand::random();
+    let ttl = Duration::from_secs(300);
+    assert!(storage
+        .add_challenge(&challenge, "add", ttl, u64::MAX)
+        .await
+        .unwrap());
+    // Challenges can only be used for their operation, and only once.
+    assert!(!storage.use_challenge(&challenge, "remove").await.unwrap());
+    assert!(storage.use_challenge(&challenge, "add").await.unwrap());
+    assert!(!storage.use_challenge(&challenge, "add").await.unwrap());
+
+    let expired: [u8; 32] = rand::random();
+    assert!(storage
+        .add_challenge(&expired, "add", Duration::ZERO, u64::MAX)
+        .await
+        .unwrap());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(!storage.use_challenge(&expired, "add").await.unwrap());
+}
+
+async fn challenge_cap(storage: &dyn Storage) {
+    // A fresh tenant, so that challenges of other tests are not counted.
+    let storage = storage.for_tenant(&hex::encode(rand::random::<[u8; 8]>()));
+    let ttl = Duration::from_secs(300);
+    let first: [u8; 32] = rand::random();
+    let second: [u8; 32] = rand::random();
+    assert!(storage.add_challenge(&first, "add", ttl, 1).await.unwrap());
+    // The cap is reached, so no more challenges are stored.
+    assert!(!storage.add_challenge(&second, "add", ttl, 1).await.unwrap());
+    assert!(!storage.use_challenge(&second, "add").await.unwrap());
+    // Using a challenge frees up its place.
+    assert!(storage.use_challenge(&first, "add").await.unwrap());
+    assert!(storage.add_challenge(&second, "add", ttl, 1).await.unwrap());
+
+    // Expired challenges are removed, and do not count towards the cap.
+    let other = storage.for_tenant(&hex::encode(rand::random::<[u8; 8]>()));
+    let expired: [u8; 32] = rand::random();
+    assert!(other
+        .add_challenge(&expired, "add", Duration::ZERO, 1)
+        .await
+        .unwrap());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(other.add_challenge(&first, "add", ttl, 1).await.unwrap());
+}
+
+async fn username_cache(storage: &dyn Storage) {
+    let discord = account(Platform::DISCORD, cred_id());
+    let discord_id = discord.id.clone();
+    storage
+        .add_verification(entry(vec![discord, account(Platform::TELEGRAM, cred_id())]))
+        .await
+        .unwrap();
+
+    let ttl = Duration::from_secs(3600);
+    assert!(storage
+        .get_cached_username(&Platform::DISCORD, &discord_id, ttl)
+        .await
+        .unwrap()
+        .is_none());
+    storage
+        .cache_username(&Platform::DISCORD, &discord_id, "first")
+        .await
+        .unwrap();
+    storage
+        .cache_username(&Platform::DISCORD, &discord_id, "second")
+        .await
+        .unwrap();
+    assert_eq!(
+        storage
+            .get_cached_username(&Platform::DISCORD, &discord_id, ttl)
+            .await
+            .unwrap(),
+        Some(("second".into(), true))
+    );
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(
+        storage
+            .get_cached_username(&Platform::DISCORD, &discord_id, Duration::from_millis(1))
+            .await
+            .unwrap(),
+        Some(("second".into(), false))
+    );
+}
+
+async fn export_and_purge(storage: &dyn Storage) {
+    let telegram_cred = cred_id();
+    let telegram = account(Platform::TELEGRAM, telegram_cred);
+    let discord = account(Platform::DISCORD, cred_id());
+    let discord_id = discord.id.clone();
+    let mut entry = entry(vec![telegram, discord]);
+    entry.claims = vec!["adult".into()];
+    storage.add_verification(entry).await.unwrap();
+    storage
+        .cache_username(&Platform::DISCORD, &discord_id, "cached")
+        .await
+        .unwrap();
+
+    let export = storage
+        .export_data(&telegram_cred, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .expect("The data is exported.");
+    assert_eq!(export.verification.accounts.len(), 2);
+    assert_eq!(export.claims, ["adult"]);
+    assert!(!export.public_name);
+    assert_eq!(export.cached_usernames.len(), 1);
+    assert_eq!(export.cached_usernames[0].username, "cached");
+    assert_eq!(
+        export.presentation,
+        Some(serde_json::json!({ "test": true }))
+    );
+    assert!(storage
+        .export_data(&cred_id(), &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Only presentations past the retention period are purged.
+    storage
+        .purge_presentations(Duration::from_secs(3600))
+        .await
+        .unwrap();
+    let (_, presentation) = storage
+        .get_verification_by_id(export.verification.id)
+        .await
+        .unwrap()
+        .expect("The verification exists.");
+    assert!(presentation.is_some());
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(storage.purge_presentations(Duration::ZERO).await.unwrap() >= 1);
+    let (verification, presentation) = storage
+        .get_verification_by_id(export.verification.id)
+        .await
+        .unwrap()
+        .expect("The verification is kept.");
+    assert!(presentation.is_none());
+    assert_eq!(verification.accounts.len(), 2);
+    let verification = storage
+        .get_verification(&discord_id, &Platform::DISCORD)
+        .await
+        .unwrap()
+        .expect("The verification is kept.");
+    assert!(verification.presentation.is_none());
+}
+
+async fn search(storage: &dyn Storage) {
+    let marker = user_id();
+    let mut telegram = account(Platform::TELEGRAM, cred_id());
+    telegram.username = format!("{marker}x50");
+    let mut entry = entry(vec![telegram, account(Platform::DISCORD, cred_id())]);
+    entry.full_name = Some(FullName {
+        first_name: "Jane".into(),
+        last_name: marker.clone(),
+    });
+    storage.add_verification(entry).await.unwrap();
+
+    let search = |filter: VerificationFilter| async move {
+        storage
+            .search_verifications(&filter, None, 10)
+            .await
+            .unwrap()
+    };
+    let found = search(VerificationFilter {
+        username: Some(format!("{}X50", marker.to_uppercase())),
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].accounts.len(), 2);
+
+    // Wildcards in the filter are matched literally.
+    let found = search(VerificationFilter {
+        username: Some(format!("{marker}_50")),
+        ..Default::default()
+    })
+    .await;
+    assert!(found.is_empty());
+    let found = search(VerificationFilter {
+        username: Some(format!("{marker}%")),
+        ..Default::default()
+    })
+    .await;
+    assert!(found.is_empty());
+
+    let found = search(VerificationFilter {
+        name: Some(format!("jane {marker}")),
+        platform: Some(Platform::DISCORD.name().into()),
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(found.len(), 1);
+    let removed = storage.delete_verification(found[0].id).await.unwrap();
+    assert_eq!(removed.map(|accounts| accounts.len()), Some(2));
+    assert!(storage
+        .delete_verification(found[0].id)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn tenant_isolation(storage: &dyn Storage) {
+    let marker = user_id();
+    let other = storage.for_tenant(&format!("tenant-{marker}"));
+    let telegram_cred = cred_id();
+    let telegram = account(Platform::TELEGRAM, telegram_cred);
+    let discord = account(Platform::DISCORD, cred_id());
+    let telegram_id = telegram.id.clone();
+    // The same accounts, verified again by the other tenant.
+    let copy = |a: &PlatformEntry| PlatformEntry {
+        platform: a.platform.clone(),
+        id: a.id.clone(),
+        username: a.username.clone(),
+        cred_id: a.cred_id,
+    };
+    let again = vec![copy(&telegram), copy(&discord)];
+    let mut first = entry(vec![telegram, discord]);
+    first.full_name = Some(FullName {
+        first_name: "Jane".into(),
+        last_name: marker.clone(),
+    });
+    storage.add_verification(first).await.unwrap();
+
+    // Other tenants neither see the verification nor clash with it.
+    assert!(other
+        .get_verification(&telegram_id, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .is_none());
+    let found = storage
+        .search_verifications(
+            &VerificationFilter {
+                name: Some(marker.clone()),
+                ..Default::default()
+            },
+            None,
+            10,
+        )
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(other
+        .search_verifications(
+            &VerificationFilter {
+                name: Some(marker.clone()),
+                ..Default::default()
+            },
+            None,
+            10,
+        )
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(other
+        .delete_verification(found[0].id)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        other.add_verification(entry(again)).await.unwrap(),
+        AddOutcome::Added { replaced: vec![] }
+    );
+
+    // Removing the verification of one tenant keeps the other.
+    assert!(storage
+        .remove_verification(&telegram_cred, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .is_some());
+    assert!(other
+        .get_verification(&telegram_id, &Platform::TELEGRAM)
+        .await
+        .unwrap()
+        .is_some());
+
+    let challenge: [u8; 32] = rand::random();
+    assert!(storage
+        .add_challenge(&challenge, "add", Duration::from_secs(300), u64::MAX)
+        .await
+        .unwrap());
+    assert!(!other.use_challenge(&challenge, "add").await.unwrap());
+    assert!(storage.use_challenge(&challenge, "add").await.unwrap());
+}
+
+async fn unhashed_ids(storage: &dyn Storage) {
+    // The Postgres database is shared between tests that store unhashed ids,
+    // so only the presence of such ids is checked here. That a database with
+    // only hashed ids has none is checked by `only_hashed_ids`.
+    let unhashed = account(Platform::DISCORD, cred_id());
+    storage
+        .add_verification(entry(vec![unhashed]))
+        .await
+        .unwrap();
+    assert!(storage.has_unhashed_ids().await.unwrap());
+}
+
+/// Run on its own SQLite database, since `has_unhashed_ids` checks the
+/// accounts of all tenants.
+#[tokio::test]
+async fn only_hashed_ids() {
+    let storage = SqliteStorage::in_memory().unwrap();
+    assert!(!storage.has_unhashed_ids().await.unwrap());
+    let mut hashed = account(Platform::TELEGRAM, cred_id());
+    hashed.id = hex::encode(rand::random::<[u8; 32]>());
+    storage.add_verification(entry(vec![hashed])).await.unwrap();
+    assert!(!storage.has_unhashed_ids().await.unwrap());
+
+    // Pseudonyms are lowercase hex.
+    let mut uppercase = account(Platform::DISCORD, cred_id());
+    uppercase.id = hex::encode_upper(rand::random::<[u8; 32]>());
+    storage
+        .for_tenant("other")
+        .add_verification(entry(vec![uppercase]))
+        .await
+        .unwrap();
+    assert!(storage.has_unhashed_ids().await.unwrap());
+}
+
+async fn integrity_status(storage: &dyn Storage) {
+    let marker = user_id();
+    let other = storage.for_tenant(&format!("tenant-{marker}"));
+    let mut entry = entry(vec![account(Platform::TELEGRAM, cred_id())]);
+    entry.full_name = Some(FullName {
+        first_name: "Jane".into(),
+        last_name: marker.clone(),
+    });
+    storage.add_verification(entry).await.unwrap();
+    let filter = VerificationFilter {
+        name: Some(marker),
+        ..Default::default()
+    };
+    let found = storage
+        .search_verifications(&filter, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    let id = found[0].id;
+    // Verifications are not checked when they are added.
+    assert_eq!(found[0].integrity_status, None);
+
+    storage
+        .set_integrity_status(id, IntegrityStatus::Mismatch)
+        .await
+        .unwrap();
+    // Other tenants cannot change the status.
+    other
+        .set_integrity_status(id, IntegrityStatus::Valid)
+        .await
+        .unwrap();
+    let (verification, _) = storage.get_verification_by_id(id).await.unwrap().unwrap();
+    assert_eq!(
+        verification.integrity_status,
+        Some(IntegrityStatus::Mismatch)
+    );
+
+    storage
+        .set_integrity_status(id, IntegrityStatus::Unverifiable)
+        .await
+        .unwrap();
+    let found = storage
+        .search_verifications(&filter, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        found[0].integrity_status,
+        Some(IntegrityStatus::Unverifiable)
+    );
+}
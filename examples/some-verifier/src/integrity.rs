@@ -0,0 +1,367 @@
+//! Checks that stored verifications are backed by their presentations. The
+//! columns of a verification are derived from its presentation when it is
+//! added, so a row that no longer matches its presentation has been changed
+//! outside of the verifier. Presentations can also be verified again against
+//! the current state of the chain when verifications are served, with
+//! `--reverify-on-read`, and by the admin API. Verifications whose
+//! presentations have been purged, or were not stored since user ids are
+//! hashed, cannot be checked and are reported as unverifiable.
+use crate::{
+    db::{
+        AdminVerification, DbVerification, IntegrityStatus, VerificationFilter, VerificationsEntry,
+    },
+    AppState,
+};
+use concordium_rust_sdk::{
+    contract_client::CredentialStatus,
+    id::constants::ArCurve,
+    types::hashes::BlockHash,
+    v2::BlockIdentifier,
+    web3id::{self, Presentation, Web3IdAttribute},
+};
+use serde::Serialize;
+use some_verifier_lib::FullName;
+use std::{collections::HashMap, sync::Mutex};
+
+/// The number of verifications that are checked per database query.
+const BATCH_SIZE: i64 = 100;
+
+/// The result of verifying a stored presentation against the current state
+/// of the chain.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reverification {
+    /// Whether the proofs are valid, match the stored verification, and all
+    /// credentials are active.
+    pub valid: bool,
+    /// The statuses of the credentials in the presentation, if they could be
+    /// looked up.
+    pub credential_statuses: Vec<CredentialStatus>,
+    /// The reason the presentation is not valid.
+    pub error: Option<String>,
+}
+
+/// The columns of a stored verification that are derived from its
+/// presentation: the full name, and the platform, user id and hex encoded
+/// credential holder id of each account.
+#[derive(PartialEq, Eq, Debug)]
+pub struct ProvenColumns {
+    full_name: Option<(String, String)>,
+    accounts: Vec<(String, String, String)>,
+}
+
+impl ProvenColumns {
+    fn new(
+        full_name: Option<&FullName>,
+        accounts: impl Iterator<Item = (String, String, String)>,
+    ) -> Self {
+        let mut accounts: Vec<_> = accounts.collect();
+        accounts.sort();
+        Self {
+            full_name: full_name.map(|n| (n.first_name.clone(), n.last_name.clone())),
+            accounts,
+        }
+    }
+
+    fn of_verification(verification: &DbVerification) -> Self {
+        Self::new(
+            verification.full_name.as_ref(),
+            verification.accounts.iter().map(|a| {
+                (
+                    a.platform.name().to_string(),
+                    a.id.clone(),
+                    hex::encode(a.cred_id.public_key.as_bytes()),
+                )
+            }),
+        )
+    }
+
+    pub fn of_admin_verification(verification: &AdminVerification) -> Self {
+        Self::new(
+            verification.full_name.as_ref(),
+            verification.accounts.iter().map(|a| {
+                (
+                    a.platform.name().to_string(),
+                    a.id.clone(),
+                    a.cred_id.clone(),
+                )
+            }),
+        )
+    }
+
+    /// The columns that the presentation results in when it is added with
+    /// the current configuration.
+    fn of_presentation(
+        state: &AppState,
+        presentation: &Presentation<ArCurve, Web3IdAttribute>,
+    ) -> Result<Self, String> {
+        let mut entry = VerificationsEntry::from_presentation(presentation);
+        for proof in &presentation.verifiable_credential {
+            state
+                .proof_to_verifications_entry(proof, &mut entry)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(Self::new(
+            entry.full_name.as_ref(),
+            entry.accounts.iter().map(|a| {
+                (
+                    a.platform.name().to_string(),
+                    a.id.clone(),
+                    hex::encode(a.cred_id.public_key.as_bytes()),
+                )
+            }),
+        ))
+    }
+}
+
+fn parse(
+    presentation: &serde_json::Value,
+) -> Result<Presentation<ArCurve, Web3IdAttribute>, String> {
+    serde_json::from_value(presentation.clone()).map_err(|e| format!("Invalid presentation: {e}"))
+}
+
+/// Check that the stored columns are the ones proven by the presentation.
+fn check_columns(
+    state: &AppState,
+    presentation: &Presentation<ArCurve, Web3IdAttribute>,
+    columns: &ProvenColumns,
+) -> Result<(), String> {
+    if &ProvenColumns::of_presentation(state, presentation)? != columns {
+        return Err("The stored verification does not match its presentation.".into());
+    }
+    Ok(())
+}
+
+/// Verify the proofs of the presentation against the public data of its
+/// credentials in the block, and return the statuses of the credentials.
+async fn verify_proofs(
+    state: &AppState,
+    presentation: &Presentation<ArCurve, Web3IdAttribute>,
+    block: BlockIdentifier,
+) -> Result<Vec<CredentialStatus>, (Vec<CredentialStatus>, String)> {
+    let public_data = web3id::get_public_data(
+        &mut state.node_client.clone(),
+        state.network,
+        presentation,
+        block,
+    )
+    .await
+    .map_err(|e| (Vec::new(), format!("Unable to look up credentials: {e}")))?;
+    let credential_statuses: Vec<_> = public_data.iter().map(|cm| cm.status).collect();
+    let crypto_params = state.crypto_params.borrow().clone();
+    if let Err(e) = presentation.verify(
+        &crypto_params.params,
+        public_data.iter().map(|cm| &cm.inputs),
+    ) {
+        return Err((credential_statuses, format!("Invalid proof: {e}")));
+    }
+    Ok(credential_statuses)
+}
+
+/// Verify the stored presentation of the verification against the current
+/// public data, and check that it matches the stored columns.
+pub async fn reverify(
+    state: &AppState,
+    presentation: &serde_json::Value,
+    columns: &ProvenColumns,
+) -> Reverification {
+    let failed = |credential_statuses, error: String| Reverification {
+        valid: false,
+        credential_statuses,
+        error: Some(error),
+    };
+    let presentation = match parse(presentation) {
+        Ok(p) => p,
+        Err(e) => return failed(Vec::new(), e),
+    };
+    let credential_statuses =
+        match verify_proofs(state, &presentation, BlockIdentifier::LastFinal).await {
+            Ok(statuses) => statuses,
+            Err((statuses, e)) => return failed(statuses, e),
+        };
+    if let Err(e) = check_columns(state, &presentation, columns) {
+        return failed(credential_statuses, e);
+    }
+    if !credential_statuses
+        .iter()
+        .all(|s| matches!(s, CredentialStatus::Active))
+    {
+        return failed(
+            credential_statuses,
+            "One or more credentials are not active.".into(),
+        );
+    }
+    Reverification {
+        valid: true,
+        credential_statuses,
+        error: None,
+    }
+}
+
+/// Results of re-verifying presentations when verifications are served. The
+/// results only depend on the chain through the last finalized block, so they
+/// are kept until a new block is finalized.
+#[derive(Default)]
+pub struct ReverificationCache {
+    results: Mutex<HashMap<i64, (BlockHash, bool)>>,
+}
+
+impl ReverificationCache {
+    /// Whether the presentation of the verification is valid in the last
+    /// finalized block and matches the stored columns. Credential statuses
+    /// are not checked, since they are reported with the accounts.
+    /// Verifications without a presentation cannot be checked, and are only
+    /// considered valid with `--serve-unverifiable`.
+    pub async fn check(
+        &self,
+        state: &AppState,
+        verification: &DbVerification,
+    ) -> anyhow::Result<bool> {
+        let Some(presentation) = &verification.presentation else {
+            if !state.serve_unverifiable {
+                tracing::debug!(
+                    "Verification {} cannot be re-verified without its presentation.",
+                    verification.id
+                );
+            }
+            return Ok(state.serve_unverifiable);
+        };
+        let block = state
+            .node_client
+            .clone()
+            .get_consensus_info()
+            .await?
+            .last_finalized_block;
+        if let Some((cached_block, valid)) = self.results().get(&verification.id) {
+            if *cached_block == block {
+                return Ok(*valid);
+            }
+        }
+
+        let result = match parse(presentation) {
+            Ok(presentation) => {
+                match verify_proofs(state, &presentation, BlockIdentifier::Given(block)).await {
+                    Ok(_) => check_columns(
+                        state,
+                        &presentation,
+                        &ProvenColumns::of_verification(verification),
+                    ),
+                    Err((_, e)) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            tracing::warn!(
+                "Verification {} failed re-verification: {e}",
+                verification.id
+            );
+        }
+
+        let mut results = self.results();
+        // Results for older blocks are no longer used.
+        results.retain(|_, (cached_block, _)| *cached_block == block);
+        results.insert(verification.id, (block, result.is_ok()));
+        Ok(result.is_ok())
+    }
+
+    fn results(&self) -> std::sync::MutexGuard<'_, HashMap<i64, (BlockHash, bool)>> {
+        self.results.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Whether the verification may be served. Without `--reverify-on-read`, all
+/// stored verifications are served.
+pub async fn may_serve(state: &AppState, verification: &DbVerification) -> anyhow::Result<bool> {
+    match &state.reverification_cache {
+        Some(cache) => cache.check(state, verification).await,
+        None => Ok(true),
+    }
+}
+
+/// The number of verifications with each result in an integrity check.
+#[derive(Default)]
+struct IntegrityCounts {
+    checked: usize,
+    mismatched: usize,
+    unverifiable: usize,
+}
+
+/// Check every `interval` that the stored verifications of the tenant match
+/// their presentations. The result is stored with each verification, and the
+/// mismatches are logged.
+pub async fn check_integrity(state: AppState, interval: std::time::Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match check_all(&state).await {
+            Ok(IntegrityCounts {
+                checked,
+                mismatched,
+                unverifiable,
+            }) => tracing::info!(
+                "Integrity check completed. {mismatched} of {checked} verifications do not match \
+                 their presentations, and {unverifiable} cannot be checked without their \
+                 presentations."
+            ),
+            Err(e) => tracing::warn!("Integrity check failed: {e}"),
+        }
+    }
+}
+
+/// Go through all verifications once, and store the result of each.
+async fn check_all(state: &AppState) -> anyhow::Result<IntegrityCounts> {
+    let mut counts = IntegrityCounts::default();
+    let mut after = None;
+    loop {
+        let verifications = state
+            .database
+            .search_verifications(&VerificationFilter::default(), after, BATCH_SIZE)
+            .await?;
+        let Some(last) = verifications.last() else {
+            return Ok(counts);
+        };
+        after = Some(last.id);
+
+        for verification in verifications {
+            let Some((_, presentation)) = state
+                .database
+                .get_verification_by_id(verification.id)
+                .await?
+            else {
+                // Removed since the search.
+                continue;
+            };
+            counts.checked += 1;
+            let status = match presentation {
+                Some(presentation) => {
+                    let columns = ProvenColumns::of_admin_verification(&verification);
+                    let result = parse(&presentation)
+                        .and_then(|presentation| check_columns(state, &presentation, &columns));
+                    match result {
+                        Ok(()) => IntegrityStatus::Valid,
+                        Err(e) => {
+                            counts.mismatched += 1;
+                            tracing::warn!(
+                                "Verification {} failed the integrity check: {e}",
+                                verification.id
+                            );
+                            IntegrityStatus::Mismatch
+                        }
+                    }
+                }
+                None => {
+                    counts.unverifiable += 1;
+                    IntegrityStatus::Unverifiable
+                }
+            };
+            if verification.integrity_status != Some(status) {
+                state
+                    .database
+                    .set_integrity_status(verification.id, status)
+                    .await?;
+            }
+        }
+    }
+}
@@ -1,41 +1,59 @@
-use crate::db::{Database, DbAccount};
+use crate::db::{AddOutcome, DbAccount, DbVerification, PostgresStorage, SqliteStorage, Storage};
 use anyhow::Context;
 use axum::{
-    extract::{rejection::JsonRejection, Path, State},
+    extract::{rejection::JsonRejection, ConnectInfo, Path, State},
+    http::HeaderMap,
     response::Html,
     routing::{get, patch, post},
     Json, Router,
 };
 use chrono::{DateTime, SecondsFormat, Utc};
+use claims::ClaimConfig;
 use clap::Parser;
 use concordium_rust_sdk::{
     cis4::Cis4Contract,
     contract_client::CredentialStatus,
-    id::{
-        constants::{ArCurve, AttributeKind},
-        id_proof_types::{AtomicProof, AtomicStatement, RevealAttributeStatement},
-    },
-    smart_contracts::common::attributes,
-    types::{ContractAddress, CryptographicParameters},
+    id::constants::ArCurve,
+    types::ContractAddress,
     v2::{self, BlockIdentifier, Scheme},
     web3id::{
-        self, did::Network, CredentialLookupError, CredentialProof, CredentialStatement,
-        Presentation, PresentationVerificationError, Web3IdAttribute,
+        self, did::Network, CredentialHolderId, CredentialLookupError, CredentialProof,
+        CredentialStatement, Presentation, PresentationVerificationError, Web3IdAttribute,
     },
 };
 use db::{PlatformEntry, VerificationsEntry};
 use futures::{future, TryFutureExt};
 use handlebars::Handlebars;
+use platforms::RegisteredPlatform;
+use rand::Rng;
 use reqwest::{StatusCode, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha2::{Digest, Sha256};
-use some_verifier_lib::{Account, FullName, Platform, Verification};
+use some_verifier_lib::{
+    pseudonym,
+    webhook::{AccountId, WebhookEvent},
+    Account, Claim, Platform, Verification,
+};
+use statements::{PlatformAttributes, StatementError};
 use std::{collections::HashMap, fs, sync::Arc};
 use tonic::transport::ClientTlsConfig;
 use tower_http::services::ServeDir;
+use web3id_crypto_params::{refresh_params, CryptoParams, ParamsReceiver};
 
+mod admin;
+mod claims;
+mod data;
 mod db;
+mod integrity;
+mod migrations;
+mod platforms;
+mod profile;
+mod rate_limit;
+mod statements;
+mod sweeper;
+mod tenants;
+mod webhooks;
 
 #[derive(clap::Parser, Debug)]
 #[clap(version, author)]
@@ -55,17 +73,11 @@ struct App {
     )]
     network: Network,
     #[clap(
-        long = "telegram-registry",
-        help = "Address of the Telegram registry smart contract.",
-        env = "SOME_VERIFIER_TELEGRAM_REGISTRY_ADDRESS"
-    )]
-    telegram_registry: ContractAddress,
-    #[clap(
-        long = "discord-registry",
-        help = "Address of the Discord registry smart contract.",
-        env = "SOME_VERIFIER_DISCORD_REGISTRY_ADDRESS"
+        long = "platforms",
+        help = "Path to a JSON file with the configuration of the supported platforms.",
+        env = "SOME_VERIFIER_PLATFORMS"
     )]
-    discord_registry: ContractAddress,
+    platforms: std::path::PathBuf,
     #[clap(
         long = "telegram-bot-name",
         help = "The name (handle) of the Telegram bot.",
@@ -74,30 +86,24 @@ struct App {
     telegram_bot_name: String,
     #[clap(
         long = "discord-bot-token",
-        help = "Discord bot token for looking up usernames.",
+        help = "Discord bot token for looking up usernames. Required if any platform looks up \
+                usernames on Discord.",
         env = "SOME_VERIFIER_DISCORD_BOT_TOKEN"
     )]
-    discord_bot_token: String,
+    discord_bot_token: Option<String>,
+    #[clap(
+        long = "github-api-token",
+        help = "GitHub API token for looking up usernames. Without it, usernames are looked up \
+                anonymously, which GitHub limits to 60 requests per hour.",
+        env = "SOME_VERIFIER_GITHUB_API_TOKEN"
+    )]
+    github_api_token: Option<String>,
     #[clap(
         long = "discord-client-id",
         help = "Discord client id for OAuth2.",
         env = "SOME_VERIFIER_DISCORD_CLIENT_ID"
     )]
     discord_client_id: String,
-    #[clap(
-        long = "telegram-issuer-url",
-        default_value = "http://127.0.0.1:8080",
-        help = "URL of the Telegram Issuer.",
-        env = "SOME_VERIFIER_TELEGRAM_ISSUER_URL"
-    )]
-    telegram_issuer_url: Url,
-    #[clap(
-        long = "discord-issuer-url",
-        default_value = "http://127.0.0.1:8081",
-        help = "URL of the Discord Issuer.",
-        env = "SOME_VERIFIER_DISCORD_ISSUER_URL"
-    )]
-    discord_issuer_url: Url,
     #[clap(
         long = "db",
         default_value = "host=localhost dbname=some-verifier user=postgres password=password \
@@ -106,6 +112,13 @@ struct App {
         env = "SOME_VERIFIER_DB_STRING"
     )]
     db_config: tokio_postgres::Config,
+    #[clap(
+        long = "sqlite",
+        help = "Path to an SQLite database file to use instead of Postgres. The --db and \
+                --db-pool-size options are then ignored.",
+        env = "SOME_VERIFIER_SQLITE"
+    )]
+    sqlite: Option<std::path::PathBuf>,
     #[clap(
         long = "db-pool-size",
         default_value = "16",
@@ -113,6 +126,12 @@ struct App {
         env = "SOME_VERIFIER_DB_POOL_SIZE"
     )]
     pool_size: usize,
+    #[clap(
+        long = "migrate",
+        help = "Apply pending database migrations and exit.",
+        env = "SOME_VERIFIER_MIGRATE"
+    )]
+    migrate: bool,
     #[clap(
         long = "log-level",
         default_value = "info",
@@ -155,31 +174,229 @@ struct App {
         env = "SOME_VERIFIER_DISCORD_INVITE_LINK"
     )]
     discord_invite_link: Url,
+    #[clap(
+        long = "params-refresh-interval",
+        default_value = "60",
+        help = "Interval (in seconds) at which to check whether the cryptographic parameters have \
+                changed.",
+        env = "SOME_VERIFIER_PARAMS_REFRESH_INTERVAL"
+    )]
+    params_refresh_interval: u64,
+    #[clap(
+        long = "revocation-sweep-interval",
+        default_value = "3600",
+        help = "Interval (in seconds) at which to remove verifications with revoked or expired \
+                credentials. If 0, verifications are not removed.",
+        env = "SOME_VERIFIER_REVOCATION_SWEEP_INTERVAL"
+    )]
+    revocation_sweep_interval: u64,
+    #[clap(
+        long = "username-cache-ttl",
+        default_value = "3600",
+        help = "Time (in seconds) for which usernames looked up using the APIs of the platforms \
+                are cached.",
+        env = "SOME_VERIFIER_USERNAME_CACHE_TTL"
+    )]
+    username_cache_ttl: u64,
+    #[clap(
+        long = "challenge-ttl",
+        default_value = "300",
+        help = "Time (in seconds) for which challenges issued by /challenge can be used.",
+        env = "SOME_VERIFIER_CHALLENGE_TTL"
+    )]
+    challenge_ttl: u64,
+    #[clap(
+        long = "allow-timestamp-challenges",
+        help = "Also accept presentations whose challenge is the hash of a recent timestamp when \
+                adding or removing verifications. This is only for compatibility with old \
+                frontends, since such presentations can be replayed.",
+        env = "SOME_VERIFIER_ALLOW_TIMESTAMP_CHALLENGES"
+    )]
+    allow_timestamp_challenges: bool,
+    #[clap(
+        long = "challenge-rate-limit",
+        default_value = "30",
+        help = "The number of challenges a client IP can request from /challenge per minute.",
+        env = "SOME_VERIFIER_CHALLENGE_RATE_LIMIT"
+    )]
+    challenge_rate_limit: u32,
+    #[clap(
+        long = "max-outstanding-challenges",
+        default_value = "10000",
+        help = "The maximum number of unused and unexpired challenges of each tenant. Further \
+                requests to /challenge are rejected until challenges are used or expire.",
+        env = "SOME_VERIFIER_MAX_OUTSTANDING_CHALLENGES"
+    )]
+    max_outstanding_challenges: u64,
+    #[clap(
+        long = "client-ip-header",
+        help = "Header set by a trusted reverse proxy with the address of the client, e.g., \
+                x-forwarded-for. The last address in the header is used. If not given, the peer \
+                address of the connection is used.",
+        env = "SOME_VERIFIER_CLIENT_IP_HEADER"
+    )]
+    client_ip_header: Option<axum::http::HeaderName>,
+    #[clap(
+        long = "admin-token",
+        help = "Token that administrators must provide as a bearer token to use the /admin \
+                endpoints. If not set, the admin endpoints are disabled.",
+        env = "SOME_VERIFIER_ADMIN_TOKEN"
+    )]
+    admin_token: Option<String>,
+    #[clap(
+        long = "claims",
+        help = "Path to a JSON file with the claims about the identity that are required or \
+                accepted in verifications. If not set, no claims are accepted.",
+        env = "SOME_VERIFIER_CLAIMS"
+    )]
+    claims: Option<std::path::PathBuf>,
+    #[clap(
+        long = "presentation-retention-days",
+        help = "Number of days after which the presentations of verifications are purged. The \
+                names, claims and accounts of verifications are kept. If not set, presentations \
+                are kept indefinitely.",
+        env = "SOME_VERIFIER_PRESENTATION_RETENTION_DAYS"
+    )]
+    presentation_retention_days: Option<u64>,
+    #[clap(
+        long = "webhooks",
+        help = "Path to a JSON file with the endpoints that receive webhooks when verifications \
+                change. If not set, no webhooks are sent.",
+        env = "SOME_VERIFIER_WEBHOOKS"
+    )]
+    webhooks: Option<std::path::PathBuf>,
+    #[clap(
+        long = "require-name",
+        help = "Reject verifications that do not reveal the full name of the user.",
+        env = "SOME_VERIFIER_REQUIRE_NAME"
+    )]
+    require_name: bool,
+    #[clap(
+        long = "tenants",
+        help = "Path to a JSON file with the tenants, i.e. communities with their own platforms, \
+                claims and verifications, served under /tenants/{name}. If not set, there is \
+                only the default tenant configured by the other options.",
+        env = "SOME_VERIFIER_TENANTS"
+    )]
+    tenants: Option<std::path::PathBuf>,
+    #[clap(
+        long = "id-hashing-key",
+        help = "Secret key used to store HMACs of platform user ids instead of the ids. The key \
+                must not change once verifications are stored, and it cannot be used with a \
+                database with verifications stored without it. Presentations are not stored, \
+                and usernames cannot be looked up using the platforms' APIs when this is set.",
+        env = "SOME_VERIFIER_ID_HASHING_KEY"
+    )]
+    id_hashing_key: Option<String>,
+    #[clap(
+        long = "omit-usernames",
+        help = "Do not store the usernames revealed in verifications. Verifications are then \
+                returned with empty usernames, unless they are looked up using the platforms' \
+                APIs.",
+        env = "SOME_VERIFIER_OMIT_USERNAMES"
+    )]
+    omit_usernames: bool,
+    #[clap(
+        long = "reverify-on-read",
+        help = "Verify the stored presentation of a verification again against the last \
+                finalized block before serving it. Verifications whose presentations are no \
+                longer valid, or do not match the stored verification, are not served. Results \
+                are cached until a new block is finalized.",
+        env = "SOME_VERIFIER_REVERIFY_ON_READ"
+    )]
+    reverify_on_read: bool,
+    #[clap(
+        long = "serve-unverifiable",
+        help = "With --reverify-on-read, also serve verifications that cannot be re-verified \
+                since their presentations have been purged or were not stored.",
+        env = "SOME_VERIFIER_SERVE_UNVERIFIABLE"
+    )]
+    serve_unverifiable: bool,
+    #[clap(
+        long = "integrity-check-interval",
+        default_value = "0",
+        help = "Interval (in seconds) at which to check that the stored verifications match \
+                their presentations. The result is stored with each verification and reported \
+                by the admin API, and mismatches are logged. If 0, the check is disabled.",
+        env = "SOME_VERIFIER_INTEGRITY_CHECK_INTERVAL"
+    )]
+    integrity_check_interval: u64,
 }
 
 #[derive(Clone)]
 struct AppState {
     http_client: reqwest::Client,
     node_client: v2::Client,
-    telegram_registry: ContractAddress,
-    discord_registry: ContractAddress,
-    telegram_contract: Cis4Contract,
-    discord_contract: Cis4Contract,
-    discord_bot_token: Arc<String>,
-    database: Arc<Database>,
+    platforms: Arc<[RegisteredPlatform]>,
+    discord_bot_token: Option<Arc<str>>,
+    github_api_token: Option<Arc<str>>,
+    database: Arc<dyn Storage>,
     network: Network,
-    crypto_params: Arc<CryptographicParameters>,
+    crypto_params: ParamsReceiver,
+    /// How long usernames looked up using the platforms' APIs are cached.
+    username_cache_ttl: std::time::Duration,
+    /// How long challenges issued by `/challenge` are valid.
+    challenge_ttl: std::time::Duration,
+    /// Whether to accept challenges that are the hash of a timestamp.
+    allow_timestamp_challenges: bool,
+    /// Limits the challenges each client IP can request. It is shared by all
+    /// tenants.
+    challenge_limiter: Arc<rate_limit::IpRateLimiter>,
+    /// The maximum number of outstanding challenges of the tenant.
+    max_outstanding_challenges: u64,
+    /// Token required to use the admin endpoints. If not set, they are
+    /// disabled.
+    admin_token: Option<Arc<str>>,
+    /// Claims about the identity that are accepted in verifications.
+    claims: Arc<[ClaimConfig]>,
+    /// Whether verifications must reveal the full name of the user.
+    require_name: bool,
+    /// Key of the HMAC stored instead of platform user ids. If not set, the
+    /// ids are stored.
+    id_hashing_key: Option<Arc<[u8]>>,
+    /// Whether usernames revealed in verifications are not stored.
+    omit_usernames: bool,
+    /// Results of re-verifying presentations before serving verifications.
+    /// If not set, verifications are served without re-verification.
+    reverification_cache: Option<Arc<integrity::ReverificationCache>>,
+    /// Whether verifications without a presentation are served when they
+    /// are re-verified before serving.
+    serve_unverifiable: bool,
+    /// Endpoints notified when verifications change.
+    webhooks: webhooks::Webhooks,
+    /// Templates of the public profile pages and badges.
+    templates: Arc<Handlebars<'static>>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct FrontendConfig {
+    #[serde(flatten)]
+    links: FrontendLinks,
+    network: Network,
+    /// The path that the API of the tenant is served under.
+    base_path: String,
+    require_name: bool,
+    issuers: HashMap<String, IssuerConfig>,
+    claims: Vec<FrontendClaim>,
+}
+
+/// The bots and groups of a tenant that the frontend links to.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FrontendLinks {
     discord_client_id: String,
     telegram_bot_name: String,
-    network: Network,
     telegram_invite_link: Url,
     discord_invite_link: Url,
-    issuers: HashMap<String, IssuerConfig>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FrontendClaim {
+    #[serde(flatten)]
+    config: ClaimConfig,
+    description: String,
 }
 
 #[derive(Serialize)]
@@ -206,13 +423,31 @@ async fn main() -> anyhow::Result<()> {
             .init();
     }
 
+    if app.migrate {
+        tracing::info!("Migrating database...");
+        if let Some(path) = &app.sqlite {
+            SqliteStorage::migrate(path)?;
+        } else {
+            db::migrate(&app.db_config).await?;
+            tracing::info!(
+                "Database schema is at version {}.",
+                migrations::latest_version()
+            );
+        }
+        return Ok(());
+    }
+
     tracing::info!("Connecting to database...");
-    let database = Database::connect(app.db_config, app.pool_size).await?;
+    let database: Arc<dyn Storage> = match &app.sqlite {
+        Some(path) => Arc::new(SqliteStorage::open(path)?),
+        None => Arc::new(PostgresStorage::connect(app.db_config, app.pool_size).await?),
+    };
 
     anyhow::ensure!(
         app.request_timeout >= 1000,
         "Request timeout should be at least 1s."
     );
+    anyhow::ensure!(app.challenge_ttl > 0, "Challenge TTL should be positive.");
 
     let endpoint = if app.endpoint.uri().scheme() == Some(&Scheme::HTTPS) {
         app.endpoint
@@ -237,76 +472,202 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Unable to establish connection to the node.")?;
 
-    let crypto_params = node_client
-        .get_cryptographic_parameters(BlockIdentifier::LastFinal)
+    anyhow::ensure!(
+        app.params_refresh_interval > 0,
+        "Parameters refresh interval should be positive."
+    );
+    let crypto_params = CryptoParams::query(&mut node_client)
         .await
-        .context("Unable to get cryptographic parameters.")?
-        .response;
+        .context("Unable to get cryptographic parameters.")?;
+    tracing::info!(
+        "Using cryptographic parameters with hash {}.",
+        crypto_params.hash
+    );
+    let (params_sender, crypto_params) = tokio::sync::watch::channel(Arc::new(crypto_params));
+    tokio::spawn(refresh_params(
+        node_client.clone(),
+        std::time::Duration::from_secs(app.params_refresh_interval),
+        params_sender,
+    ));
+
+    let platform_configs =
+        platforms::read_config(&app.platforms, app.discord_bot_token.as_deref())?;
+    anyhow::ensure!(
+        app.id_hashing_key.as_ref().map_or(true, |k| !k.is_empty()),
+        "The id hashing key is empty."
+    );
+    let id_hashing = app.id_hashing_key.is_some();
+    if id_hashing {
+        anyhow::ensure!(
+            !database.has_unhashed_ids().await?,
+            "The database contains user ids that are not hashed. An id hashing key can only be \
+             used with a database where all verifications were stored with it."
+        );
+        anyhow::ensure!(
+            !app.reverify_on_read || app.serve_unverifiable,
+            "Presentations are not stored when user ids are hashed, so no verification would be \
+             served with --reverify-on-read unless --serve-unverifiable is set."
+        );
+    }
+    check_username_lookups(&platform_configs, id_hashing)?;
+    let platforms = register_platforms(&node_client, platform_configs).await?;
+    tracing::info!(
+        "Supported platforms: {}.",
+        platforms
+            .iter()
+            .map(|p| p.config.name.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
 
-    let telegram_contract =
-        Cis4Contract::create(node_client.clone(), app.telegram_registry).await?;
-    let discord_contract = Cis4Contract::create(node_client.clone(), app.discord_registry).await?;
+    let claims = match &app.claims {
+        Some(path) => claims::read_config(path)?,
+        None => Vec::new(),
+    };
 
-    let http_client = reqwest::Client::new();
+    // GitHub rejects API requests without a user agent.
+    let http_client = reqwest::Client::builder()
+        .user_agent(concat!("some-verifier/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Unable to create HTTP client.")?;
+
+    let webhooks = match &app.webhooks {
+        Some(path) => webhooks::Webhooks::start(http_client.clone(), webhooks::read_config(path)?),
+        None => webhooks::Webhooks::default(),
+    };
 
     let state = AppState {
         http_client,
         node_client,
-        telegram_registry: app.telegram_registry,
-        discord_registry: app.discord_registry,
-        telegram_contract,
-        discord_contract,
-        discord_bot_token: Arc::new(app.discord_bot_token),
-        database: Arc::new(database),
+        platforms: platforms.into(),
+        discord_bot_token: app.discord_bot_token.map(Arc::from),
+        github_api_token: app.github_api_token.map(Arc::from),
+        database,
         network: app.network,
-        crypto_params: Arc::new(crypto_params),
+        crypto_params,
+        username_cache_ttl: std::time::Duration::from_secs(app.username_cache_ttl),
+        challenge_ttl: std::time::Duration::from_secs(app.challenge_ttl),
+        allow_timestamp_challenges: app.allow_timestamp_challenges,
+        challenge_limiter: Arc::new(rate_limit::IpRateLimiter::new(
+            std::time::Duration::from_secs(60),
+            app.challenge_rate_limit,
+            app.client_ip_header,
+        )),
+        max_outstanding_challenges: app.max_outstanding_challenges,
+        admin_token: app.admin_token.map(Arc::from),
+        claims: claims.into(),
+        require_name: app.require_name,
+        id_hashing_key: app.id_hashing_key.map(|k| Arc::from(k.into_bytes())),
+        omit_usernames: app.omit_usernames,
+        reverification_cache: app
+            .reverify_on_read
+            .then(|| Arc::new(integrity::ReverificationCache::default())),
+        serve_unverifiable: app.serve_unverifiable,
+        webhooks,
+        templates: Arc::new(profile::templates()?),
+    };
+    let links = FrontendLinks {
+        discord_client_id: app.discord_client_id,
+        telegram_bot_name: app.telegram_bot_name,
+        telegram_invite_link: app.telegram_invite_link,
+        discord_invite_link: app.discord_invite_link,
     };
 
+    // Each tenant gets its own state, sharing the clients and the database
+    // connections with the default tenant.
+    let tenant_configs = match &app.tenants {
+        Some(path) => tenants::read_config(path, app.discord_bot_token.as_deref())?,
+        None => Vec::new(),
+    };
+    let mut tenants = Vec::with_capacity(tenant_configs.len());
+    for config in tenant_configs {
+        check_username_lookups(&config.platforms, id_hashing)
+            .with_context(|| format!("Invalid platforms of tenant {}.", config.name))?;
+        let platforms = register_platforms(&state.node_client, config.platforms).await?;
+        let tenant_state = AppState {
+            platforms: platforms.into(),
+            database: state.database.for_tenant(&config.name),
+            claims: config.claims.into(),
+            require_name: config.require_name,
+            omit_usernames: config.omit_usernames,
+            webhooks: state
+                .webhooks
+                .for_tenant(&config.name, &state.http_client, config.webhooks),
+            admin_token: config
+                .admin_token
+                .map(Arc::from)
+                .or_else(|| state.admin_token.clone()),
+            ..state.clone()
+        };
+        let tenant_links = FrontendLinks {
+            discord_client_id: config
+                .discord_client_id
+                .unwrap_or_else(|| links.discord_client_id.clone()),
+            telegram_bot_name: config
+                .telegram_bot_name
+                .unwrap_or_else(|| links.telegram_bot_name.clone()),
+            telegram_invite_link: config
+                .telegram_invite_link
+                .unwrap_or_else(|| links.telegram_invite_link.clone()),
+            discord_invite_link: config
+                .discord_invite_link
+                .unwrap_or_else(|| links.discord_invite_link.clone()),
+        };
+        tracing::info!("Serving tenant {}.", config.name);
+        tenants.push((config.name, tenant_state, tenant_links));
+    }
+
+    for state in std::iter::once(&state).chain(tenants.iter().map(|(_, state, _)| state)) {
+        if app.revocation_sweep_interval > 0 {
+            tokio::spawn(sweeper::sweep_revocations(
+                state.database.clone(),
+                state.platforms.clone(),
+                state.webhooks.clone(),
+                std::time::Duration::from_secs(app.revocation_sweep_interval),
+            ));
+        }
+
+        if let Some(days) = app.presentation_retention_days {
+            tokio::spawn(sweeper::purge_presentations(
+                state.database.clone(),
+                std::time::Duration::from_secs(days * 24 * 60 * 60),
+            ));
+        }
+
+        if app.integrity_check_interval > 0 {
+            tokio::spawn(integrity::check_integrity(
+                state.clone(),
+                std::time::Duration::from_secs(app.integrity_check_interval),
+            ));
+        }
+    }
+
     // Render index.html with config
     let index_template = fs::read_to_string(app.frontend_assets.join("index.html"))
         .context("Frontend was not built.")?;
     let mut reg = Handlebars::new();
     // Prevent handlebars from escaping inserted object
     reg.register_escape_fn(|s| s.into());
-    let frontend_config = FrontendConfig {
-        discord_client_id: app.discord_client_id,
-        telegram_bot_name: app.telegram_bot_name,
-        network: app.network,
-        telegram_invite_link: app.telegram_invite_link,
-        discord_invite_link: app.discord_invite_link,
-        issuers: [
-            (
-                "telegram".to_string(),
-                IssuerConfig {
-                    url: app.telegram_issuer_url.to_string(),
-                    index: app.telegram_registry.index.to_string(),
-                    subindex: app.telegram_registry.subindex.to_string(),
-                },
-            ),
-            (
-                "discord".to_string(),
-                IssuerConfig {
-                    url: app.discord_issuer_url.to_string(),
-                    index: app.discord_registry.index.to_string(),
-                    subindex: app.discord_registry.subindex.to_string(),
-                },
-            ),
-        ]
-        .into(),
+    let render_index = |state: &AppState, links: FrontendLinks, base_path: String| {
+        let config = frontend_config(state, links, base_path);
+        let config_string = serde_json::to_string(&config)?;
+        anyhow::Ok(reg.render_template(&index_template, &json!({ "config": config_string }))?)
     };
-    let config_string = serde_json::to_string(&frontend_config)?;
-    let index_html = reg.render_template(&index_template, &json!({ "config": config_string }))?;
 
     tracing::info!("Starting server...");
-    let serve_dir_service = ServeDir::new(app.frontend_assets.join("assets"));
-    let router = Router::new()
-        .route("/", get(|| async { Html(index_html) }))
-        .nest_service("/assets", serve_dir_service)
-        .route("/verifications", post(add_verification))
-        .route("/verifications", patch(remove_verification))
-        .route("/verifications/:platform/:id", get(get_verification))
+    let index_html = render_index(&state, links, String::new())?;
+    let mut router = tenant_router(state.clone(), index_html, &app.frontend_assets)
         .route("/health", get(health))
-        .with_state(state)
+        .with_state(state);
+    for (name, state, links) in tenants {
+        let base_path = format!("/tenants/{name}");
+        let index_html = render_index(&state, links, base_path.clone())?;
+        router = router.nest(
+            &base_path,
+            tenant_router(state.clone(), index_html, &app.frontend_assets).with_state(state),
+        );
+    }
+    let router = router
         .layer(
             tower_http::trace::TraceLayer::new_for_http()
                 .make_span_with(tower_http::trace::DefaultMakeSpan::new())
@@ -321,13 +682,105 @@ async fn main() -> anyhow::Result<()> {
     let socket = app.listen_address;
     let shutdown_signal = set_shutdown()?;
     axum::Server::bind(&socket)
-        .serve(router.into_make_service())
+        .serve(router.into_make_service_with_connect_info::<std::net::SocketAddr>())
         .with_graceful_shutdown(shutdown_signal)
         .await?;
 
     Ok(())
 }
 
+/// Create the clients of the registries of the platforms.
+async fn register_platforms(
+    node_client: &v2::Client,
+    configs: Vec<platforms::PlatformConfig>,
+) -> anyhow::Result<Vec<RegisteredPlatform>> {
+    let mut platforms = Vec::with_capacity(configs.len());
+    for config in configs {
+        let contract = Cis4Contract::create(node_client.clone(), config.registry)
+            .await
+            .with_context(|| format!("Unable to find registry for {}.", config.name))?;
+        platforms.push(RegisteredPlatform {
+            config,
+            contract,
+            backoff: Default::default(),
+        });
+    }
+    Ok(platforms)
+}
+
+/// Usernames can only be looked up using the platforms' APIs if the user ids
+/// are stored.
+fn check_username_lookups(
+    platforms: &[platforms::PlatformConfig],
+    id_hashing: bool,
+) -> anyhow::Result<()> {
+    if !id_hashing {
+        return Ok(());
+    }
+    if let Some(platform) = platforms.iter().find(|p| p.username_lookup.is_remote()) {
+        anyhow::bail!(
+            "Platform {} looks up usernames using its API, which is not possible when user ids \
+             are hashed.",
+            platform.name.name()
+        );
+    }
+    Ok(())
+}
+
+/// The configuration passed to the frontend of a tenant.
+fn frontend_config(state: &AppState, links: FrontendLinks, base_path: String) -> FrontendConfig {
+    FrontendConfig {
+        links,
+        network: state.network,
+        base_path,
+        require_name: state.require_name,
+        issuers: state
+            .platforms
+            .iter()
+            .map(|p| {
+                let config = &p.config;
+                (
+                    config.name.name().to_string(),
+                    IssuerConfig {
+                        url: config.issuer_url.to_string(),
+                        index: config.registry.index.to_string(),
+                        subindex: config.registry.subindex.to_string(),
+                    },
+                )
+            })
+            .collect(),
+        claims: state
+            .claims
+            .iter()
+            .map(|c| FrontendClaim {
+                config: c.clone(),
+                description: c.description(),
+            })
+            .collect(),
+    }
+}
+
+/// The frontend and API of a tenant. The default tenant is served at the
+/// root, and the others under `/tenants/{name}`.
+fn tenant_router(
+    state: AppState,
+    index_html: String,
+    frontend_assets: &std::path::Path,
+) -> Router<AppState> {
+    Router::new()
+        .route("/", get(|| async { Html(index_html) }))
+        .nest_service("/assets", ServeDir::new(frontend_assets.join("assets")))
+        .route("/challenge", post(create_challenge))
+        .route("/verifications", post(add_verification))
+        .route("/verifications", patch(remove_verification))
+        .route("/verifications/:platform/:id", get(get_verification))
+        .route("/profile/:platform/:id", get(profile::profile))
+        .route("/badge/:platform/:file", get(profile::badge))
+        .route("/data/export", post(data::export_data))
+        .route("/data/erase", post(data::erase_data))
+        .nest("/admin", admin::router(state))
+}
+
 #[derive(Debug, thiserror::Error)]
 enum Error {
     #[error("Unable to parse request: {0}")]
@@ -342,16 +795,28 @@ enum Error {
     InvalidProof(#[from] PresentationVerificationError),
     #[error("Challenge did not match timestamp.")]
     InvalidChallenge,
+    #[error("The challenge is unknown, expired, or has already been used.")]
+    UnknownChallenge,
+    #[error("A challenge from /challenge is required.")]
+    TimestampChallengesDisabled,
+    #[error("Too many challenges have been requested. Try again later.")]
+    TooManyChallenges,
     #[error("Expected at least 2 statements, got {0}.")]
     NotEnoughStatements(usize),
     #[error("Expected exactly 1 statement, got {0}.")]
     NotSingleStatement(usize),
-    #[error("A statement was invalid.")]
-    InvalidStatement,
+    #[error("A statement was invalid: {0}")]
+    InvalidStatement(#[from] StatementError),
     #[error("A statement was from the wrong issuer.")]
     InvalidIssuer,
     #[error("Attempt to add duplicate users: {0}")]
     DuplicateUserIds(anyhow::Error),
+    #[error("The required claim {0} was not proven.")]
+    MissingClaim(String),
+    #[error("The full name must be revealed.")]
+    NameRequired,
+    #[error("The credential id is not valid hex.")]
+    InvalidCredentialId,
     #[error("The database returned an error: {0}")]
     Database(anyhow::Error),
 }
@@ -370,6 +835,10 @@ impl axum::response::IntoResponse for Error {
                 tracing::error!("Internal error: {e}");
                 (StatusCode::INTERNAL_SERVER_ERROR, Json(format!("{e}")))
             }
+            error @ Error::TooManyChallenges => {
+                tracing::debug!("Rate limited: {error}");
+                (StatusCode::TOO_MANY_REQUESTS, Json(format!("{}", error)))
+            }
             error => {
                 tracing::debug!("Bad request: {error}");
                 (StatusCode::BAD_REQUEST, Json(format!("{}", error)))
@@ -382,7 +851,101 @@ impl axum::response::IntoResponse for Error {
 #[derive(Deserialize)]
 struct Request {
     proof: Presentation<ArCurve, Web3IdAttribute>,
-    timestamp: DateTime<Utc>,
+    /// If present, the challenge of the presentation is the hash of the
+    /// timestamp. This is only supported for compatibility with old frontends
+    /// if `--allow-timestamp-challenges` is set, and only when adding or
+    /// removing verifications. Otherwise the challenge must be issued by
+    /// `/challenge`.
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
+    /// Whether the full name may be shown on the public profile and badge.
+    /// Only used when adding a verification.
+    #[serde(default, rename = "publicName")]
+    public_name: bool,
+}
+
+/// The operation that a challenge is issued for.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+enum Operation {
+    Add,
+    Remove,
+    Export,
+    Erase,
+}
+
+impl Operation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Operation::Add => "add",
+            Operation::Remove => "remove",
+            Operation::Export => "export",
+            Operation::Erase => "erase",
+        }
+    }
+
+    /// Whether a presentation for the operation may have the hash of a
+    /// timestamp as its challenge, if `--allow-timestamp-challenges` is set.
+    /// Old frontends only add and remove verifications, and data exports and
+    /// erasures require a fresh presentation.
+    fn allows_timestamp_challenge(self) -> bool {
+        matches!(self, Operation::Add | Operation::Remove)
+    }
+}
+
+#[derive(Deserialize)]
+struct ChallengeRequest {
+    operation: Operation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChallengeResponse {
+    /// The challenge, hex encoded.
+    challenge: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Issue a single-use challenge for the operation. The number of challenges
+/// is limited per client IP, and the number of outstanding challenges per
+/// tenant, since every challenge is stored until it is used or expires.
+#[tracing::instrument(level = "info", skip_all)]
+async fn create_challenge(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: HeaderMap,
+    request: Result<Json<ChallengeRequest>, JsonRejection>,
+) -> Result<Json<ChallengeResponse>, Error> {
+    let Json(ChallengeRequest { operation }) = request?;
+    let client_ip = state.challenge_limiter.client_ip(peer, &headers);
+    if !state
+        .challenge_limiter
+        .check(client_ip, std::time::Instant::now())
+    {
+        return Err(Error::TooManyChallenges);
+    }
+    let mut challenge = [0u8; 32];
+    rand::thread_rng().fill(&mut challenge);
+    let expires_at = Utc::now()
+        + chrono::Duration::from_std(state.challenge_ttl).expect("Challenge TTL is in range.");
+    let added = state
+        .database
+        .add_challenge(
+            &challenge,
+            operation.as_str(),
+            state.challenge_ttl,
+            state.max_outstanding_challenges,
+        )
+        .await
+        .map_err(Error::Database)?;
+    if !added {
+        tracing::warn!("The maximum number of outstanding challenges is reached.");
+        return Err(Error::TooManyChallenges);
+    }
+    Ok(Json(ChallengeResponse {
+        challenge: hex::encode(challenge),
+        expires_at,
+    }))
 }
 
 #[tracing::instrument(level = "info", skip_all)]
@@ -391,9 +954,11 @@ async fn add_verification(
     request: Result<Json<Request>, JsonRejection>,
 ) -> Result<StatusCode, Error> {
     let Json(request) = request?;
-    let _ = state.verify_request(&request).await?;
+    let _ = state.verify_request(&request, Operation::Add).await?;
     // Check the statements and add them to the database
-    let Request { proof, .. } = request;
+    let Request {
+        proof, public_name, ..
+    } = request;
 
     // Check the statements and add them to the database
     let num_statements = proof.verifiable_credential.len();
@@ -402,16 +967,48 @@ async fn add_verification(
     }
 
     let mut entry = VerificationsEntry::from_presentation(&proof);
+    entry.public_name = public_name;
     for proof in &proof.verifiable_credential {
         state.proof_to_verifications_entry(proof, &mut entry)?;
     }
+    if let Some(claim) = state
+        .claims
+        .iter()
+        .find(|c| c.required && !entry.claims.contains(&c.name))
+    {
+        return Err(Error::MissingClaim(claim.name.clone()));
+    }
+    if state.require_name && entry.full_name.is_none() {
+        return Err(Error::NameRequired);
+    }
+    // The presentation reveals the user ids and names, so it would defeat
+    // storing pseudonyms.
+    if state.id_hashing_key.is_some() {
+        entry.presentation = None;
+    }
 
+    let accounts = entry
+        .accounts
+        .iter()
+        .map(|a| AccountId {
+            platform: a.platform.clone(),
+            id: a.id.clone(),
+        })
+        .collect();
     match state.database.add_verification(entry).await {
-        Ok(None) => {
+        Ok(AddOutcome::Added { replaced }) => {
             tracing::info!("Successfully added new verification.");
+            for accounts in replaced {
+                state
+                    .webhooks
+                    .send(WebhookEvent::VerificationRemoved { accounts });
+            }
+            state
+                .webhooks
+                .send(WebhookEvent::VerificationAdded { accounts });
             Ok(StatusCode::CREATED)
         }
-        Ok(Some(user_id)) => Err(Error::DuplicateUserIds(anyhow::anyhow!(
+        Ok(AddOutcome::DuplicateUser(user_id)) => Err(Error::DuplicateUserIds(anyhow::anyhow!(
             "Duplicate user id: {user_id}."
         ))),
         Err(err) => {
@@ -427,33 +1024,21 @@ async fn remove_verification(
     request: Result<Json<Request>, JsonRejection>,
 ) -> Result<StatusCode, Error> {
     let Json(request) = request?;
-    let creds_with_metadata = state.verify_request(&request).await?;
-
-    let Some((credential, &[])) = creds_with_metadata.credential_statements.split_first() else {
-        return Err(Error::NotSingleStatement(
-            creds_with_metadata.credential_statements.len(),
-        ));
-    };
-
-    let CredentialStatement::Web3Id {
-        contract,
-        credential,
-        ..
-    } = credential
-    else {
-        return Err(Error::InvalidStatement);
-    };
-
-    let platform = state.get_platform_for_contract(contract)?;
+    let (platform, credential) = state
+        .verify_single_credential(&request, Operation::Remove)
+        .await?;
 
     match state
         .database
-        .remove_verification(credential, platform)
+        .remove_verification(&credential, &platform)
         .await
     {
         Ok(removed) => {
-            if removed {
+            if let Some(accounts) = removed {
                 tracing::debug!("Successfully removed verification for {credential}.");
+                state
+                    .webhooks
+                    .send(WebhookEvent::VerificationRemoved { accounts });
                 Ok(StatusCode::OK)
             } else {
                 tracing::debug!(
@@ -470,14 +1055,32 @@ async fn remove_verification(
 }
 
 impl AppState {
-    fn get_platform_for_contract(&self, address: &ContractAddress) -> Result<Platform, Error> {
-        match address {
-            addr if addr == &self.telegram_registry => Ok(Platform::Telegram),
-            addr if addr == &self.discord_registry => Ok(Platform::Discord),
-            _ => Err(Error::InvalidIssuer),
+    fn get_platform_for_contract(
+        &self,
+        address: &ContractAddress,
+    ) -> Result<&RegisteredPlatform, Error> {
+        self.platforms
+            .iter()
+            .find(|p| &p.config.registry == address)
+            .ok_or(Error::InvalidIssuer)
+    }
+
+    /// The id under which an account is stored, which is its pseudonym if an
+    /// id hashing key is configured.
+    pub(crate) fn stored_id(&self, platform: &Platform, id: &str) -> String {
+        match &self.id_hashing_key {
+            Some(key) => pseudonym::hash_id(key, platform, id),
+            None => id.to_string(),
         }
     }
 
+    fn get_platform(&self, platform: &Platform) -> anyhow::Result<&RegisteredPlatform> {
+        self.platforms
+            .iter()
+            .find(|p| &p.config.name == platform)
+            .with_context(|| format!("Platform {platform} is not supported."))
+    }
+
     pub(crate) fn proof_to_verifications_entry(
         &self,
         proof: &CredentialProof<ArCurve, Web3IdAttribute>,
@@ -486,127 +1089,145 @@ impl AppState {
         match proof {
             // Platform verification (Telegram, Discord, etc.)
             CredentialProof::Web3Id {
+                network,
                 contract,
                 proofs,
                 holder,
                 ..
             } => {
-                let (id, username) = match &proofs[..] {
-                    [(
-                        _,
-                        AtomicProof::RevealAttribute {
-                            attribute: Web3IdAttribute::String(AttributeKind(id)),
-                            ..
-                        },
-                    ), (
-                        _,
-                        AtomicProof::RevealAttribute {
-                            attribute: Web3IdAttribute::String(AttributeKind(username)),
-                            ..
-                        },
-                    )] => (id.clone(), username.clone()),
-                    _ => {
-                        return Err(Error::InvalidStatement);
-                    }
-                };
-
-                let platform_entry = PlatformEntry {
-                    id,
+                if *network != self.network {
+                    return Err(StatementError::WrongNetwork.into());
+                }
+                let PlatformAttributes { id, username } =
+                    statements::platform_attributes(&statements::revealed(proofs))?;
+
+                let platform = self
+                    .get_platform_for_contract(contract)?
+                    .config
+                    .name
+                    .clone();
+                // Make sure we have distinct statements for each platform.
+                if entry.accounts.iter().any(|acc| acc.platform == platform) {
+                    return Err(StatementError::DuplicatePlatform(platform).into());
+                }
+                entry.accounts.push(PlatformEntry {
+                    id: self.stored_id(&platform, &id),
+                    platform,
                     cred_id: *holder,
-                    username,
-                };
-                match self.get_platform_for_contract(contract)? {
-                    Platform::Telegram => {
-                        // Make sure we have two distinct statements.
-                        if entry.telegram.replace(platform_entry).is_some() {
-                            return Err(Error::InvalidStatement);
-                        }
-                    }
-                    Platform::Discord => {
-                        if entry.discord.replace(platform_entry).is_some() {
-                            return Err(Error::InvalidStatement);
-                        }
-                    }
-                };
+                    username: if self.omit_usernames {
+                        String::new()
+                    } else {
+                        username
+                    },
+                });
 
                 Ok(())
             }
-            // Full name
-            CredentialProof::Account { proofs, .. } => {
-                // There should be exactly a first and last name
-                if !proofs.len() == 2 {
-                    return Err(Error::InvalidStatement);
-                }
-
-                let mut first_name = None;
-                let mut last_name = None;
-                for (statement, proof) in proofs {
-                    let AtomicProof::RevealAttribute {
-                        attribute: Web3IdAttribute::String(AttributeKind(name)),
-                        ..
-                    } = proof
-                    else {
-                        return Err(Error::InvalidStatement);
-                    };
-                    let AtomicStatement::RevealAttribute {
-                        statement: RevealAttributeStatement { attribute_tag },
-                    } = statement
-                    else {
-                        return Err(Error::InvalidStatement);
-                    };
-                    if attribute_tag.0 == attributes::FIRST_NAME.0 {
-                        first_name = Some(name.clone());
-                    } else if attribute_tag.0 == attributes::LAST_NAME.0 {
-                        last_name = Some(name.clone());
-                    } else {
-                        return Err(Error::InvalidStatement);
-                    }
+            // Full name and claims about the identity
+            CredentialProof::Account {
+                network, proofs, ..
+            } => {
+                if *network != self.network {
+                    return Err(StatementError::WrongNetwork.into());
                 }
-                let Some(first_name) = first_name else {
-                    return Err(Error::InvalidStatement);
-                };
-                let Some(last_name) = last_name else {
-                    return Err(Error::InvalidStatement);
-                };
-                if entry
-                    .full_name
-                    .replace(FullName {
-                        first_name,
-                        last_name,
-                    })
-                    .is_some()
-                {
-                    return Err(Error::InvalidStatement);
+                // There should be at most one identity credential.
+                if entry.full_name.is_some() || !entry.claims.is_empty() {
+                    return Err(StatementError::DuplicateIdentity.into());
                 }
-
+                let identity = statements::identity_statements(
+                    &statements::revealed(proofs),
+                    &self.claims,
+                    Utc::now().date_naive(),
+                )?;
+                entry.full_name = identity.full_name;
+                entry.claims = identity.claims;
                 Ok(())
             }
         }
     }
 
+    /// Verify a request with a presentation of a single social media
+    /// credential, and return its platform and credential holder id.
+    async fn verify_single_credential(
+        &mut self,
+        request: &Request,
+        operation: Operation,
+    ) -> Result<(Platform, CredentialHolderId), Error> {
+        let creds_with_metadata = self.verify_request(request, operation).await?;
+
+        let Some((credential, &[])) = creds_with_metadata.credential_statements.split_first()
+        else {
+            return Err(Error::NotSingleStatement(
+                creds_with_metadata.credential_statements.len(),
+            ));
+        };
+
+        let CredentialStatement::Web3Id {
+            network,
+            contract,
+            credential,
+            ..
+        } = credential
+        else {
+            return Err(StatementError::NotPlatformCredential.into());
+        };
+        if *network != self.network {
+            return Err(StatementError::WrongNetwork.into());
+        }
+
+        let platform = self
+            .get_platform_for_contract(contract)?
+            .config
+            .name
+            .clone();
+        Ok((platform, *credential))
+    }
+
     /// Verify the request. In particular this checks
     /// - all credentials mentioned in the request exist, and are active (in
     ///   particular they have not expired)
     /// - all credentials are on the required network
     /// - cryptographic proofs are valid
-    /// - the timestamp in the request is no more than 10min from present.
+    /// - the challenge was issued for the operation, has not expired, and has
+    ///   not been used before. Or, if timestamp challenges are allowed for the
+    ///   operation, the timestamp in the request is no more than 10min from
+    ///   present.
     async fn verify_request(
         &mut self,
         request: &Request,
+        operation: Operation,
     ) -> Result<web3id::Request<ArCurve, Web3IdAttribute>, Error> {
-        let Request { proof, timestamp } = request;
+        let Request {
+            proof, timestamp, ..
+        } = request;
 
-        let delta = Utc::now().signed_duration_since(*timestamp);
-        if delta.num_minutes().abs() > 10 {
-            return Err(Error::InvalidTimestamp);
-        }
+        if let Some(timestamp) = timestamp {
+            if !self.allow_timestamp_challenges || !operation.allows_timestamp_challenge() {
+                return Err(Error::TimestampChallengesDisabled);
+            }
+            let delta = Utc::now().signed_duration_since(*timestamp);
+            if delta.num_minutes().abs() > 10 {
+                return Err(Error::InvalidTimestamp);
+            }
 
-        // Check that the challenge is the hash of the supplied timestamp
-        let iso_time = timestamp.to_rfc3339_opts(SecondsFormat::Millis, true);
-        let hash = Sha256::digest(iso_time.as_bytes());
+            // Check that the challenge is the hash of the supplied timestamp
+            let iso_time = timestamp.to_rfc3339_opts(SecondsFormat::Millis, true);
+            let hash = Sha256::digest(iso_time.as_bytes());
 
-        if proof.presentation_context[..] != hash[..] {
-            return Err(Error::InvalidChallenge);
+            if proof.presentation_context[..] != hash[..] {
+                return Err(Error::InvalidChallenge);
+            }
+        } else {
+            // The challenge is used up before the proofs are checked, so that
+            // it cannot be used by concurrent requests.
+            let valid = self
+                .database
+                .use_challenge(&proof.presentation_context[..], operation.as_str())
+                .await
+                .map_err(Error::Database)?;
+            if !valid {
+                return Err(Error::UnknownChallenge);
+            }
         }
 
         let public_data = web3id::get_public_data(
@@ -625,21 +1246,28 @@ impl AppState {
             return Err(Error::InactiveCredentials);
         }
         // And then verify the cryptographic proofs
-        let request = proof.verify(&self.crypto_params, public_data.iter().map(|cm| &cm.inputs))?;
+        let crypto_params = self.crypto_params.borrow().clone();
+        let request = proof.verify(
+            &crypto_params.params,
+            public_data.iter().map(|cm| &cm.inputs),
+        )?;
 
         Ok(request)
     }
 }
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 struct Health {
     version: &'static str,
+    crypto_params_hash: String,
 }
 
-#[tracing::instrument(level = "info")]
-async fn health() -> Json<Health> {
+#[tracing::instrument(level = "info", skip_all)]
+async fn health(State(state): State<AppState>) -> Json<Health> {
     Json(Health {
         version: env!("CARGO_PKG_VERSION"),
+        crypto_params_hash: state.crypto_params.borrow().hash.clone(),
     })
 }
 
@@ -648,45 +1276,17 @@ async fn get_verification(
     State(state): State<AppState>,
     Path((platform, id)): Path<(Platform, String)>,
 ) -> Result<Json<Verification>, StatusCode> {
-    let verification = state.database.get_verification(&id, platform).await;
+    let id = state.stored_id(&platform, &id);
+    let verification = state.database.get_verification(&id, &platform).await;
     match verification {
-        Ok(Some(verification)) => {
-            // Futures that simultaneously look up username and revocation status of user
-            // The futures are then mapped to Accounts
-            let futures = verification.accounts.iter().map(|acc| {
-                future::try_join3(
-                    async { Ok(acc.platform) },
-                    get_username(&state, acc),
-                    get_credential_status(&state, acc, &verification.presentation),
-                )
-                .map_ok(|(platform, username, cred_status)| Account {
-                    platform,
-                    username,
-                    cred_status,
-                })
-            });
-
-            // Keep all the non-error values since the others could not get a username
-            // or a revocation status for whatever reason, probably because the user
-            // is not verified with that platform
-            let accounts = future::join_all(futures)
-                .await
-                .into_iter()
-                .filter_map(|res| {
-                    if let Err(e) = &res {
-                        tracing::error!("Failed to look up user: {e}");
-                    }
-                    res.ok()
-                })
-                .collect();
-
-            let result = Verification {
-                accounts,
-                full_name: verification.full_name,
-            };
-
-            Ok(Json(result))
-        }
+        Ok(Some(verification)) => match integrity::may_serve(&state, &verification).await {
+            Ok(true) => Ok(Json(resolve_verification(&state, verification).await)),
+            Ok(false) => Ok(Json(Verification::default())),
+            Err(err) => {
+                tracing::error!("Unable to re-verify verification: {err}");
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
         Ok(None) => Ok(Json(Verification::default())),
         Err(err) => {
             tracing::error!("Database error when looking up verification: {err}");
@@ -695,38 +1295,108 @@ async fn get_verification(
     }
 }
 
-const DISCORD_API_ENDPOINT: &str = "https://discord.com/api/v10";
+/// Look up the current usernames and credential statuses of the accounts of a
+/// stored verification, and the descriptions of its claims. The full name is
+/// only included if the user made it public.
+async fn resolve_verification(state: &AppState, verification: DbVerification) -> Verification {
+    // Futures that simultaneously look up username and revocation status of user
+    // The futures are then mapped to Accounts
+    let futures = verification.accounts.iter().map(|acc| {
+        future::try_join3(
+            async { Ok(acc.platform.clone()) },
+            get_username(state, acc),
+            get_credential_status(state, acc),
+        )
+        .map_ok(|(platform, username, cred_status)| Account {
+            platform,
+            username,
+            cred_status,
+        })
+    });
 
-#[derive(Deserialize)]
-struct DiscordUser {
-    username: String,
-    discriminator: String,
+    // Keep all the non-error values since the others could not get a username
+    // or a revocation status for whatever reason, probably because the user
+    // is not verified with that platform
+    let accounts = future::join_all(futures)
+        .await
+        .into_iter()
+        .filter_map(|res| {
+            if let Err(e) = &res {
+                tracing::error!("Failed to look up user: {e}");
+            }
+            res.ok()
+        })
+        .collect();
+
+    // Claims that are no longer configured are shown by their name.
+    let claims = verification
+        .claims
+        .into_iter()
+        .map(|name| {
+            let description = state
+                .claims
+                .iter()
+                .find(|c| c.name == name)
+                .map_or_else(|| name.clone(), |c| c.description());
+            Claim { name, description }
+        })
+        .collect();
+
+    Verification {
+        accounts,
+        full_name: verification.full_name.filter(|_| verification.public_name),
+        claims,
+    }
 }
 
 /// Looks up the username of the given account.
 #[tracing::instrument(level = "debug", skip_all, fields(username = account.username, user_id = account.id))]
 async fn get_username(state: &AppState, account: &DbAccount) -> anyhow::Result<String> {
-    match account.platform {
-        Platform::Telegram => Ok(account.username.clone()),
-        Platform::Discord => {
-            let user = state
-                .http_client
-                .get(format!("{DISCORD_API_ENDPOINT}/users/{}", account.id))
-                .header("Authorization", format!("Bot {}", state.discord_bot_token))
-                .send()
-                .await?
-                .json::<DiscordUser>()
-                .await?;
-
-            // Discord has two types of usernames, with discriminator (e.g. abcd#1234),
-            // and without (e.g. abcdef). In the latter case, the discriminator is "0"
-            let username = if user.discriminator == "0" {
-                user.username
-            } else {
-                format!("{}#{}", user.username, user.discriminator)
-            };
+    let platform = state.get_platform(&account.platform)?;
+    let lookup = &platform.config.username_lookup;
+    if !lookup.is_remote() {
+        return Ok(account.username.clone());
+    }
+
+    let cached = match state
+        .database
+        .get_cached_username(&account.platform, &account.id, state.username_cache_ttl)
+        .await
+    {
+        Ok(Some((username, true))) => return Ok(username),
+        Ok(cached) => cached.map(|(username, _)| username),
+        Err(e) => {
+            tracing::warn!("Unable to get cached username: {e}");
+            None
+        }
+    };
+
+    match lookup
+        .lookup(
+            &state.http_client,
+            state.discord_bot_token.as_deref(),
+            state.github_api_token.as_deref(),
+            &platform.backoff,
+            account,
+        )
+        .await
+    {
+        Ok(username) => {
+            if let Err(e) = state
+                .database
+                .cache_username(&account.platform, &account.id, &username)
+                .await
+            {
+                tracing::warn!("Unable to cache username: {e}");
+            }
             Ok(username)
         }
+        Err(e) => {
+            // Fall back to the last known username, or the username from the
+            // time of verification.
+            tracing::warn!("Unable to look up username, using the last known one: {e}");
+            Ok(cached.unwrap_or_else(|| account.username.clone()))
+        }
     }
 }
 
@@ -734,25 +1404,12 @@ async fn get_username(state: &AppState, account: &DbAccount) -> anyhow::Result<S
 async fn get_credential_status(
     state: &AppState,
     account: &DbAccount,
-    proof: &Presentation<ArCurve, Web3IdAttribute>,
 ) -> anyhow::Result<CredentialStatus> {
-    let (mut contract_client, registry) = match account.platform {
-        Platform::Telegram => (state.telegram_contract.clone(), state.telegram_registry),
-        Platform::Discord => (state.discord_contract.clone(), state.discord_registry),
-    };
-
-    let cred_id = proof
-        .metadata()
-        .find_map(|cred| match cred.cred_metadata {
-            web3id::CredentialMetadata::Web3Id { contract, holder } if contract == registry => {
-                Some(holder)
-            }
-            _ => None,
-        })
-        .with_context(|| format!("No credential for {} in presentation", account.platform))?;
+    let platform = state.get_platform(&account.platform)?;
+    let mut contract_client = platform.contract.clone();
 
     contract_client
-        .credential_status(cred_id, BlockIdentifier::LastFinal)
+        .credential_status(account.cred_id, BlockIdentifier::LastFinal)
         .await
         .context("Failed to get credential status")
 }
@@ -0,0 +1,154 @@
+//! Versioned migrations of the database schema. The version of the schema is
+//! recorded in the `schema_version` table, and migrations are applied in
+//! order by the `--migrate` option.
+use anyhow::Context;
+use tokio_postgres::Client;
+
+const SCHEMA_VERSION_TABLE: &str = "schema_version";
+
+/// Key of the advisory lock that is held while migrating, so that concurrent
+/// migrations of the same database are serialized.
+const MIGRATION_LOCK: i64 = 0x736f_6d65_7665_7269;
+
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// All known migrations, in order of increasing version. Existing migrations
+/// must never be changed, only new ones added.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: include_str!("../resources/migrations/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "generic accounts table",
+        sql: include_str!("../resources/migrations/0002_accounts.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "username cache",
+        sql: include_str!("../resources/migrations/0003_username_cache.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "challenges",
+        sql: include_str!("../resources/migrations/0004_challenges.sql"),
+    },
+    Migration {
+        version: 5,
+        description: "identity claims",
+        sql: include_str!("../resources/migrations/0005_claims.sql"),
+    },
+    Migration {
+        version: 6,
+        description: "presentation retention",
+        sql: include_str!("../resources/migrations/0006_retention.sql"),
+    },
+    Migration {
+        version: 7,
+        description: "public names",
+        sql: include_str!("../resources/migrations/0007_public_name.sql"),
+    },
+    Migration {
+        version: 8,
+        description: "tenants",
+        sql: include_str!("../resources/migrations/0008_tenants.sql"),
+    },
+    Migration {
+        version: 9,
+        description: "integrity status",
+        sql: include_str!("../resources/migrations/0009_integrity_status.sql"),
+    },
+];
+
+/// The version of the schema that this binary expects.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+async fn ensure_version_table(client: &Client) -> anyhow::Result<()> {
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {SCHEMA_VERSION_TABLE} (version INT8 PRIMARY KEY, \
+             description VARCHAR NOT NULL, applied_at TIMESTAMPTZ NOT NULL DEFAULT now())"
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Get the current version of the schema, which is 0 if no migrations have
+/// been applied.
+pub async fn current_version(client: &Client) -> anyhow::Result<i64> {
+    ensure_version_table(client).await?;
+    let row = client
+        .query_one(
+            &format!("SELECT COALESCE(MAX(version), 0) FROM {SCHEMA_VERSION_TABLE}"),
+            &[],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Check that the schema is at the version expected by this binary.
+pub async fn check(client: &Client) -> anyhow::Result<()> {
+    let current = current_version(client).await?;
+    let latest = latest_version();
+    anyhow::ensure!(
+        current <= latest,
+        "The database schema is at version {current}, which is newer than the latest version \
+         {latest} known by this verifier. Refusing to start."
+    );
+    anyhow::ensure!(
+        current == latest,
+        "The database schema is at version {current}, but version {latest} is required. Run the \
+         verifier with --migrate to apply the pending migrations."
+    );
+    Ok(())
+}
+
+/// Apply all pending migrations. Each migration is applied in its own
+/// transaction together with the update of the schema version.
+pub async fn migrate(client: &mut Client) -> anyhow::Result<()> {
+    ensure_version_table(client).await?;
+    for migration in MIGRATIONS {
+        let tx = client.transaction().await?;
+        tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])
+            .await?;
+        let current: i64 = tx
+            .query_one(
+                &format!("SELECT COALESCE(MAX(version), 0) FROM {SCHEMA_VERSION_TABLE}"),
+                &[],
+            )
+            .await?
+            .get(0);
+        anyhow::ensure!(
+            current <= latest_version(),
+            "The database schema is at version {current}, which is newer than the latest version \
+             {} known by this verifier.",
+            latest_version()
+        );
+        if migration.version <= current {
+            continue;
+        }
+        tracing::info!(
+            "Applying migration {} ({}).",
+            migration.version,
+            migration.description
+        );
+        tx.batch_execute(migration.sql)
+            .await
+            .with_context(|| format!("Migration {} failed.", migration.version))?;
+        tx.execute(
+            &format!("INSERT INTO {SCHEMA_VERSION_TABLE} (version, description) VALUES ($1, $2)"),
+            &[&migration.version, &migration.description],
+        )
+        .await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,296 @@
+//! Configuration of the social media platforms supported by the verifier.
+use crate::db::DbAccount;
+use anyhow::Context;
+use concordium_rust_sdk::{cis4::Cis4Contract, types::ContractAddress};
+use reqwest::{header::HeaderMap, StatusCode, Url};
+use serde::Deserialize;
+use some_verifier_lib::Platform;
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+const DISCORD_API_ENDPOINT: &str = "https://discord.com/api/v10/";
+
+/// Backoff used when an API rate limits us without saying for how long.
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How to look up the current username of an account on a platform.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UsernameLookup {
+    /// Use the username that was revealed when the account was verified.
+    Stored,
+    /// Look up the username using the Discord API at the given base URL,
+    /// which defaults to the public Discord API. This requires a Discord bot
+    /// token to be configured.
+    #[serde(rename_all = "camelCase")]
+    Discord {
+        #[serde(default = "default_discord_api_url")]
+        api_url: Url,
+    },
+    /// Look up the username using the GitHub API at the given base URL, e.g.,
+    /// `https://api.github.com/`. Requests are authenticated if a GitHub API
+    /// token is configured, which raises the rate limit of the API.
+    #[serde(rename_all = "camelCase")]
+    Github { api_url: Url },
+}
+
+/// Configuration of a single platform.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformConfig {
+    /// The name of the platform. This is used in the API, the database, and
+    /// by the frontend to identify the platform.
+    pub name: Platform,
+    /// Address of the registry smart contract of the platform's issuer.
+    pub registry: ContractAddress,
+    /// URL of the platform's issuer.
+    pub issuer_url: Url,
+    #[serde(default = "default_username_lookup")]
+    pub username_lookup: UsernameLookup,
+}
+
+fn default_username_lookup() -> UsernameLookup {
+    UsernameLookup::Stored
+}
+
+fn default_discord_api_url() -> Url {
+    Url::parse(DISCORD_API_ENDPOINT).expect("Discord API endpoint is a valid URL.")
+}
+
+impl UsernameLookup {
+    /// Whether the lookup calls an external API, in which case the results
+    /// are cached.
+    pub fn is_remote(&self) -> bool {
+        !matches!(self, UsernameLookup::Stored)
+    }
+}
+
+/// A configured platform together with a client for its registry contract.
+#[derive(Clone)]
+pub struct RegisteredPlatform {
+    pub config: PlatformConfig,
+    pub contract: Cis4Contract,
+    /// Rate limit state of the platform's username lookup API.
+    pub backoff: Arc<Backoff>,
+}
+
+/// Tracks when a rate limited API may be called again, based on the
+/// rate limit headers of its responses.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    until: Mutex<Option<Instant>>,
+}
+
+impl Backoff {
+    /// Fail if the API is currently rate limited.
+    fn check(&self) -> anyhow::Result<()> {
+        let until = self.until.lock().expect("Lock is not poisoned.");
+        if let Some(until) = *until {
+            let now = Instant::now();
+            anyhow::ensure!(
+                until <= now,
+                "Rate limited for another {}ms.",
+                (until - now).as_millis()
+            );
+        }
+        Ok(())
+    }
+
+    /// Hold back requests for `wait`, if given.
+    fn update(&self, wait: Option<Duration>) {
+        if let Some(wait) = wait {
+            tracing::warn!("Rate limited. Backing off for {}ms.", wait.as_millis());
+            let mut until = self.until.lock().expect("Lock is not poisoned.");
+            let new_until = Instant::now() + wait;
+            *until = Some(until.map_or(new_until, |u| u.max(new_until)));
+        }
+    }
+}
+
+/// Parse a numeric header.
+fn header_number(headers: &HeaderMap, name: &str) -> Option<f64> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+/// Parse a header with a number of seconds.
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    header_number(headers, name).and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+impl UsernameLookup {
+    /// How long to hold back requests after a response with the given status
+    /// and headers at time `now`. This is [`None`] unless the response says
+    /// that no more requests may be made until the rate limit resets.
+    fn rate_limit_wait(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        let exhausted = header_number(headers, "x-ratelimit-remaining") == Some(0.0);
+        match self {
+            UsernameLookup::Stored => None,
+            // Discord responds with 429, and gives the number of seconds until
+            // the limit resets.
+            UsernameLookup::Discord { .. } => {
+                if status == StatusCode::TOO_MANY_REQUESTS {
+                    Some(
+                        header_secs(headers, "retry-after")
+                            .or_else(|| header_secs(headers, "x-ratelimit-reset-after"))
+                            .unwrap_or(DEFAULT_BACKOFF),
+                    )
+                } else if exhausted {
+                    header_secs(headers, "x-ratelimit-reset-after")
+                } else {
+                    None
+                }
+            }
+            // GitHub responds with 403 or 429. The primary rate limit gives
+            // the time it resets as a unix time in seconds, and secondary rate
+            // limits say how long to wait with `retry-after`.
+            UsernameLookup::Github { .. } => {
+                let limited = matches!(
+                    status,
+                    StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+                );
+                if let Some(retry_after) = header_secs(headers, "retry-after").filter(|_| limited) {
+                    Some(retry_after)
+                } else if exhausted {
+                    let reset = header_secs(headers, "x-ratelimit-reset")
+                        .and_then(|reset| SystemTime::UNIX_EPOCH.checked_add(reset));
+                    Some(reset.map_or(DEFAULT_BACKOFF, |reset| {
+                        reset.duration_since(now).unwrap_or_default()
+                    }))
+                } else if status == StatusCode::TOO_MANY_REQUESTS {
+                    Some(DEFAULT_BACKOFF)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Read the platform configuration from a JSON file, and check that names and
+/// registries are unique.
+pub fn read_config(
+    path: &Path,
+    discord_bot_token: Option<&str>,
+) -> anyhow::Result<Vec<PlatformConfig>> {
+    let file = std::fs::File::open(path).context("Unable to open platforms file.")?;
+    let platforms: Vec<PlatformConfig> =
+        serde_json::from_reader(file).context("Unable to parse platforms file.")?;
+    validate(&platforms, discord_bot_token)?;
+    Ok(platforms)
+}
+
+/// Check that names and registries of the platforms are unique, and that
+/// Discord usernames can be looked up if needed.
+pub fn validate(
+    platforms: &[PlatformConfig],
+    discord_bot_token: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    let mut registries = HashSet::new();
+    for platform in platforms {
+        anyhow::ensure!(
+            names.insert(platform.name.clone()),
+            "Platform {} is configured more than once.",
+            platform.name.name()
+        );
+        anyhow::ensure!(
+            registries.insert(platform.registry),
+            "Registry {} is used by more than one platform.",
+            platform.registry
+        );
+        if matches!(platform.username_lookup, UsernameLookup::Discord { .. }) {
+            anyhow::ensure!(
+                discord_bot_token.is_some(),
+                "Platform {} looks up usernames on Discord, but no Discord bot token is \
+                 configured.",
+                platform.name.name()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct GithubUser {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    username: String,
+    discriminator: String,
+}
+
+impl UsernameLookup {
+    /// Look up the current username of the account. Requests are not made
+    /// while the API is rate limited according to `backoff`.
+    pub async fn lookup(
+        &self,
+        http_client: &reqwest::Client,
+        discord_bot_token: Option<&str>,
+        github_api_token: Option<&str>,
+        backoff: &Backoff,
+        account: &DbAccount,
+    ) -> anyhow::Result<String> {
+        match self {
+            UsernameLookup::Stored => Ok(account.username.clone()),
+            UsernameLookup::Discord { api_url } => {
+                let token = discord_bot_token.context("No Discord bot token configured.")?;
+                backoff.check()?;
+                let response = http_client
+                    .get(api_url.join(&format!("users/{}", account.id))?)
+                    .header("Authorization", format!("Bot {token}"))
+                    .send()
+                    .await?;
+                backoff.update(self.rate_limit_wait(
+                    response.status(),
+                    response.headers(),
+                    SystemTime::now(),
+                ));
+                let user = response.error_for_status()?.json::<DiscordUser>().await?;
+
+                // Discord has two types of usernames, with discriminator (e.g. abcd#1234),
+                // and without (e.g. abcdef). In the latter case, the discriminator is "0"
+                let username = if user.discriminator == "0" {
+                    user.username
+                } else {
+                    format!("{}#{}", user.username, user.discriminator)
+                };
+                Ok(username)
+            }
+            UsernameLookup::Github { api_url } => {
+                backoff.check()?;
+                let url = api_url.join(&format!("user/{}", account.id))?;
+                let mut request = http_client
+                    .get(url)
+                    .header("Accept", "application/vnd.github+json");
+                if let Some(token) = github_api_token {
+                    request = request.bearer_auth(token);
+                }
+                let response = request.send().await?;
+                backoff.update(self.rate_limit_wait(
+                    response.status(),
+                    response.headers(),
+                    SystemTime::now(),
+                ));
+                let user = response.error_for_status()?.json::<GithubUser>().await?;
+                Ok(user.login)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,208 @@
+//! Tests of the interpretation of the rate limit headers of the platform APIs.
+use super::{UsernameLookup, DEFAULT_BACKOFF};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode, Url,
+};
+use std::time::{Duration, SystemTime};
+
+fn discord() -> UsernameLookup {
+    UsernameLookup::Discord {
+        api_url: Url::parse("https://discord.com/api/v10/").unwrap(),
+    }
+}
+
+fn github() -> UsernameLookup {
+    UsernameLookup::Github {
+        api_url: Url::parse("https://api.github.com/").unwrap(),
+    }
+}
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            (
+                HeaderName::from_static(*name),
+                HeaderValue::from_str(value).unwrap(),
+            )
+        })
+        .collect()
+}
+
+const NOW: u64 = 1_700_000_000;
+
+fn wait(
+    lookup: &UsernameLookup,
+    status: StatusCode,
+    pairs: &[(&'static str, &str)],
+) -> Option<Duration> {
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(NOW);
+    lookup.rate_limit_wait(status, &headers(pairs), now)
+}
+
+#[test]
+fn discord_rate_limits() {
+    let cases: Vec<(
+        &str,
+        StatusCode,
+        Vec<(&'static str, &str)>,
+        Option<Duration>,
+    )> = vec![
+        ("ok", StatusCode::OK, vec![], None),
+        (
+            "remaining requests",
+            StatusCode::OK,
+            vec![
+                ("x-ratelimit-remaining", "3"),
+                ("x-ratelimit-reset-after", "1.5"),
+            ],
+            None,
+        ),
+        (
+            "last request",
+            StatusCode::OK,
+            vec![
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset-after", "1.5"),
+            ],
+            Some(Duration::from_millis(1500)),
+        ),
+        (
+            "limited with retry-after",
+            StatusCode::TOO_MANY_REQUESTS,
+            vec![("retry-after", "2"), ("x-ratelimit-reset-after", "5")],
+            Some(Duration::from_secs(2)),
+        ),
+        (
+            "limited with reset-after",
+            StatusCode::TOO_MANY_REQUESTS,
+            vec![("x-ratelimit-reset-after", "5")],
+            Some(Duration::from_secs(5)),
+        ),
+        (
+            "limited without headers",
+            StatusCode::TOO_MANY_REQUESTS,
+            vec![],
+            Some(DEFAULT_BACKOFF),
+        ),
+        ("forbidden", StatusCode::FORBIDDEN, vec![], None),
+    ];
+    for (name, status, pairs, expected) in cases {
+        assert_eq!(wait(&discord(), status, &pairs), expected, "{name}");
+    }
+}
+
+#[test]
+fn github_rate_limits() {
+    let reset_in_60 = (NOW + 60).to_string();
+    let reset_in_past = (NOW - 60).to_string();
+    let cases: Vec<(
+        &str,
+        StatusCode,
+        Vec<(&'static str, &str)>,
+        Option<Duration>,
+    )> = vec![
+        ("ok", StatusCode::OK, vec![], None),
+        (
+            "remaining requests",
+            StatusCode::OK,
+            vec![
+                ("x-ratelimit-remaining", "59"),
+                ("x-ratelimit-reset", &reset_in_60),
+            ],
+            None,
+        ),
+        (
+            "last request",
+            StatusCode::OK,
+            vec![
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", &reset_in_60),
+            ],
+            Some(Duration::from_secs(60)),
+        ),
+        (
+            "primary limit",
+            StatusCode::FORBIDDEN,
+            vec![
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", &reset_in_60),
+            ],
+            Some(Duration::from_secs(60)),
+        ),
+        (
+            "primary limit with 429",
+            StatusCode::TOO_MANY_REQUESTS,
+            vec![
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", &reset_in_60),
+            ],
+            Some(Duration::from_secs(60)),
+        ),
+        (
+            "reset in the past",
+            StatusCode::FORBIDDEN,
+            vec![
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", &reset_in_past),
+            ],
+            Some(Duration::ZERO),
+        ),
+        (
+            "primary limit without reset",
+            StatusCode::FORBIDDEN,
+            vec![("x-ratelimit-remaining", "0")],
+            Some(DEFAULT_BACKOFF),
+        ),
+        (
+            "secondary limit",
+            StatusCode::FORBIDDEN,
+            vec![("retry-after", "30"), ("x-ratelimit-remaining", "10")],
+            Some(Duration::from_secs(30)),
+        ),
+        (
+            "retry-after on success",
+            StatusCode::OK,
+            vec![("retry-after", "30")],
+            None,
+        ),
+        (
+            "forbidden without rate limit headers",
+            StatusCode::FORBIDDEN,
+            vec![("x-ratelimit-remaining", "10")],
+            None,
+        ),
+        (
+            "limited without headers",
+            StatusCode::TOO_MANY_REQUESTS,
+            vec![],
+            Some(DEFAULT_BACKOFF),
+        ),
+        (
+            "invalid reset",
+            StatusCode::FORBIDDEN,
+            vec![
+                ("x-ratelimit-remaining", "0"),
+                ("x-ratelimit-reset", "soon"),
+            ],
+            Some(DEFAULT_BACKOFF),
+        ),
+    ];
+    for (name, status, pairs, expected) in cases {
+        assert_eq!(wait(&github(), status, &pairs), expected, "{name}");
+    }
+}
+
+#[test]
+fn stored_is_never_limited() {
+    let lookup = UsernameLookup::Stored;
+    assert_eq!(
+        wait(
+            &lookup,
+            StatusCode::TOO_MANY_REQUESTS,
+            &[("retry-after", "5")]
+        ),
+        None
+    );
+}
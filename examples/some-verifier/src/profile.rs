@@ -0,0 +1,201 @@
+//! Public profile pages and embeddable badges of verified users, e.g.,
+//! `/profile/discord/1234` and `/badge/discord/1234.svg`. They show which of
+//! the linked accounts have active credentials, and the proven claims. The
+//! full name is only shown if the user opted in when verifying.
+use crate::{integrity, resolve_verification, AppState};
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{Html, IntoResponse, Response},
+};
+use concordium_rust_sdk::contract_client::CredentialStatus;
+use handlebars::Handlebars;
+use reqwest::StatusCode;
+use serde::Serialize;
+use some_verifier_lib::{Platform, Verification};
+
+const PROFILE_TEMPLATE: &str = "profile";
+const BADGE_TEMPLATE: &str = "badge";
+
+/// Profiles and badges are public, so they may be cached by proxies for a
+/// short while.
+const CACHE_CONTROL: &str = "public, max-age=300";
+
+const BADGE_LABEL: &str = "Concordia";
+/// Approximate width in pixels of a character in the badge font. Badges are
+/// rendered without access to the font, so text widths are estimated.
+const BADGE_CHAR_WIDTH: usize = 7;
+/// Horizontal padding in pixels around each text of the badge.
+const BADGE_PADDING: usize = 10;
+
+/// Load the templates of profiles and badges. Values inserted into them are
+/// escaped.
+pub fn templates() -> anyhow::Result<Handlebars<'static>> {
+    let mut templates = Handlebars::new();
+    templates.register_template_string(
+        PROFILE_TEMPLATE,
+        include_str!("../resources/templates/profile.html.hbs"),
+    )?;
+    templates.register_template_string(
+        BADGE_TEMPLATE,
+        include_str!("../resources/templates/badge.svg.hbs"),
+    )?;
+    Ok(templates)
+}
+
+#[derive(Serialize)]
+struct ProfileAccount {
+    platform: String,
+    username: String,
+    active: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Profile {
+    accounts: Vec<ProfileAccount>,
+    full_name: Option<String>,
+    claims: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Badge {
+    label: &'static str,
+    message: String,
+    color: &'static str,
+    width: usize,
+    label_width: usize,
+    message_width: usize,
+    label_x: usize,
+    message_x: usize,
+}
+
+impl Badge {
+    fn new(message: String, color: &'static str) -> Self {
+        let text_width = |text: &str| text.chars().count() * BADGE_CHAR_WIDTH + 2 * BADGE_PADDING;
+        let label_width = text_width(BADGE_LABEL);
+        let message_width = text_width(&message);
+        Self {
+            label: BADGE_LABEL,
+            message,
+            color,
+            width: label_width + message_width,
+            label_width,
+            message_width,
+            label_x: label_width / 2,
+            message_x: label_width + message_width / 2,
+        }
+    }
+}
+
+fn is_active(status: &CredentialStatus) -> bool {
+    matches!(status, CredentialStatus::Active)
+}
+
+/// Look up the verification of the account. Verifications that fail
+/// re-verification are treated as missing.
+async fn load(
+    state: &AppState,
+    platform: &Platform,
+    id: &str,
+) -> Result<Option<Verification>, StatusCode> {
+    let id = state.stored_id(platform, id);
+    match state.database.get_verification(&id, platform).await {
+        Ok(Some(verification)) => {
+            match integrity::may_serve(state, &verification).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(None),
+                Err(err) => {
+                    tracing::error!("Unable to re-verify verification: {err}");
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            }
+            Ok(Some(resolve_verification(state, verification).await))
+        }
+        Ok(None) => Ok(None),
+        Err(err) => {
+            tracing::error!("Database error when looking up verification: {err}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn render(state: &AppState, template: &str, data: &impl Serialize) -> Result<String, StatusCode> {
+    state.templates.render(template, data).map_err(|e| {
+        tracing::error!("Unable to render {template}: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Respond with the profile page of the account, or `404 Not Found` if it is
+/// not verified.
+#[tracing::instrument(level = "info", skip(state))]
+pub async fn profile(
+    State(state): State<AppState>,
+    Path((platform, id)): Path<(Platform, String)>,
+) -> Result<Response, StatusCode> {
+    let Some(verification) = load(&state, &platform, &id).await? else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let profile = Profile {
+        accounts: verification
+            .accounts
+            .into_iter()
+            .map(|a| ProfileAccount {
+                platform: a.platform.to_string(),
+                username: a.username,
+                active: is_active(&a.cred_status),
+            })
+            .collect(),
+        full_name: verification.full_name.map(|n| n.to_string()),
+        claims: verification
+            .claims
+            .into_iter()
+            .map(|c| c.description)
+            .collect(),
+    };
+    let html = render(&state, PROFILE_TEMPLATE, &profile)?;
+    Ok(([(header::CACHE_CONTROL, CACHE_CONTROL)], Html(html)).into_response())
+}
+
+/// Respond with an SVG badge listing the platforms of the account's
+/// verification with active credentials. `file` is the user id followed by
+/// `.svg`. Accounts that are not verified get a "not verified" badge, so that
+/// embedded badges do not break.
+#[tracing::instrument(level = "info", skip(state))]
+pub async fn badge(
+    State(state): State<AppState>,
+    Path((platform, file)): Path<(Platform, String)>,
+) -> Result<Response, StatusCode> {
+    let Some(id) = file.strip_suffix(".svg") else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let active: Vec<String> = load(&state, &platform, id)
+        .await?
+        .map(|verification| {
+            verification
+                .accounts
+                .into_iter()
+                .filter(|a| is_active(&a.cred_status))
+                .map(|a| a.platform.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    // A verification links at least two accounts, so a single active account
+    // is not verified by anything.
+    let badge = if active.len() >= 2 {
+        Badge::new(active.join(" · "), "#4c1")
+    } else {
+        Badge::new("not verified".into(), "#9f9f9f")
+    };
+    let svg = render(&state, BADGE_TEMPLATE, &badge)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, CACHE_CONTROL),
+        ],
+        svg,
+    )
+        .into_response())
+}
@@ -0,0 +1,75 @@
+//! Rate limiting of unauthenticated endpoints per client IP with a sliding
+//! time window. Requests are only counted in memory, so the limits are per
+//! replica and reset on restarts.
+use axum::http::{HeaderMap, HeaderName};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug)]
+pub struct IpRateLimiter {
+    window: Duration,
+    max_per_ip: u32,
+    /// A header set by a trusted reverse proxy with the address of the
+    /// client, see [`client_ip`](some_verifier_lib::client_ip::client_ip).
+    client_ip_header: Option<HeaderName>,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    requests: HashMap<IpAddr, VecDeque<Instant>>,
+    last_pruned: Instant,
+}
+
+impl IpRateLimiter {
+    pub fn new(window: Duration, max_per_ip: u32, client_ip_header: Option<HeaderName>) -> Self {
+        Self {
+            window,
+            max_per_ip,
+            client_ip_header,
+            inner: Mutex::new(Inner {
+                requests: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// The address of the client that sent a request to `peer`.
+    pub fn client_ip(&self, peer: SocketAddr, headers: &HeaderMap) -> IpAddr {
+        some_verifier_lib::client_ip::client_ip(peer, headers, self.client_ip_header.as_ref())
+    }
+
+    /// Count a request from the client at `now`. Returns `false`, and does not
+    /// count the request, if the client has already made the maximum number
+    /// of requests within the window.
+    pub fn check(&self, client: IpAddr, now: Instant) -> bool {
+        let mut inner = self.inner.lock().expect("Lock is not poisoned.");
+        let since = now.checked_sub(self.window);
+        let expired = |at: &Instant| since.is_some_and(|since| *at <= since);
+        // Remove the clients without recent requests once per window, so that
+        // the map does not grow with every client ever seen.
+        if now.duration_since(inner.last_pruned) >= self.window {
+            inner.requests.retain(|_, times| {
+                times.retain(|at| !expired(at));
+                !times.is_empty()
+            });
+            inner.last_pruned = now;
+        }
+        let times = inner.requests.entry(client).or_default();
+        while times.front().is_some_and(expired) {
+            times.pop_front();
+        }
+        if times.len() >= self.max_per_ip as usize {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+use super::IpRateLimiter;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn limits_each_client_within_window() {
+    let limiter = IpRateLimiter::new(WINDOW, 2, None);
+    let start = Instant::now();
+    let (a, b) = (ip("10.0.0.1"), ip("10.0.0.2"));
+    assert!(limiter.check(a, start));
+    assert!(limiter.check(a, start + Duration::from_secs(10)));
+    assert!(!limiter.check(a, start + Duration::from_secs(20)));
+    // Other clients have their own limit.
+    assert!(limiter.check(b, start + Duration::from_secs(20)));
+    // Rejected requests are not counted, so the first request expiring is
+    // enough to allow another one.
+    assert!(limiter.check(a, start + WINDOW + Duration::from_secs(1)));
+    assert!(!limiter.check(a, start + WINDOW + Duration::from_secs(2)));
+    assert!(limiter.check(a, start + WINDOW + Duration::from_secs(11)));
+}
+
+#[test]
+fn prunes_idle_clients() {
+    let limiter = IpRateLimiter::new(WINDOW, 1, None);
+    let start = Instant::now();
+    for i in 0..10u8 {
+        assert!(limiter.check(ip(&format!("10.0.0.{i}")), start));
+    }
+    assert!(limiter.check(ip("10.0.1.1"), start + 2 * WINDOW));
+    let inner = limiter.inner.lock().unwrap();
+    assert_eq!(inner.requests.len(), 1);
+}
+
+#[test]
+fn client_ip_from_header() {
+    let peer: SocketAddr = "192.168.0.1:4000".parse().unwrap();
+    let header = HeaderName::from_static("x-forwarded-for");
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header.clone(),
+        HeaderValue::from_static("1.2.3.4, 10.0.0.1"),
+    );
+
+    let direct = IpRateLimiter::new(WINDOW, 1, None);
+    assert_eq!(direct.client_ip(peer, &headers), peer.ip());
+
+    let proxied = IpRateLimiter::new(WINDOW, 1, Some(header.clone()));
+    assert_eq!(proxied.client_ip(peer, &headers), ip("10.0.0.1"));
+    assert_eq!(proxied.client_ip(peer, &HeaderMap::new()), peer.ip());
+    headers.insert(header, HeaderValue::from_static("not an address"));
+    assert_eq!(proxied.client_ip(peer, &headers), peer.ip());
+}
@@ -0,0 +1,216 @@
+//! Validation of the statements proven by the credentials of a presentation.
+//! Revealed attributes are identified by their tags, so the order of the
+//! statements does not matter, and every statement must be one that the
+//! verifier expects: a social media credential reveals exactly the attributes
+//! in [`PLATFORM_ATTRIBUTES`], and an identity credential reveals the
+//! attributes in [`NAME_ATTRIBUTES`] or proves a configured claim.
+use crate::claims::ClaimConfig;
+use chrono::NaiveDate;
+use concordium_rust_sdk::{
+    id::{
+        constants::{ArCurve, AttributeKind},
+        id_proof_types::{
+            AtomicProof, AtomicStatement, AttributeInRangeStatement, AttributeInSetStatement,
+            AttributeNotInSetStatement, RevealAttributeStatement,
+        },
+        types::AttributeTag,
+    },
+    smart_contracts::common::attributes,
+    web3id::Web3IdAttribute,
+};
+use some_verifier_lib::{FullName, Platform};
+
+#[cfg(test)]
+mod tests;
+
+/// The attributes of social media credentials, by their names in the
+/// credential schemas, that must be revealed: the user id and the username.
+pub const PLATFORM_ATTRIBUTES: [&str; 2] = ["userId", "username"];
+
+/// The attributes of identity credentials that make up the full name: the
+/// first and last name. Either both or none of them must be revealed.
+pub const NAME_ATTRIBUTES: [AttributeTag; 2] = [
+    AttributeTag(attributes::FIRST_NAME.0),
+    AttributeTag(attributes::LAST_NAME.0),
+];
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum StatementError {
+    #[error("The credential is not for the network of the verifier.")]
+    WrongNetwork,
+    #[error("Expected a social media credential.")]
+    NotPlatformCredential,
+    #[error("More than one credential for {0}.")]
+    DuplicatePlatform(Platform),
+    #[error("More than one identity credential.")]
+    DuplicateIdentity,
+    #[error("The identity credential has no statements.")]
+    EmptyIdentity,
+    #[error("Unexpected statement about {0}.")]
+    UnexpectedStatement(String),
+    #[error("The attribute {0} is revealed more than once.")]
+    DuplicateAttribute(String),
+    #[error("The attribute {0} is not revealed.")]
+    MissingAttribute(String),
+    #[error("The proof of the statement about {0} does not reveal it.")]
+    NotRevealed(String),
+    #[error("The revealed value of {0} is not a string.")]
+    NotAString(String),
+    #[error("Only one of the first and last name is revealed.")]
+    IncompleteName,
+}
+
+/// A statement together with the attribute revealed by its proof, if any.
+pub type Revealed<'a, Tag> = (
+    &'a AtomicStatement<ArCurve, Tag, Web3IdAttribute>,
+    Option<&'a Web3IdAttribute>,
+);
+
+/// Pair the statements of a credential with the attributes revealed by their
+/// proofs.
+pub fn revealed<Tag>(
+    proofs: &[(
+        AtomicStatement<ArCurve, Tag, Web3IdAttribute>,
+        AtomicProof<ArCurve, Web3IdAttribute>,
+    )],
+) -> Vec<Revealed<'_, Tag>> {
+    proofs
+        .iter()
+        .map(|(statement, proof)| {
+            let attribute = match proof {
+                AtomicProof::RevealAttribute { attribute, .. } => Some(attribute),
+                _ => None,
+            };
+            (statement, attribute)
+        })
+        .collect()
+}
+
+/// The attributes revealed from a social media credential.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PlatformAttributes {
+    pub id: String,
+    pub username: String,
+}
+
+/// Check that the statements of a social media credential reveal exactly the
+/// [`PLATFORM_ATTRIBUTES`], and return them.
+pub fn platform_attributes(
+    statements: &[Revealed<'_, String>],
+) -> Result<PlatformAttributes, StatementError> {
+    let tags = PLATFORM_ATTRIBUTES.map(String::from);
+    let values = collect_revealed(statements, &tags, |statement| {
+        Err(StatementError::UnexpectedStatement(tag_name(statement)))
+    })?;
+    let [id, username] = required(values, &tags)?;
+    Ok(PlatformAttributes { id, username })
+}
+
+/// What an identity credential proves.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IdentityStatements {
+    pub full_name: Option<FullName>,
+    /// The names of the proven claims.
+    pub claims: Vec<String>,
+}
+
+/// Check that each statement of an identity credential either reveals one of
+/// the [`NAME_ATTRIBUTES`] or proves one of the `claims` on `today`, and
+/// return the full name and the names of the proven claims.
+pub fn identity_statements(
+    statements: &[Revealed<'_, AttributeTag>],
+    claims: &[ClaimConfig],
+    today: NaiveDate,
+) -> Result<IdentityStatements, StatementError> {
+    if statements.is_empty() {
+        return Err(StatementError::EmptyIdentity);
+    }
+    let mut proven = Vec::new();
+    let values = collect_revealed(statements, &NAME_ATTRIBUTES, |statement| {
+        let Some(claim) = claims.iter().find(|c| c.is_proven_by(statement, today)) else {
+            return Err(StatementError::UnexpectedStatement(tag_name(statement)));
+        };
+        if !proven.contains(&claim.name) {
+            proven.push(claim.name.clone());
+        }
+        Ok(())
+    })?;
+    let full_name = match values {
+        [Some(first_name), Some(last_name)] => Some(FullName {
+            first_name,
+            last_name,
+        }),
+        [None, None] => None,
+        _ => return Err(StatementError::IncompleteName),
+    };
+    Ok(IdentityStatements {
+        full_name,
+        claims: proven,
+    })
+}
+
+/// Collect the values revealed for `tags`, in the order of `tags`. Each tag
+/// may be revealed at most once, and revealing any other attribute is an
+/// error. Statements that do not reveal an attribute are passed to `other`.
+fn collect_revealed<Tag: PartialEq + ToString, const N: usize>(
+    statements: &[Revealed<'_, Tag>],
+    tags: &[Tag; N],
+    mut other: impl FnMut(&AtomicStatement<ArCurve, Tag, Web3IdAttribute>) -> Result<(), StatementError>,
+) -> Result<[Option<String>; N], StatementError> {
+    let mut values = std::array::from_fn(|_| None);
+    for (statement, attribute) in statements {
+        let AtomicStatement::RevealAttribute {
+            statement: RevealAttributeStatement { attribute_tag },
+        } = statement
+        else {
+            other(statement)?;
+            continue;
+        };
+        let Some(index) = tags.iter().position(|tag| tag == attribute_tag) else {
+            return Err(StatementError::UnexpectedStatement(
+                attribute_tag.to_string(),
+            ));
+        };
+        let value = match attribute {
+            Some(Web3IdAttribute::String(AttributeKind(value))) => value,
+            Some(_) => return Err(StatementError::NotAString(attribute_tag.to_string())),
+            None => return Err(StatementError::NotRevealed(attribute_tag.to_string())),
+        };
+        let slot: &mut Option<String> = &mut values[index];
+        if slot.replace(value.clone()).is_some() {
+            return Err(StatementError::DuplicateAttribute(
+                attribute_tag.to_string(),
+            ));
+        }
+    }
+    Ok(values)
+}
+
+/// Check that all `tags` were revealed.
+fn required<Tag: ToString, const N: usize>(
+    values: [Option<String>; N],
+    tags: &[Tag; N],
+) -> Result<[String; N], StatementError> {
+    if let Some(index) = values.iter().position(Option::is_none) {
+        return Err(StatementError::MissingAttribute(tags[index].to_string()));
+    }
+    Ok(values.map(|value| value.unwrap_or_default()))
+}
+
+/// The name of the attribute that the statement is about.
+fn tag_name<Tag: ToString>(statement: &AtomicStatement<ArCurve, Tag, Web3IdAttribute>) -> String {
+    match statement {
+        AtomicStatement::RevealAttribute {
+            statement: RevealAttributeStatement { attribute_tag },
+        }
+        | AtomicStatement::AttributeInRange {
+            statement: AttributeInRangeStatement { attribute_tag, .. },
+        }
+        | AtomicStatement::AttributeInSet {
+            statement: AttributeInSetStatement { attribute_tag, .. },
+        }
+        | AtomicStatement::AttributeNotInSet {
+            statement: AttributeNotInSetStatement { attribute_tag, .. },
+        } => attribute_tag.to_string(),
+    }
+}
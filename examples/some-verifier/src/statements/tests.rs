@@ -0,0 +1,173 @@
+//! Table-driven tests of the validation of malformed, reordered and extra
+//! statements.
+use super::{
+    identity_statements, platform_attributes, IdentityStatements, PlatformAttributes,
+    StatementError, NAME_ATTRIBUTES,
+};
+use chrono::NaiveDate;
+use concordium_rust_sdk::{
+    id::{
+        constants::{ArCurve, AttributeKind},
+        id_proof_types::{AtomicStatement, RevealAttributeStatement},
+        types::AttributeTag,
+    },
+    smart_contracts::common::attributes,
+    web3id::Web3IdAttribute,
+};
+use some_verifier_lib::FullName;
+
+type Statement<Tag> = (
+    AtomicStatement<ArCurve, Tag, Web3IdAttribute>,
+    Option<Web3IdAttribute>,
+);
+
+fn reveal<Tag>(attribute_tag: Tag, value: &str) -> Statement<Tag> {
+    (
+        AtomicStatement::RevealAttribute {
+            statement: RevealAttributeStatement { attribute_tag },
+        },
+        Some(Web3IdAttribute::String(AttributeKind(value.into()))),
+    )
+}
+
+fn platform(tag: &str, value: &str) -> Statement<String> {
+    reveal(tag.into(), value)
+}
+
+fn platform_ok(id: &str, username: &str) -> Result<PlatformAttributes, StatementError> {
+    Ok(PlatformAttributes {
+        id: id.into(),
+        username: username.into(),
+    })
+}
+
+fn unexpected(tag: impl ToString) -> StatementError {
+    StatementError::UnexpectedStatement(tag.to_string())
+}
+
+#[test]
+fn platform_statements() {
+    let numeric_id = (
+        AtomicStatement::RevealAttribute {
+            statement: RevealAttributeStatement {
+                attribute_tag: "userId".into(),
+            },
+        },
+        Some(Web3IdAttribute::Numeric(1234)),
+    );
+    let not_revealed = (numeric_id.0.clone(), None);
+    let cases: Vec<(&str, Vec<Statement<String>>, _)> = vec![
+        (
+            "in order",
+            vec![platform("userId", "1234"), platform("username", "alice")],
+            platform_ok("1234", "alice"),
+        ),
+        (
+            "reordered",
+            vec![platform("username", "alice"), platform("userId", "1234")],
+            platform_ok("1234", "alice"),
+        ),
+        (
+            "empty",
+            vec![],
+            Err(StatementError::MissingAttribute("userId".into())),
+        ),
+        (
+            "missing username",
+            vec![platform("userId", "1234")],
+            Err(StatementError::MissingAttribute("username".into())),
+        ),
+        (
+            "duplicate id",
+            vec![
+                platform("userId", "1234"),
+                platform("username", "alice"),
+                platform("userId", "5678"),
+            ],
+            Err(StatementError::DuplicateAttribute("userId".into())),
+        ),
+        (
+            "extra attribute",
+            vec![
+                platform("userId", "1234"),
+                platform("username", "alice"),
+                platform("email", "alice@example.com"),
+            ],
+            Err(unexpected("email")),
+        ),
+        (
+            "numeric id",
+            vec![numeric_id, platform("username", "alice")],
+            Err(StatementError::NotAString("userId".into())),
+        ),
+        (
+            "not revealed by proof",
+            vec![not_revealed, platform("username", "alice")],
+            Err(StatementError::NotRevealed("userId".into())),
+        ),
+    ];
+    for (name, statements, expected) in cases {
+        let statements: Vec<_> = statements.iter().map(|(s, a)| (s, a.as_ref())).collect();
+        assert_eq!(platform_attributes(&statements), expected, "{name}");
+    }
+}
+
+#[test]
+fn identity_statements_reveal_names() {
+    let [first, last] = NAME_ATTRIBUTES;
+    let dob = AttributeTag(attributes::DOB.0);
+    let full_name = |first_name: &str, last_name: &str| {
+        Ok(IdentityStatements {
+            full_name: Some(FullName {
+                first_name: first_name.into(),
+                last_name: last_name.into(),
+            }),
+            claims: Vec::new(),
+        })
+    };
+    let cases: Vec<(&str, Vec<Statement<AttributeTag>>, _)> = vec![
+        (
+            "in order",
+            vec![reveal(first, "John"), reveal(last, "Doe")],
+            full_name("John", "Doe"),
+        ),
+        (
+            "reordered",
+            vec![reveal(last, "Doe"), reveal(first, "John")],
+            full_name("John", "Doe"),
+        ),
+        ("empty", vec![], Err(StatementError::EmptyIdentity)),
+        (
+            "only first name",
+            vec![reveal(first, "John")],
+            Err(StatementError::IncompleteName),
+        ),
+        (
+            "duplicate last name",
+            vec![
+                reveal(first, "John"),
+                reveal(last, "Doe"),
+                reveal(last, "Roe"),
+            ],
+            Err(StatementError::DuplicateAttribute(last.to_string())),
+        ),
+        (
+            "extra attribute",
+            vec![
+                reveal(first, "John"),
+                reveal(last, "Doe"),
+                reveal(dob, "19700101"),
+            ],
+            Err(unexpected(dob)),
+        ),
+    ];
+    let today = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    for (name, statements, expected) in cases {
+        let statements: Vec<_> = statements.iter().map(|(s, a)| (s, a.as_ref())).collect();
+        assert_eq!(
+            identity_statements(&statements, &[], today),
+            expected,
+            "{name}"
+        );
+    }
+}
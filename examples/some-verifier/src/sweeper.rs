@@ -0,0 +1,117 @@
+//! Background tasks that clean up the database. One removes verifications
+//! whose credentials have been revoked or have expired on chain. Credential
+//! statuses are otherwise only checked when a verification is looked up, so
+//! without it stale rows would remain in the database forever. The other
+//! purges presentations after the retention period.
+use crate::{db::Storage, platforms::RegisteredPlatform, webhooks::Webhooks};
+use concordium_rust_sdk::{contract_client::CredentialStatus, v2::BlockIdentifier};
+use some_verifier_lib::{webhook::WebhookEvent, Platform};
+use std::sync::Arc;
+
+/// The number of accounts that are checked per database query.
+const BATCH_SIZE: i64 = 100;
+
+/// How often presentations older than the retention period are purged.
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Check the status of the credentials of all accounts every `interval`, and
+/// remove verifications with credentials that are revoked or expired.
+pub async fn sweep_revocations(
+    database: Arc<dyn Storage>,
+    platforms: Arc<[RegisteredPlatform]>,
+    webhooks: Webhooks,
+    interval: std::time::Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match sweep(&database, &platforms, &webhooks).await {
+            Ok(removed) => tracing::info!(
+                "Revocation sweep completed. Removed {removed} verifications with inactive \
+                 credentials."
+            ),
+            Err(e) => tracing::warn!("Revocation sweep failed: {e}"),
+        }
+    }
+}
+
+/// Go through all accounts once. Returns the number of removed verifications.
+async fn sweep(
+    database: &dyn Storage,
+    platforms: &[RegisteredPlatform],
+    webhooks: &Webhooks,
+) -> anyhow::Result<usize> {
+    let mut removed = 0;
+    let mut last: Option<(Platform, String)> = None;
+    loop {
+        let accounts = database
+            .list_accounts(last.as_ref().map(|(p, id)| (p, id.as_str())), BATCH_SIZE)
+            .await?;
+        let Some(last_account) = accounts.last() else {
+            return Ok(removed);
+        };
+        last = Some((last_account.platform.clone(), last_account.id.clone()));
+
+        for account in accounts {
+            // Accounts of platforms that are no longer configured are left alone,
+            // since their registry is unknown.
+            let Some(platform) = platforms.iter().find(|p| p.config.name == account.platform)
+            else {
+                continue;
+            };
+            let mut contract = platform.contract.clone();
+            let status = match contract
+                .credential_status(account.cred_id, BlockIdentifier::LastFinal)
+                .await
+            {
+                Ok(status) => status,
+                Err(e) => {
+                    tracing::warn!(
+                        "Unable to get credential status of {} account {}: {e}",
+                        account.platform,
+                        account.id
+                    );
+                    continue;
+                }
+            };
+            if matches!(
+                status,
+                CredentialStatus::Revoked | CredentialStatus::Expired
+            ) {
+                tracing::info!(
+                    "Removing verification of {} account {}, since its credential is {status:?}.",
+                    account.platform,
+                    account.id
+                );
+                if let Some(accounts) = database
+                    .remove_verification(&account.cred_id, &account.platform)
+                    .await?
+                {
+                    removed += 1;
+                    webhooks.send(WebhookEvent::CredentialInactive {
+                        platform: account.platform.clone(),
+                        accounts: accounts.clone(),
+                    });
+                    webhooks.send(WebhookEvent::VerificationRemoved { accounts });
+                }
+            }
+        }
+    }
+}
+
+/// Purge the presentations of verifications older than `retention` every
+/// hour.
+pub async fn purge_presentations(database: Arc<dyn Storage>, retention: std::time::Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match database.purge_presentations(retention).await {
+            Ok(purged) => {
+                tracing::info!("Purged {purged} presentations past the retention period.")
+            }
+            Err(e) => tracing::warn!("Purging presentations failed: {e}"),
+        }
+    }
+}
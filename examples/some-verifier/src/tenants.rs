@@ -0,0 +1,90 @@
+//! Tenants, i.e. communities that share a verifier but each have their own
+//! platforms, claims and verifications. The API and frontend of a tenant are
+//! served under `/tenants/{name}`, and its rows in the database are only seen
+//! by it. Everything served outside of `/tenants` belongs to the default
+//! tenant, which is configured with the command line options.
+use crate::{
+    claims::ClaimConfig, db::DEFAULT_TENANT, platforms::PlatformConfig, webhooks::WebhookConfig,
+};
+use anyhow::Context;
+use reqwest::Url;
+use serde::Deserialize;
+use std::{collections::HashSet, path::Path};
+
+/// Configuration of a single tenant. The bot names, invite links, webhook
+/// endpoints and admin token default to those of the command line options.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantConfig {
+    /// The name of the tenant, used in its URLs and stored with its rows.
+    pub name: String,
+    /// The platforms of the tenant, with the registries of the issuers it
+    /// trusts.
+    pub platforms: Vec<PlatformConfig>,
+    /// Whether verifications must reveal the full name of the user.
+    #[serde(default)]
+    pub require_name: bool,
+    /// Claims about the identity that are accepted in verifications.
+    #[serde(default)]
+    pub claims: Vec<ClaimConfig>,
+    /// Whether usernames revealed in verifications are not stored.
+    #[serde(default)]
+    pub omit_usernames: bool,
+    /// The endpoints that receive the webhooks of the tenant, instead of
+    /// those configured with `--webhooks`.
+    pub webhooks: Option<Vec<WebhookConfig>>,
+    /// The token for the admin endpoints of the tenant, instead of the one
+    /// configured with `--admin-token`.
+    pub admin_token: Option<String>,
+    pub telegram_bot_name: Option<String>,
+    pub discord_client_id: Option<String>,
+    pub telegram_invite_link: Option<Url>,
+    pub discord_invite_link: Option<Url>,
+}
+
+/// Read the tenants from a JSON file, and check their names and
+/// configurations.
+pub fn read_config(
+    path: &Path,
+    discord_bot_token: Option<&str>,
+) -> anyhow::Result<Vec<TenantConfig>> {
+    let file = std::fs::File::open(path).context("Unable to open tenants file.")?;
+    let tenants: Vec<TenantConfig> =
+        serde_json::from_reader(file).context("Unable to parse tenants file.")?;
+    let mut names = HashSet::new();
+    for tenant in &tenants {
+        anyhow::ensure!(
+            !tenant.name.is_empty()
+                && tenant
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+            "Tenant name {:?} must be non-empty and only contain lowercase letters, digits and \
+             dashes.",
+            tenant.name
+        );
+        anyhow::ensure!(
+            tenant.name != DEFAULT_TENANT,
+            "The tenant name {DEFAULT_TENANT} is reserved."
+        );
+        anyhow::ensure!(
+            names.insert(tenant.name.as_str()),
+            "Tenant {} is configured more than once.",
+            tenant.name
+        );
+        crate::platforms::validate(&tenant.platforms, discord_bot_token)
+            .with_context(|| format!("Invalid platforms of tenant {}.", tenant.name))?;
+        crate::claims::validate(&tenant.claims)
+            .with_context(|| format!("Invalid claims of tenant {}.", tenant.name))?;
+        if let Some(webhooks) = &tenant.webhooks {
+            crate::webhooks::validate(webhooks)
+                .with_context(|| format!("Invalid webhooks of tenant {}.", tenant.name))?;
+        }
+        anyhow::ensure!(
+            tenant.admin_token.as_ref().map_or(true, |t| !t.is_empty()),
+            "The admin token of tenant {} is empty.",
+            tenant.name
+        );
+    }
+    Ok(tenants)
+}
@@ -0,0 +1,188 @@
+//! Delivery of webhooks to the endpoints configured with `--webhooks`, e.g.,
+//! the bots, so that they learn about changes to verifications without
+//! polling. Each endpoint has its own queue, so that webhooks are delivered to
+//! an endpoint in the order the changes happened, and a failing endpoint does
+//! not hold up the others. Queues are kept in memory, so webhooks that are not
+//! yet delivered are lost when the verifier stops.
+use crate::db::DEFAULT_TENANT;
+use anyhow::Context;
+use chrono::Utc;
+use rand::Rng;
+use reqwest::Url;
+use serde::Deserialize;
+use some_verifier_lib::webhook::{self, Webhook, WebhookEvent};
+use std::{path::Path, sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+/// The number of webhooks that can be queued for an endpoint before new ones
+/// are dropped.
+const QUEUE_SIZE: usize = 1000;
+/// The number of attempts to deliver a webhook before giving up.
+const MAX_ATTEMPTS: u32 = 8;
+/// The delay before the first retry. It is doubled after each attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Timeout of a single delivery attempt.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An endpoint that receives webhooks.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfig {
+    /// The URL that webhooks are posted to.
+    pub url: Url,
+    /// The secret used to sign the webhooks, shared with the receiver.
+    pub secret: String,
+}
+
+/// Read the webhook endpoints from a JSON file.
+pub fn read_config(path: &Path) -> anyhow::Result<Vec<WebhookConfig>> {
+    let file = std::fs::File::open(path).context("Unable to open webhooks file.")?;
+    let webhooks: Vec<WebhookConfig> =
+        serde_json::from_reader(file).context("Unable to parse webhooks file.")?;
+    validate(&webhooks)?;
+    Ok(webhooks)
+}
+
+/// Check that the secrets of the endpoints are not empty.
+pub fn validate(webhooks: &[WebhookConfig]) -> anyhow::Result<()> {
+    for webhook in webhooks {
+        anyhow::ensure!(
+            !webhook.secret.is_empty(),
+            "The webhook secret for {} is empty.",
+            webhook.url
+        );
+    }
+    Ok(())
+}
+
+/// Handle for sending webhooks about the verifications of a tenant. Without
+/// endpoints, sending does nothing.
+#[derive(Clone)]
+pub struct Webhooks {
+    queues: Arc<[(Url, mpsc::Sender<Arc<[u8]>>)]>,
+    tenant: Arc<str>,
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        Self {
+            queues: Arc::new([]),
+            tenant: DEFAULT_TENANT.into(),
+        }
+    }
+}
+
+impl Webhooks {
+    /// Start a delivery task for each endpoint.
+    pub fn start(http_client: reqwest::Client, configs: Vec<WebhookConfig>) -> Self {
+        let queues = configs
+            .into_iter()
+            .map(|config| {
+                let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+                let url = config.url.clone();
+                tokio::spawn(deliver(http_client.clone(), config, receiver));
+                (url, sender)
+            })
+            .collect();
+        Self {
+            queues,
+            tenant: DEFAULT_TENANT.into(),
+        }
+    }
+
+    /// A handle for changes to the verifications of the tenant. If the
+    /// tenant has its own endpoints, delivery tasks are started for them and
+    /// its webhooks are only sent there. Otherwise, they are sent to the same
+    /// endpoints as this handle.
+    pub fn for_tenant(
+        &self,
+        tenant: &str,
+        http_client: &reqwest::Client,
+        configs: Option<Vec<WebhookConfig>>,
+    ) -> Self {
+        let queues = match configs {
+            Some(configs) => Self::start(http_client.clone(), configs).queues,
+            None => self.queues.clone(),
+        };
+        Self {
+            queues,
+            tenant: tenant.into(),
+        }
+    }
+
+    /// Queue a webhook for the event to all endpoints.
+    pub fn send(&self, event: WebhookEvent) {
+        if self.queues.is_empty() {
+            return;
+        }
+        let webhook = Webhook {
+            id: hex::encode(rand::thread_rng().gen::<[u8; 16]>()),
+            tenant: self.tenant.to_string(),
+            event,
+        };
+        let body: Arc<[u8]> = serde_json::to_vec(&webhook)
+            .expect("Webhooks can be serialized")
+            .into();
+        for (url, queue) in self.queues.iter() {
+            if queue.try_send(body.clone()).is_err() {
+                tracing::warn!(
+                    "Dropping webhook {} to {url}, since its queue is full.",
+                    webhook.id
+                );
+            }
+        }
+    }
+}
+
+/// Deliver the webhooks queued for an endpoint, one at a time, retrying with
+/// exponential backoff.
+async fn deliver(
+    http_client: reqwest::Client,
+    config: WebhookConfig,
+    mut queue: mpsc::Receiver<Arc<[u8]>>,
+) {
+    while let Some(body) = queue.recv().await {
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match post(&http_client, &config, &body).await {
+                Ok(()) => break,
+                Err(e) if attempt == MAX_ATTEMPTS => {
+                    tracing::warn!(
+                        "Giving up delivering webhook to {} after {attempt} attempts: {e}",
+                        config.url
+                    );
+                }
+                Err(e) => {
+                    tracing::debug!(
+                        "Unable to deliver webhook to {}, retrying in {backoff:?}: {e}",
+                        config.url
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+    }
+}
+
+/// Make a single delivery attempt. The webhook is signed at the time of the
+/// attempt, so that receivers can reject old timestamps.
+async fn post(
+    http_client: &reqwest::Client,
+    config: &WebhookConfig,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    let timestamp = Utc::now().timestamp();
+    let signature = webhook::sign(config.secret.as_bytes(), timestamp, body);
+    http_client
+        .post(config.url.clone())
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(webhook::TIMESTAMP_HEADER, timestamp)
+        .header(webhook::SIGNATURE_HEADER, signature)
+        .timeout(DELIVERY_TIMEOUT)
+        .body(body.to_vec())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
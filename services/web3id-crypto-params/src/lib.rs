@@ -0,0 +1,65 @@
+//! Tracking of the chain's cryptographic parameters, which are needed to
+//! produce commitments and verify proofs. They can change with protocol
+//! updates, so they are refreshed periodically. This is shared by the issuers
+//! and verifiers.
+use concordium_rust_sdk::{
+    common,
+    types::CryptographicParameters,
+    v2::{self, BlockIdentifier, QueryError},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Cryptographic parameters together with their hash.
+#[derive(Debug)]
+pub struct CryptoParams {
+    pub params: CryptographicParameters,
+    /// Hex encoded SHA-256 hash of the serialized parameters.
+    pub hash: String,
+}
+
+impl CryptoParams {
+    /// Get the parameters in the last finalized block.
+    pub async fn query(client: &mut v2::Client) -> Result<Self, QueryError> {
+        let params = client
+            .get_cryptographic_parameters(BlockIdentifier::LastFinal)
+            .await?
+            .response;
+        let hash = hex::encode(Sha256::digest(common::to_bytes(&params)));
+        Ok(Self { params, hash })
+    }
+}
+
+/// The receiving end of the channel on which updated cryptographic
+/// parameters are sent by [`refresh_params`].
+pub type ParamsReceiver = tokio::sync::watch::Receiver<Arc<CryptoParams>>;
+
+/// Query the cryptographic parameters every `interval` and send them on the
+/// channel if their hash differs from the current ones. This returns once
+/// all receivers have been dropped.
+pub async fn refresh_params(
+    mut client: v2::Client,
+    interval: std::time::Duration,
+    sender: tokio::sync::watch::Sender<Arc<CryptoParams>>,
+) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // Skip the first tick, which completes immediately.
+    interval.tick().await;
+    while !sender.is_closed() {
+        interval.tick().await;
+        match CryptoParams::query(&mut client).await {
+            Ok(params) => {
+                let changed = params.hash != sender.borrow().hash;
+                if changed {
+                    tracing::info!(
+                        "Cryptographic parameters changed. New hash is {}.",
+                        params.hash
+                    );
+                    sender.send_replace(Arc::new(params));
+                }
+            }
+            Err(e) => tracing::warn!("Unable to query cryptographic parameters: {e}"),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! The issuance engine shared by the Web3ID issuers. An [`IssuerWorker`]
+//! registers credentials in the registry contract one at a time, so that the
+//! nonces of the issuer account are used in order, and returns the credentials
+//! with their signed commitments to the request handlers. What is issued is
+//! configured by an [`IssuancePolicy`], which validates credentials and builds
+//! the attributes.
+mod services;
+mod worker;
+
+pub use services::{spawn_cancel, start_services};
+pub use web3id_crypto_params::{refresh_params, CryptoParams, ParamsReceiver};
+pub use worker::{
+    initial_nonce, IssuancePolicy, IssueChannelData, IssueError, IssueResponse, IssuerConfig,
+    IssuerHandle, IssuerWorker, MakeSecretsError,
+};
@@ -0,0 +1,163 @@
+//! Running the issuer worker together with the HTTP servers, and shutting
+//! all of them down when one stops or a signal is received.
+use crate::{IssuancePolicy, IssuerWorker};
+use anyhow::Context;
+use axum::Router;
+use axum_prometheus::metrics_exporter_prometheus::PrometheusHandle;
+use futures::{Future, FutureExt};
+use std::net::SocketAddr;
+
+/// Like `tokio::spawn` but the provided future is modified so that
+/// once it terminates it sends a message on the provided channel.
+/// This is sent regardless of how the future terminates, as long as it
+/// terminates normally (i.e., does not panic).
+pub fn spawn_cancel<T>(
+    died_sender: tokio::sync::broadcast::Sender<()>,
+    future: T,
+) -> tokio::task::JoinHandle<T::Output>
+where
+    T: Future + Send + 'static,
+    T::Output: Send + 'static,
+{
+    tokio::spawn(async move {
+        let res = future.await;
+        // We ignore errors here since this always happens at the end of a task.
+        // Since we keep one receiver alive until the end of the `main` function
+        // the error should not happen anyhow.
+        let _ = died_sender.send(());
+        res
+    })
+}
+
+/// Run the issuer worker, the server with the `router` and, if an address is
+/// given, a server with Prometheus metrics, until one of them stops or a
+/// shutdown signal is received.
+pub async fn start_services<P: IssuancePolicy>(
+    issuer_worker: IssuerWorker<P>,
+    metric_handle: PrometheusHandle,
+    prometheus_address: Option<SocketAddr>,
+    listen_address: SocketAddr,
+    router: Router,
+) -> anyhow::Result<()> {
+    // If a service crashes it will send a message on this channel, which will then
+    // cause all of the other services to shut down.
+    let (died_sender, died_receiver) = tokio::sync::broadcast::channel(10);
+    // We create additional receivers of the broadcast messages.
+    // We do this before any message is potentially sent to make sure all receivers
+    // will receive them.
+    let prometheus_receiver = died_sender.subscribe();
+    let server_receiver = died_sender.subscribe();
+
+    {
+        let died_sender = died_sender.clone();
+        // Start handling of shutdown signals now, before starting the server.
+        let shutdown_signal = set_shutdown()?;
+        tokio::spawn(async move {
+            shutdown_signal.await;
+            if died_sender.send(()).is_err() {
+                tracing::error!("Unable to notify shutdown.");
+            }
+        });
+    }
+
+    if let Some(prometheus_address) = prometheus_address {
+        let prometheus_api = axum::Router::new()
+            .route(
+                "/metrics",
+                axum::routing::get(|| async move { metric_handle.render() }),
+            )
+            .layer(tower_http::timeout::TimeoutLayer::new(
+                std::time::Duration::from_millis(1000),
+            ))
+            .layer(tower_http::limit::RequestBodyLimitLayer::new(0));
+        tracing::info!("Starting prometheus server at {prometheus_address}.");
+        spawn_cancel(died_sender.clone(), async move {
+            axum::Server::bind(&prometheus_address)
+                .serve(prometheus_api.into_make_service())
+                .with_graceful_shutdown(shutdown_trigger(prometheus_receiver))
+                .await
+                .context("Unable to start Prometheus server.")?;
+            Ok::<(), anyhow::Error>(())
+        });
+    }
+
+    let transaction_sender = spawn_cancel(died_sender.clone(), issuer_worker.tx_sender());
+
+    tracing::info!("Starting server on {}...", listen_address);
+    let server_handle = spawn_cancel(
+        died_sender.clone(),
+        axum::Server::bind(&listen_address)
+            .http1_header_read_timeout(std::time::Duration::from_secs(5))
+            // The peer address is available to handlers that rate limit clients.
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown_trigger(server_receiver)),
+    );
+
+    // Wait until something triggers shutdown. Either a signal handler or an error
+    // in the service startup or transaction sender.
+    shutdown_trigger(died_receiver).await;
+    tracing::info!("Received shutdown trigger.");
+
+    // Wait for the server to shut down itself. However this might not happen since
+    // open connections can make it wait until the client drops them.
+    // Thus we wait for 5s only, which should be sufficient to handle any
+    // outstanding requests. After that we forcefully kill it.
+    let res = tokio::time::timeout(std::time::Duration::from_secs(5), server_handle).await;
+
+    if res.is_err() {
+        tracing::error!(
+            "Unable to stop the server gracefully in required time. Terminating forcefully."
+        )
+    }
+    // Abort the sender explicitly. Since the server is now not responding even if
+    // there are any pending transactions there is no point in sending them/waiting
+    // for them to be sent.
+    // This would happen implicitly as well, so this is here just for documentation.
+    transaction_sender.abort();
+
+    Ok(())
+}
+
+async fn shutdown_trigger(mut receiver: tokio::sync::broadcast::Receiver<()>) {
+    if receiver.recv().await.is_err() {
+        tracing::error!("Shutdown channel unexpectedly closed.");
+    }
+}
+
+/// Construct a future for shutdown signals (for unix: SIGINT and SIGTERM) (for
+/// windows: ctrl c and ctrl break). The signal handler is set when the future
+/// is polled and until then the default signal handler.
+fn set_shutdown() -> anyhow::Result<impl Future<Output = ()>> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix as unix_signal;
+
+        let mut terminate_stream = unix_signal::signal(unix_signal::SignalKind::terminate())?;
+        let mut interrupt_stream = unix_signal::signal(unix_signal::SignalKind::interrupt())?;
+
+        Ok(async move {
+            futures::future::select(
+                Box::pin(terminate_stream.recv()),
+                Box::pin(interrupt_stream.recv()),
+            )
+            .map(|_| ())
+            .await
+        })
+    }
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows as windows_signal;
+
+        let mut ctrl_break_stream = windows_signal::ctrl_break()?;
+        let mut ctrl_c_stream = windows_signal::ctrl_c()?;
+
+        Ok(async move {
+            futures::future::select(
+                Box::pin(ctrl_break_stream.recv()),
+                Box::pin(ctrl_c_stream.recv()),
+            )
+            .map(|_| ())
+            .await
+        })
+    }
+}
@@ -0,0 +1,361 @@
+//! The worker that registers credentials and produces their commitments, and
+//! the hooks with which issuers configure it.
+use crate::ParamsReceiver;
+use axum::http::StatusCode;
+use chrono::TimeZone;
+use concordium_rust_sdk::{
+    cis4::{Cis4Contract, Cis4TransactionError, Cis4TransactionMetadata},
+    common::types::{KeyPair, TransactionTime},
+    contract_client::{CredentialInfo, CredentialType},
+    id::{constants::ArCurve, pedersen_commitment},
+    smart_contracts::common::{Amount, Timestamp},
+    types::{
+        hashes::TransactionHash, transactions::send::GivenEnergy, AccountAddress, Energy, Nonce,
+        WalletAccount,
+    },
+    v2::{self, RPCError},
+    web3id::{did::Network, SignedCommitments, Web3IdAttribute, Web3IdCredential},
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// The number of requests that can wait for the worker before handlers wait
+/// to enqueue theirs.
+const CHANNEL_CAPACITY: usize = 100;
+
+/// Hooks that configure what an [`IssuerWorker`] issues. They are called by
+/// the worker in the order the requests arrive. Rate limits are enforced by
+/// the request handlers before requests are sent to the worker, so that
+/// limited requests do not wait behind the registrations of others.
+pub trait IssuancePolicy: Send + 'static {
+    /// The data of a request that the hooks need, e.g., the account of the
+    /// user that the credential is issued for.
+    type Request: Send + 'static;
+
+    /// Check the credential before it is registered. By default all
+    /// credentials are accepted.
+    fn validate(
+        &self,
+        _credential: &CredentialInfo,
+        _request: &Self::Request,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// The attributes of the credential issued for the request.
+    fn attributes(&self, request: Self::Request) -> BTreeMap<String, Web3IdAttribute>;
+}
+
+/// What the worker needs to register and sign credentials.
+pub struct IssuerConfig {
+    pub crypto_params: ParamsReceiver,
+    /// The registry contract that credentials are registered in.
+    pub contract_client: Cis4Contract,
+    pub network: Network,
+    /// The account that sends the transactions.
+    pub issuer: WalletAccount,
+    /// The key that commitments are signed with.
+    pub issuer_key: Arc<KeyPair>,
+    /// The credential type of the registry, from its metadata.
+    pub credential_type: CredentialType,
+    pub credential_schema_url: String,
+    /// The energy allowed for executing a registration.
+    pub max_register_energy: Energy,
+}
+
+/// Data sent on a channel from the request handler task to the transaction
+/// sender task.
+#[derive(Debug)]
+pub struct IssueChannelData<R> {
+    pub credential: CredentialInfo,
+    pub request: R,
+    /// The channel where the response is sent. The type that is sent is
+    /// [`IssueResponse`], however that type is not [`Send`] so we serialize it
+    /// to a JSON value in the worker thread instead.
+    pub response_sender: tokio::sync::oneshot::Sender<Result<serde_json::Value, IssueError>>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueResponse {
+    pub tx_hash: TransactionHash,
+    pub credential: Web3IdCredential<ArCurve, Web3IdAttribute>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum IssueError {
+    #[error("Credential is not valid: {0}")]
+    InvalidCredential(anyhow::Error),
+    #[error("Error sending transaction: {0}")]
+    Chain(#[from] Cis4TransactionError),
+    #[error("Internal issue error: {0}")]
+    Internal(#[from] MakeSecretsError),
+    #[error("The transaction sender is not running.")]
+    WorkerStopped,
+}
+
+impl IssueError {
+    /// The status code of the response to a request that failed with the
+    /// error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            IssueError::InvalidCredential(_) => StatusCode::BAD_REQUEST,
+            IssueError::Chain(Cis4TransactionError::NodeRejected(_)) => StatusCode::BAD_REQUEST,
+            IssueError::Chain(_) => StatusCode::BAD_GATEWAY,
+            IssueError::Internal(_) | IssueError::WorkerStopped => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Whether the node rejected the transaction as malformed. This most
+    /// likely means that the nonce of the worker is wrong, so no further
+    /// transactions are sent.
+    fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            IssueError::Chain(Cis4TransactionError::RPCError(RPCError::CallError(err)))
+                if err.code() == tonic::Code::InvalidArgument
+        )
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MakeSecretsError {
+    #[error("Incompatible number of values and randomness: {values} != {randomness}.")]
+    IncompatibleValuesAndRandomness { values: usize, randomness: usize },
+    #[error("Invalid timestamp.")]
+    InvalidTimestamp,
+}
+
+/// Get the next nonce of the issuer account. This fails if the account has
+/// transactions that are not finalized, since the nonce might then be reused.
+pub async fn initial_nonce(
+    client: &mut v2::Client,
+    account: &AccountAddress,
+) -> anyhow::Result<Nonce> {
+    let nonce = client.get_next_account_sequence_number(account).await?;
+    anyhow::ensure!(
+        nonce.all_final,
+        "Not all transactions are finalized. Refusing to start."
+    );
+    tracing::info!("Using account {account} starting at nonce {}.", nonce.nonce);
+    Ok(nonce.nonce)
+}
+
+/// The handle with which request handlers ask the worker to issue credentials.
+pub struct IssuerHandle<R> {
+    sender: tokio::sync::mpsc::Sender<IssueChannelData<R>>,
+}
+
+// Derived `Clone` would require `R: Clone`.
+impl<R> Clone for IssuerHandle<R> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<R> IssuerHandle<R> {
+    /// Register the credential and return the [`IssueResponse`] serialized
+    /// as JSON.
+    #[tracing::instrument(level = "debug", skip_all, fields(holder_id = %credential.holder_id))]
+    pub async fn issue(
+        &self,
+        credential: CredentialInfo,
+        request: R,
+    ) -> Result<serde_json::Value, IssueError> {
+        let (response_sender, response_receiver) = tokio::sync::oneshot::channel();
+        let data = IssueChannelData {
+            credential,
+            request,
+            response_sender,
+        };
+        if self.sender.send(data).await.is_err() {
+            tracing::error!("Failed enqueueing transaction. The transaction sender task died.");
+            return Err(IssueError::WorkerStopped);
+        }
+        response_receiver.await.unwrap_or_else(|_| {
+            // There is no information in the error.
+            tracing::error!(
+                "Failed sending transaction; did not get response from transaction sender."
+            );
+            Err(IssueError::WorkerStopped)
+        })
+    }
+}
+
+pub struct IssuerWorker<P: IssuancePolicy> {
+    config: IssuerConfig,
+    policy: P,
+    /// The nonce of the next transaction of the issuer account.
+    nonce: Nonce,
+    /// A channel where new issue requests will be given.
+    receiver: tokio::sync::mpsc::Receiver<IssueChannelData<P::Request>>,
+}
+
+fn send_and_log<T>(sender: tokio::sync::oneshot::Sender<T>, msg: T) {
+    if sender.send(msg).is_err() {
+        tracing::warn!("Unabled to send response. The request has been cancelled.");
+    }
+}
+
+impl<P: IssuancePolicy> IssuerWorker<P> {
+    /// Create a worker that sends transactions from the issuer account
+    /// starting at `nonce`, see [`initial_nonce`], together with the handle
+    /// that requests are sent to it with.
+    pub fn new(config: IssuerConfig, policy: P, nonce: Nonce) -> (Self, IssuerHandle<P::Request>) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(CHANNEL_CAPACITY);
+        let worker = Self {
+            config,
+            policy,
+            nonce,
+            receiver,
+        };
+        (worker, IssuerHandle { sender })
+    }
+
+    /// A transaction sender job. This listens for incoming issue requests and
+    /// sends transactions to the chain.
+    ///
+    /// This is intended to be run in a background task that is started once.
+    /// The task is not cancel-safe in the sense that if it is cancelled, the
+    /// state of [`IssuerWorker`] might be inconsistent. This is why this
+    /// function consumes `self`.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn tx_sender(mut self) {
+        while let Some(IssueChannelData {
+            credential,
+            request,
+            response_sender,
+        }) = self.receiver.recv().await
+        {
+            let result = self.issue(&credential, request).await;
+            let fatal = matches!(&result, Err(err) if err.is_fatal());
+            match &result {
+                Ok(_) => {}
+                Err(err) if fatal => tracing::error!(
+                    "Transaction rejected by the node: {err}
+                     Assuming account sequence number is incorrect, or some other \
+                     inconsistency, and terminating."
+                ),
+                Err(err @ IssueError::InvalidCredential(_)) => tracing::warn!("{err}"),
+                Err(IssueError::Chain(Cis4TransactionError::NodeRejected(rr))) => {
+                    tracing::warn!("Bad request rejected by the contract: {rr:?}")
+                }
+                Err(err) => tracing::error!("Failed to issue credential: {err}"),
+            }
+            send_and_log(response_sender, result);
+            if fatal {
+                break;
+            }
+        }
+        // All senders of the channel have been dropped, or the worker stopped.
+        tracing::info!("The transaction sender was stopped.");
+    }
+
+    async fn issue(
+        &mut self,
+        credential: &CredentialInfo,
+        request: P::Request,
+    ) -> Result<serde_json::Value, IssueError> {
+        self.policy
+            .validate(credential, &request)
+            .map_err(IssueError::InvalidCredential)?;
+        let tx_hash = self.register_credential(credential).await?;
+        tracing::debug!(
+            "Successfully registered credential with id {} (tx {tx_hash}).",
+            credential.holder_id
+        );
+
+        let values = self.policy.attributes(request);
+        let credential = self.make_secrets(values, credential)?;
+        let response = IssueResponse {
+            tx_hash,
+            credential,
+        };
+        Ok(serde_json::to_value(&response)
+            .expect("Serialization of web3id credentials does not fail."))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(holder_id = %credential.holder_id))]
+    async fn register_credential(
+        &mut self,
+        credential: &CredentialInfo,
+    ) -> Result<TransactionHash, Cis4TransactionError> {
+        let expiry = TransactionTime::minutes_after(5);
+        tracing::debug!("Using nonce {} to send the transaction.", self.nonce);
+        let metadata = Cis4TransactionMetadata {
+            sender_address: self.config.issuer.address,
+            nonce: self.nonce,
+            expiry,
+            energy: GivenEnergy::Add(self.config.max_register_energy),
+            amount: Amount::zero(),
+        };
+
+        let tx_hash = self
+            .config
+            .contract_client
+            .register_credential(&self.config.issuer, &metadata, credential, &[])
+            .await?;
+        self.nonce.next_mut();
+        Ok(tx_hash)
+    }
+
+    fn make_secrets(
+        &self,
+        values: BTreeMap<String, Web3IdAttribute>,
+        credential: &CredentialInfo,
+    ) -> Result<Web3IdCredential<ArCurve, Web3IdAttribute>, MakeSecretsError> {
+        let mut randomness = BTreeMap::new();
+        {
+            let mut rng = rand::thread_rng();
+            for idx in values.keys() {
+                randomness.insert(
+                    idx.clone(),
+                    pedersen_commitment::Randomness::generate(&mut rng),
+                );
+            }
+        }
+
+        let crypto_params = self.config.crypto_params.borrow().clone();
+        let signed_commitments = SignedCommitments::from_secrets(
+            &crypto_params.params,
+            &values,
+            &randomness,
+            &credential.holder_id,
+            self.config.issuer_key.as_ref(),
+            self.config.contract_client.address,
+        )
+        .ok_or(MakeSecretsError::IncompatibleValuesAndRandomness {
+            values: values.len(),
+            randomness: randomness.len(),
+        })?;
+
+        Ok(Web3IdCredential {
+            holder_id: credential.holder_id,
+            network: self.config.network,
+            registry: self.config.contract_client.address,
+            credential_type: [
+                String::from("VerifiableCredential"),
+                String::from("ConcordiumVerifiableCredential"),
+                self.config.credential_type.credential_type.clone(),
+            ]
+            .into(),
+            valid_from: to_date_time(credential.valid_from)?,
+            valid_until: credential.valid_until.map(to_date_time).transpose()?,
+            issuer_key: self.config.issuer_key.public().into(),
+            values,
+            randomness,
+            signature: signed_commitments.signature,
+            credential_schema: self.config.credential_schema_url.clone(),
+        })
+    }
+}
+
+fn to_date_time(timestamp: Timestamp) -> Result<chrono::DateTime<chrono::Utc>, MakeSecretsError> {
+    chrono::Utc
+        .timestamp_millis_opt(timestamp.timestamp_millis() as i64)
+        .single()
+        .ok_or(MakeSecretsError::InvalidTimestamp)
+}
@@ -1,10 +1,9 @@
 use concordium_rust_sdk::{
     contract_client::MetadataUrl,
-    id::constants::ArCurve,
-    types::hashes::TransactionHash,
-    web3id::{did::Method, Web3IdAttribute, Web3IdCredential},
+    web3id::{did::Method, Web3IdAttribute},
 };
 use std::collections::BTreeMap;
+pub use web3id_issuer_engine::IssueResponse;
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -22,10 +21,3 @@ pub struct IssueRequest {
     pub credential_subject: CredentialSubject,
     pub metadata_url: MetadataUrl,
 }
-
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct IssueResponse {
-    pub tx_hash: TransactionHash,
-    pub credential: Web3IdCredential<ArCurve, Web3IdAttribute>,
-}
@@ -5,36 +5,27 @@ use axum::{
     routing::{get, post},
     Router,
 };
-use axum_prometheus::{
-    metrics_exporter_prometheus::PrometheusHandle, PrometheusMetricLayerBuilder,
-};
+use axum_prometheus::PrometheusMetricLayerBuilder;
 use clap::Parser;
 use concordium_rust_sdk::{
-    cis4::{Cis4Contract, Cis4TransactionError, Cis4TransactionMetadata},
-    common::types::{KeyPair, TransactionTime},
+    cis4::Cis4Contract,
     contract_client::CredentialInfo,
-    id::{constants::ArCurve, pedersen_commitment},
-    smart_contracts::common::{Amount, Timestamp},
+    smart_contracts::common::Timestamp,
     types::{
         hashes::{BlockHash, TransactionHash},
-        transactions::send::GivenEnergy,
-        ContractAddress, CryptographicParameters, Energy, Nonce, WalletAccount,
+        ContractAddress, Energy, WalletAccount,
     },
     v2::{self, upward::UnknownDataError, BlockIdentifier, QueryError, Scheme},
-    web3id::{
-        did::Network, CredentialHolderId, SignedCommitments, Web3IdAttribute, Web3IdCredential,
-    },
-};
-use futures::{Future, FutureExt};
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    net::SocketAddr,
-    path::PathBuf,
-    sync::Arc,
+    web3id::{did::Network, Web3IdAttribute},
 };
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 use tonic::transport::ClientTlsConfig;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse};
-use web3id_issuer::{IssueRequest, IssueResponse};
+use web3id_issuer::IssueRequest;
+use web3id_issuer_engine::{
+    initial_nonce, refresh_params, start_services, CryptoParams, IssuancePolicy, IssueError,
+    IssuerConfig, IssuerHandle, IssuerWorker, ParamsReceiver,
+};
 
 #[derive(clap::Parser, Debug)]
 #[clap(arg_required_else_help(true))]
@@ -113,75 +104,14 @@ struct App {
         env = "CONCORDIUM_WEB3ID_ISSUER_MAX_REGISTER_ENERGY"
     )]
     max_register_energy: Energy,
-}
-
-/// Data sent on a channel from the request handler task to the transaction
-/// sender task.
-#[derive(Debug)]
-struct IssueChannelData {
-    credential: CredentialInfo,
-    /// The channel where the response is sent.
-    response_sender: tokio::sync::oneshot::Sender<Result<TransactionHash, Error>>,
-}
-
-struct IssuerWorker {
-    client: Cis4Contract,
-    issuer: WalletAccount,
-    nonce_counter: Nonce,
-    max_register_energy: Energy,
-    /// A channel where new issue requests will be given.
-    receiver: tokio::sync::mpsc::Receiver<IssueChannelData>,
-}
-
-impl IssuerWorker {
-    /// A transaction sender job. This listens for incoming issue requests and
-    /// sends transactions to the chain.
-    ///
-    /// This is intended to be run in a background task that is started once.
-    /// The task is not cancel-safe in the sense that if it is cancelled, the
-    /// state of [`IssuerWorker`] might be inconsistent. This is why this
-    /// function consumes `self`.
-    #[tracing::instrument(level = "debug", skip_all)]
-    async fn tx_sender(mut self) {
-        while let Some(IssueChannelData {
-            credential,
-            response_sender,
-        }) = self.receiver.recv().await
-        {
-            let expiry = TransactionTime::minutes_after(5);
-            let metadata = Cis4TransactionMetadata {
-                sender_address: self.issuer.address,
-                nonce: self.nonce_counter,
-                expiry,
-                energy: GivenEnergy::Add(self.max_register_energy),
-                amount: Amount::zero(),
-            };
-
-            let res = self.register_credential(&credential, &metadata).await;
-            if response_sender.send(res).is_err() {
-                tracing::warn!("Unabled to send response. The request has been cancelled.");
-            }
-        }
-    }
-
-    #[tracing::instrument(level = "debug", skip_all, fields(holder_id = %credential.holder_id))]
-    async fn register_credential(
-        &mut self,
-        credential: &CredentialInfo,
-        metadata: &Cis4TransactionMetadata,
-    ) -> Result<TransactionHash, Error> {
-        tracing::info!(
-            "Using nonce {} to send the transaction.",
-            self.nonce_counter
-        );
-
-        let tx_hash = self
-            .client
-            .register_credential(&self.issuer, metadata, credential, &[])
-            .await?;
-        self.nonce_counter.next_mut();
-        Ok(tx_hash)
-    }
+    #[clap(
+        long = "params-refresh-interval",
+        help = "Interval (in seconds) at which to check whether the cryptographic parameters \
+                have changed, e.g., due to a protocol update.",
+        default_value = "60",
+        env = "CONCORDIUM_WEB3ID_ISSUER_PARAMS_REFRESH_INTERVAL"
+    )]
+    params_refresh_interval: u64,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -190,14 +120,12 @@ enum Error {
     InvalidRequest(#[from] JsonRejection),
     #[error("Unable to parse path: {0}")]
     InvalidPath(#[from] PathRejection),
-    #[error("Unable to submit transaction: {0}")]
-    CouldNotSubmit(#[from] Cis4TransactionError),
+    #[error("Unable to issue credential: {0}")]
+    Issue(#[from] IssueError),
     #[error("Unknown data: {0}")]
     UnknownData(#[from] UnknownDataError),
     #[error("Transaction query error: {0}.")]
     Query(#[from] QueryError),
-    #[error("Internal error: {0}.")]
-    Internal(String),
     #[error("Invalid time ranges.")]
     InvalidTimeRange,
     #[error("The network was not as expected.")]
@@ -247,19 +175,19 @@ impl axum::response::IntoResponse for Error {
                     axum::Json("Invalid ID.".to_string()),
                 )
             }
-            Error::CouldNotSubmit(e) => {
-                tracing::error!("Failed to submit transaction: {e}");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    axum::Json("Could not submit transaction.".to_string()),
-                )
-            }
-            Error::Internal(e) => {
-                tracing::error!("Another internal error: {e}");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    axum::Json("Internal error.".to_string()),
-                )
+            Error::Issue(e) => {
+                let status = e.status_code();
+                if status.is_server_error() {
+                    (
+                        status,
+                        axum::Json("Could not issue credential.".to_string()),
+                    )
+                } else {
+                    (
+                        status,
+                        axum::Json(format!("Could not issue credential: {e}")),
+                    )
+                }
             }
             Error::Query(e) => {
                 if e.is_not_found() {
@@ -287,59 +215,25 @@ impl axum::response::IntoResponse for Error {
     }
 }
 
-#[derive(Clone, Debug)]
-struct State {
-    crypto_params: Arc<CryptographicParameters>,
-    client: Cis4Contract,
-    issuer_key: Arc<KeyPair>,
-    network: Network,
-    credential_schema: String,
-    credential_type: BTreeSet<String>,
-    // A channel where new issue requests should be sent to the worker.
-    sender: tokio::sync::mpsc::Sender<IssueChannelData>,
-}
+/// Issues credentials with the attributes given in the request. Requests are
+/// validated by the handler.
+struct RequestedAttributes;
 
-fn make_secrets(
-    state: &State,
-    holder_id: CredentialHolderId,
-    request: IssueRequest,
-) -> Result<Web3IdCredential<ArCurve, Web3IdAttribute>, Error> {
-    let mut randomness = BTreeMap::new();
-    {
-        let mut rng = rand::thread_rng();
-        for idx in request.credential_subject.attributes.keys() {
-            randomness.insert(
-                idx.clone(),
-                pedersen_commitment::Randomness::generate(&mut rng),
-            );
-        }
-    }
+impl IssuancePolicy for RequestedAttributes {
+    type Request = BTreeMap<String, Web3IdAttribute>;
 
-    let signed_commitments = SignedCommitments::from_secrets(
-        &state.crypto_params,
-        &request.credential_subject.attributes,
-        &randomness,
-        &holder_id,
-        state.issuer_key.as_ref(),
-        state.client.address,
-    )
-    .ok_or_else(|| {
-        Error::Internal("Incorrect number of values vs. randomness. This should not happen.".into())
-    })?;
+    fn attributes(&self, request: Self::Request) -> BTreeMap<String, Web3IdAttribute> {
+        request
+    }
+}
 
-    Ok(Web3IdCredential {
-        registry: state.client.address,
-        issuer_key: state.issuer_key.public().into(),
-        values: request.credential_subject.attributes,
-        randomness,
-        signature: signed_commitments.signature,
-        holder_id,
-        network: state.network,
-        credential_type: state.credential_type.clone(),
-        credential_schema: state.credential_schema.clone(),
-        valid_from: request.valid_from,
-        valid_until: request.valid_until,
-    })
+#[derive(Clone)]
+struct State {
+    crypto_params: ParamsReceiver,
+    client: Cis4Contract,
+    network: Network,
+    // The handle with which new issue requests are sent to the worker.
+    issuer: IssuerHandle<BTreeMap<String, Web3IdAttribute>>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -372,7 +266,7 @@ async fn status(
 async fn issue_credential(
     axum::extract::State(state): axum::extract::State<State>,
     request: Result<axum::Json<IssueRequest>, JsonRejection>,
-) -> Result<axum::Json<IssueResponse>, Error> {
+) -> Result<axum::Json<serde_json::Value>, Error> {
     tracing::info!("Request to issue a credential.");
     let axum::Json(request) = request?;
 
@@ -411,35 +305,30 @@ async fn issue_credential(
         metadata_url: request.metadata_url.clone(),
     };
 
-    let (response_sender, response_receiver) = tokio::sync::oneshot::channel();
     // Ask the issuer worker to send the transaction.
-    if state
-        .sender
-        .send(IssueChannelData {
-            credential: cred_info,
-            response_sender,
-        })
-        .await
-        .is_err()
-    {
-        tracing::error!("Failed enqueueing transaction. The transaction sender task died.");
-        return Err(Error::Internal("Failed sending transaction.".into()));
-    }
+    let response = state
+        .issuer
+        .issue(cred_info, request.credential_subject.attributes)
+        .await?;
+    Ok(axum::Json(response))
+}
 
-    if let Ok(r) = response_receiver.await {
-        let tx_hash = r?;
-        let credential = make_secrets(&state, holder_id, request)?;
-        Ok(axum::Json(IssueResponse {
-            tx_hash,
-            credential,
-        }))
-    } else {
-        // There is no information in the error.
-        tracing::error!(
-            "Failed sending transaction; did not get response from transaction sender."
-        );
-        Err(Error::Internal("Failed sending transaction.".into()))
-    }
+/// Struct returned by the `health` endpoint. It returns the version of the
+/// backend and the hash of the cryptographic parameters in use.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Health {
+    version: &'static str,
+    crypto_params_hash: String,
+}
+
+/// Handles the `health` endpoint.
+#[tracing::instrument(level = "info", skip_all)]
+async fn health(axum::extract::State(state): axum::extract::State<State>) -> axum::Json<Health> {
+    axum::Json(Health {
+        version: env!("CARGO_PKG_VERSION"),
+        crypto_params_hash: state.crypto_params.borrow().hash.clone(),
+    })
 }
 
 #[tokio::main]
@@ -450,6 +339,7 @@ async fn main() -> anyhow::Result<()> {
         use tracing_subscriber::prelude::*;
         let log_filter = tracing_subscriber::filter::Targets::new()
             .with_target(module_path!(), app.log_level)
+            .with_target("web3id_issuer_engine", app.log_level)
             .with_target("tower_http", app.log_level);
         tracing_subscriber::registry()
             .with(tracing_subscriber::fmt::layer())
@@ -485,52 +375,47 @@ async fn main() -> anyhow::Result<()> {
 
     let issuer = WalletAccount::from_json_file(app.wallet)?;
 
-    let nonce = client
-        .get_next_account_sequence_number(&issuer.address)
-        .await?;
+    let nonce = initial_nonce(&mut client, &issuer.address).await?;
+
     anyhow::ensure!(
-        nonce.all_final,
-        "Not all transactions are finalized. Refusing to start."
+        app.params_refresh_interval > 0,
+        "Parameters refresh interval should be positive."
     );
-
+    let crypto_params = CryptoParams::query(&mut client).await?;
     tracing::info!(
-        "Using account {} starting at nonce {}.",
-        issuer.address,
-        nonce.nonce
+        "Using cryptographic parameters with hash {}.",
+        crypto_params.hash
     );
-
-    let crypto_params = client
-        .get_cryptographic_parameters(BlockIdentifier::LastFinal)
-        .await?
-        .response;
+    let (params_sender, crypto_params) = tokio::sync::watch::channel(Arc::new(crypto_params));
+    tokio::spawn(refresh_params(
+        client.clone(),
+        std::time::Duration::from_secs(app.params_refresh_interval),
+        params_sender,
+    ));
     let mut client = Cis4Contract::create(client, app.registry).await?;
 
     let credential_schema = client.registry_metadata(BlockIdentifier::LastFinal).await?;
 
-    let (issuer_sender, issuer_receiver) = tokio::sync::mpsc::channel(100);
-
-    let worker = IssuerWorker {
-        client: client.clone(),
-        issuer,
-        nonce_counter: nonce.nonce,
-        max_register_energy: app.max_register_energy,
-        receiver: issuer_receiver,
-    };
+    let (worker, issuer) = IssuerWorker::new(
+        IssuerConfig {
+            crypto_params: crypto_params.clone(),
+            contract_client: client.clone(),
+            network: app.network,
+            issuer,
+            issuer_key: Arc::new(issuer_key),
+            credential_type: credential_schema.credential_type,
+            credential_schema_url: credential_schema.credential_schema.schema_ref.url().into(),
+            max_register_energy: app.max_register_energy,
+        },
+        RequestedAttributes,
+        nonce,
+    );
 
     let state = State {
         client,
-        crypto_params: Arc::new(crypto_params),
+        crypto_params,
         network: app.network,
-        credential_schema: credential_schema.credential_schema.schema_ref.url().into(),
-        credential_type: [
-            "VerifiableCredential".into(),
-            "ConcordiumVerifiableCredential".into(),
-            credential_schema.credential_type.credential_type,
-        ]
-        .into_iter()
-        .collect(),
-        issuer_key: Arc::new(issuer_key),
-        sender: issuer_sender,
+        issuer,
     };
 
     let (prometheus_layer, metric_handle) = PrometheusMetricLayerBuilder::new()
@@ -542,6 +427,7 @@ async fn main() -> anyhow::Result<()> {
     let router = Router::new()
         .route("/v0/issue", post(issue_credential))
         .route("/v0/status/:transactionHash", get(status))
+        .route("/v0/health", get(health))
         .with_state(state)
         .layer(
             tower_http::trace::TraceLayer::new_for_http()
@@ -564,153 +450,3 @@ async fn main() -> anyhow::Result<()> {
     )
     .await
 }
-
-/// Like `tokio::spawn` but the provided future is modified so that
-/// once it terminates it sends a message on the provided channel.
-/// This is sent regardless of how the future terminates, as long as it
-/// terminates normally (i.e., does not panic).
-pub fn spawn_cancel<T>(
-    died_sender: tokio::sync::broadcast::Sender<()>,
-    future: T,
-) -> tokio::task::JoinHandle<T::Output>
-where
-    T: futures::Future + Send + 'static,
-    T::Output: Send + 'static,
-{
-    tokio::spawn(async move {
-        let res = future.await;
-        // We ignore errors here since this always happens at the end of a task.
-        // Since we keep one receiver alive until the end of the `main` function
-        // the error should not happen anyhow.
-        let _ = died_sender.send(());
-        res
-    })
-}
-
-async fn start_services(
-    issuer_worker: IssuerWorker,
-    metric_handle: PrometheusHandle,
-    prometheus_address: Option<SocketAddr>,
-    listen_address: SocketAddr,
-    router: Router,
-) -> anyhow::Result<()> {
-    // If a service crashes it will send a message on this channel, which will then
-    // cause all of the other services to shut down.
-    let (died_sender, died_receiver) = tokio::sync::broadcast::channel(10);
-    // We create additional receivers of the broadcast messages.
-    // We do this before any message is potentially sent to make sure all receivers
-    // will receive them.
-    let prometheus_receiver = died_sender.subscribe();
-    let server_receiver = died_sender.subscribe();
-
-    {
-        let died_sender = died_sender.clone();
-        // Start handling of shutdown signals now, before starting the server.
-        let shutdown_signal = set_shutdown()?;
-        tokio::spawn(async move {
-            shutdown_signal.await;
-            if died_sender.send(()).is_err() {
-                tracing::error!("Unable to notify shutdown.");
-            }
-        });
-    }
-
-    if let Some(prometheus_address) = prometheus_address {
-        let prometheus_api = axum::Router::new()
-            .route(
-                "/metrics",
-                axum::routing::get(|| async move { metric_handle.render() }),
-            )
-            .layer(tower_http::timeout::TimeoutLayer::new(
-                std::time::Duration::from_millis(1000),
-            ))
-            .layer(tower_http::limit::RequestBodyLimitLayer::new(0));
-        tracing::info!("Starting prometheus server at {prometheus_address}.");
-        spawn_cancel(died_sender.clone(), async move {
-            axum::Server::bind(&prometheus_address)
-                .serve(prometheus_api.into_make_service())
-                .with_graceful_shutdown(shutdown_trigger(prometheus_receiver))
-                .await
-                .context("Unable to start Prometheus server.")?;
-            Ok::<(), anyhow::Error>(())
-        });
-    }
-
-    let transaction_sender = spawn_cancel(died_sender.clone(), issuer_worker.tx_sender());
-
-    let server_handle = spawn_cancel(
-        died_sender.clone(),
-        axum::Server::bind(&listen_address)
-            .http1_header_read_timeout(std::time::Duration::from_secs(5))
-            .serve(router.into_make_service())
-            .with_graceful_shutdown(shutdown_trigger(server_receiver)),
-    );
-
-    // Wait until something triggers shutdown. Either a signal handler or an error
-    // in the service startup or transaction sender.
-    shutdown_trigger(died_receiver).await;
-    tracing::info!("Received shutdown trigger.");
-
-    // Wait for the server to shut down itself. However this might not happen since
-    // open connections can make it wait until the client drops them.
-    // Thus we wait for 5s only, which should be sufficient to handle any
-    // outstanding requests. After that we forcefully kill it.
-    let res = tokio::time::timeout(std::time::Duration::from_secs(5), server_handle).await;
-
-    if res.is_err() {
-        tracing::error!(
-            "Unable to stop the server gracefully in required time. Terminating forcefully."
-        )
-    }
-    // Abort the sender explicitly. Since the server is now not responding even if
-    // there are any pending transactions there is no point in sending them/waiting
-    // for them to be sent.
-    // This would happen implicitly as well, so this is here just for documentation.
-    transaction_sender.abort();
-
-    Ok(())
-}
-
-async fn shutdown_trigger(mut receiver: tokio::sync::broadcast::Receiver<()>) {
-    if receiver.recv().await.is_err() {
-        tracing::error!("Shutdown channel unexpectedly closed.");
-    }
-}
-
-/// Construct a future for shutdown signals (for unix: SIGINT and SIGTERM) (for
-/// windows: ctrl c and ctrl break). The signal handler is set when the future
-/// is polled and until then the default signal handler.
-fn set_shutdown() -> anyhow::Result<impl Future<Output = ()>> {
-    #[cfg(unix)]
-    {
-        use tokio::signal::unix as unix_signal;
-
-        let mut terminate_stream = unix_signal::signal(unix_signal::SignalKind::terminate())?;
-        let mut interrupt_stream = unix_signal::signal(unix_signal::SignalKind::interrupt())?;
-
-        Ok(async move {
-            futures::future::select(
-                Box::pin(terminate_stream.recv()),
-                Box::pin(interrupt_stream.recv()),
-            )
-            .map(|_| ())
-            .await
-        })
-    }
-    #[cfg(windows)]
-    {
-        use tokio::signal::windows as windows_signal;
-
-        let mut ctrl_break_stream = windows_signal::ctrl_break()?;
-        let mut ctrl_c_stream = windows_signal::ctrl_c()?;
-
-        Ok(async move {
-            futures::future::select(
-                Box::pin(ctrl_break_stream.recv()),
-                Box::pin(ctrl_c_stream.recv()),
-            )
-            .map(|_| ())
-            .await
-        })
-    }
-}
@@ -0,0 +1,159 @@
+//! The gRPC interface of the verifier. It shares the state and the
+//! verification code with the HTTP API.
+use crate::{limits::ClientLimiter, Error, State};
+use concordium_rust_sdk::{
+    id::constants::ArCurve,
+    web3id::{Presentation, Web3IdAttribute},
+};
+use std::sync::Arc;
+
+mod proto {
+    tonic::include_proto!("concordium.web3id.verifier.v0");
+}
+
+pub use proto::verifier_server::VerifierServer;
+
+pub struct VerifierService {
+    pub state: State,
+    pub limiter: Arc<ClientLimiter>,
+    /// Maximum number of presentations in a batch.
+    pub max_batch_size: usize,
+}
+
+impl VerifierService {
+    /// Take a permit for the client that made the request.
+    fn acquire<T>(
+        &self,
+        request: &tonic::Request<T>,
+    ) -> Result<Option<crate::limits::ClientPermit>, tonic::Status> {
+        // The remote address is only missing if the server is not served over
+        // TCP, in which case there is no client to limit.
+        let Some(addr) = request.remote_addr() else {
+            return Ok(None);
+        };
+        match self.limiter.acquire(addr.ip()) {
+            Some(permit) => Ok(Some(permit)),
+            None => Err(to_status(Error::TooManyRequests(addr.ip()))),
+        }
+    }
+}
+
+/// Parse the presentation. Parsing failures are reported in the same way as
+/// invalid JSON bodies in the HTTP API.
+fn parse_presentation(
+    presentation: Option<proto::Presentation>,
+) -> Result<Presentation<ArCurve, Web3IdAttribute>, Error> {
+    let result = match presentation.and_then(|p| p.presentation) {
+        Some(proto::presentation::Presentation::Json(json)) => serde_json::from_str(&json),
+        None => return Err(Error::MissingPresentation),
+    };
+    result.map_err(Error::InvalidPresentation)
+}
+
+async fn verify(
+    state: &State,
+    presentation: Presentation<ArCurve, Web3IdAttribute>,
+) -> Result<proto::VerifyResponse, Error> {
+    let response = crate::verify(state, presentation).await?;
+    Ok(proto::VerifyResponse {
+        block: response.block.as_ref().to_vec(),
+        block_time: response.block_time.timestamp_millis() as u64,
+        request: serde_json::to_string(&response.request)
+            .expect("Request can be serialized to JSON."),
+    })
+}
+
+fn to_error(error: Error) -> proto::VerificationError {
+    let (_, response) = error.into_parts();
+    proto::VerificationError {
+        code: response.code.as_str().into(),
+        message: response.message.clone(),
+        details: serde_json::to_string(&response).expect("Error response can be serialized."),
+    }
+}
+
+fn to_status(error: Error) -> tonic::Status {
+    let (status, response) = error.into_parts();
+    let code = match status {
+        axum::http::StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+        axum::http::StatusCode::NOT_FOUND => tonic::Code::NotFound,
+        axum::http::StatusCode::TOO_MANY_REQUESTS => tonic::Code::ResourceExhausted,
+        axum::http::StatusCode::BAD_GATEWAY => tonic::Code::Unavailable,
+        _ => tonic::Code::Internal,
+    };
+    let details = serde_json::to_vec(&response).expect("Error response can be serialized.");
+    tonic::Status::with_details(code, response.message, details.into())
+}
+
+#[tonic::async_trait]
+impl proto::verifier_server::Verifier for VerifierService {
+    #[tracing::instrument(level = "info", skip_all)]
+    async fn verify(
+        &self,
+        request: tonic::Request<proto::VerifyRequest>,
+    ) -> Result<tonic::Response<proto::VerifyResponse>, tonic::Status> {
+        let _permit = self.acquire(&request)?;
+        let presentation =
+            parse_presentation(request.into_inner().presentation).map_err(to_status)?;
+        let response = verify(&self.state, presentation).await.map_err(to_status)?;
+        Ok(tonic::Response::new(response))
+    }
+
+    #[tracing::instrument(level = "info", skip_all)]
+    async fn verify_batch(
+        &self,
+        request: tonic::Request<proto::VerifyBatchRequest>,
+    ) -> Result<tonic::Response<proto::VerifyBatchResponse>, tonic::Status> {
+        let _permit = self.acquire(&request)?;
+        let presentations = request.into_inner().presentations;
+        if presentations.len() > self.max_batch_size {
+            return Err(tonic::Status::invalid_argument(format!(
+                "The batch has {} presentations, but at most {} are allowed.",
+                presentations.len(),
+                self.max_batch_size
+            )));
+        }
+        let presentations = presentations
+            .into_iter()
+            .map(|presentation| parse_presentation(Some(presentation)))
+            .collect::<Vec<_>>();
+        // The whole batch is limited like a single presentation, so that a
+        // batch does not allow more work than a request to the HTTP API.
+        let credentials = presentations
+            .iter()
+            .flatten()
+            .map(|presentation| presentation.verifiable_credential.len())
+            .sum();
+        self.state
+            .limits
+            .check_credentials(credentials)
+            .map_err(|e| to_status(e.into()))?;
+        // The presentations are verified one at a time, since the client only
+        // holds a single permit for the batch.
+        let mut results = Vec::with_capacity(presentations.len());
+        for presentation in presentations {
+            let result = match presentation {
+                Ok(presentation) => verify(&self.state, presentation).await,
+                Err(e) => Err(e),
+            };
+            results.push(proto::VerifyResult {
+                result: Some(match result {
+                    Ok(response) => proto::verify_result::Result::Success(response),
+                    Err(e) => proto::verify_result::Result::Error(to_error(e)),
+                }),
+            });
+        }
+        Ok(tonic::Response::new(proto::VerifyBatchResponse { results }))
+    }
+
+    #[tracing::instrument(level = "info", skip_all)]
+    async fn health(
+        &self,
+        _request: tonic::Request<proto::HealthRequest>,
+    ) -> Result<tonic::Response<proto::HealthResponse>, tonic::Status> {
+        Ok(tonic::Response::new(proto::HealthResponse {
+            version: env!("CARGO_PKG_VERSION").into(),
+            crypto_params_hash: self.state.params.borrow().hash.clone(),
+        }))
+    }
+}
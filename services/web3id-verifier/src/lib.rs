@@ -0,0 +1,36 @@
+use concordium_rust_sdk::{
+    id::types::{CredentialRegistrationID, IpIdentity},
+    types::ContractAddress,
+    web3id::{CredentialHolderId, CredentialMetadata},
+};
+
+pub mod snapshot;
+
+/// The on-chain reference of a credential as stated in a presentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CredentialReference {
+    #[serde(rename_all = "camelCase")]
+    Account {
+        issuer: IpIdentity,
+        cred_id: CredentialRegistrationID,
+    },
+    #[serde(rename_all = "camelCase")]
+    Web3Id {
+        contract: ContractAddress,
+        holder: CredentialHolderId,
+    },
+}
+
+impl From<&CredentialMetadata> for CredentialReference {
+    fn from(value: &CredentialMetadata) -> Self {
+        match *value {
+            CredentialMetadata::Account { issuer, cred_id } => {
+                CredentialReference::Account { issuer, cred_id }
+            }
+            CredentialMetadata::Web3Id { contract, holder } => {
+                CredentialReference::Web3Id { contract, holder }
+            }
+        }
+    }
+}
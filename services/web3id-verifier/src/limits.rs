@@ -0,0 +1,205 @@
+//! Limits on the size of presentations and on the number of concurrent
+//! requests per client. Verification of a presentation requires node queries
+//! and proof verification for each credential and statement, so these are
+//! checked before any work is done on the request.
+use crate::policy::CredentialTemplate;
+use concordium_rust_sdk::{
+    id::{constants::ArCurve, id_proof_types::AtomicStatement},
+    web3id::{CredentialProof, Presentation, Web3IdAttribute},
+};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+/// Limits on the complexity of presentations.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum number of credentials in a presentation.
+    pub max_credentials: usize,
+    /// Maximum number of statements about a single credential.
+    pub max_statements: usize,
+    /// Maximum size of the set in set membership and non-membership
+    /// statements.
+    pub max_set_size: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LimitError {
+    #[error("The request has {actual} credentials, but at most {max} are allowed.")]
+    TooManyCredentials { actual: usize, max: usize },
+    #[error("Credential {index} has {actual} statements, but at most {max} are allowed.")]
+    TooManyStatements {
+        index: usize,
+        actual: usize,
+        max: usize,
+    },
+    #[error(
+        "Statement {statement} of credential {index} has a set of size {actual}, but at most \
+         {max} elements are allowed."
+    )]
+    SetTooLarge {
+        index: usize,
+        statement: usize,
+        actual: usize,
+        max: usize,
+    },
+}
+
+impl Limits {
+    /// Check the number of credentials in a request.
+    pub fn check_credentials(&self, actual: usize) -> Result<(), LimitError> {
+        if actual > self.max_credentials {
+            return Err(LimitError::TooManyCredentials {
+                actual,
+                max: self.max_credentials,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the presentation is within the limits.
+    pub fn check_presentation(
+        &self,
+        presentation: &Presentation<ArCurve, Web3IdAttribute>,
+    ) -> Result<(), LimitError> {
+        self.check_credentials(presentation.verifiable_credential.len())?;
+        for (index, proof) in presentation.verifiable_credential.iter().enumerate() {
+            match proof {
+                CredentialProof::Account { proofs, .. } => {
+                    self.check_statements(index, proofs.iter().map(|(s, _)| s))?
+                }
+                CredentialProof::Web3Id { proofs, .. } => {
+                    self.check_statements(index, proofs.iter().map(|(s, _)| s))?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the proof request described by the templates is within the
+    /// limits, so that the resulting presentation would be accepted.
+    pub fn check_templates(&self, templates: &[CredentialTemplate]) -> Result<(), LimitError> {
+        self.check_credentials(templates.len())?;
+        for (index, template) in templates.iter().enumerate() {
+            // The statements are counted in the order they appear in the
+            // request: revealed attributes, the minimum age, and then the
+            // additional statements.
+            match template {
+                CredentialTemplate::Account {
+                    minimum_age,
+                    reveal,
+                    statement,
+                    ..
+                } => self.check_statements_from(
+                    index,
+                    reveal.len() + usize::from(minimum_age.is_some()),
+                    statement.iter(),
+                )?,
+                CredentialTemplate::Web3Id {
+                    reveal, statement, ..
+                } => self.check_statements_from(index, reveal.len(), statement.iter())?,
+            }
+        }
+        Ok(())
+    }
+
+    fn check_statements<'a, TagType: 'a>(
+        &self,
+        index: usize,
+        statements: impl ExactSizeIterator<
+            Item = &'a AtomicStatement<ArCurve, TagType, Web3IdAttribute>,
+        >,
+    ) -> Result<(), LimitError> {
+        self.check_statements_from(index, 0, statements)
+    }
+
+    /// Check `statements`, which follow `preceding` statements without sets
+    /// about the same credential.
+    fn check_statements_from<'a, TagType: 'a>(
+        &self,
+        index: usize,
+        preceding: usize,
+        statements: impl ExactSizeIterator<
+            Item = &'a AtomicStatement<ArCurve, TagType, Web3IdAttribute>,
+        >,
+    ) -> Result<(), LimitError> {
+        let actual = preceding + statements.len();
+        if actual > self.max_statements {
+            return Err(LimitError::TooManyStatements {
+                index,
+                actual,
+                max: self.max_statements,
+            });
+        }
+        for (statement_index, statement) in (preceding..).zip(statements) {
+            let set_size = match statement {
+                AtomicStatement::AttributeInSet { statement } => statement.set.len(),
+                AtomicStatement::AttributeNotInSet { statement } => statement.set.len(),
+                _ => continue,
+            };
+            if set_size > self.max_set_size {
+                return Err(LimitError::SetTooLarge {
+                    index,
+                    statement: statement_index,
+                    actual: set_size,
+                    max: self.max_set_size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tracks the number of requests in progress for each client, identified by
+/// its IP address.
+#[derive(Debug)]
+pub struct ClientLimiter {
+    max_concurrent: usize,
+    active: Mutex<HashMap<IpAddr, usize>>,
+}
+
+/// A permit for a request in progress. The request is no longer counted
+/// once the permit is dropped.
+#[derive(Debug)]
+pub struct ClientPermit {
+    limiter: Arc<ClientLimiter>,
+    client: IpAddr,
+}
+
+impl ClientLimiter {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get a permit for a new request from the client, or [`None`] if the
+    /// client already has the maximum number of requests in progress.
+    pub fn acquire(self: &Arc<Self>, client: IpAddr) -> Option<ClientPermit> {
+        let mut active = self.active.lock().expect("Lock is not poisoned.");
+        let count = active.entry(client).or_insert(0);
+        if *count >= self.max_concurrent {
+            return None;
+        }
+        *count += 1;
+        Some(ClientPermit {
+            limiter: self.clone(),
+            client,
+        })
+    }
+}
+
+impl Drop for ClientPermit {
+    fn drop(&mut self) {
+        let mut active = self.limiter.active.lock().expect("Lock is not poisoned.");
+        if let Some(count) = active.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.client);
+            }
+        }
+    }
+}
@@ -1,26 +1,37 @@
 use anyhow::Context;
 use axum::{
-    extract::rejection::JsonRejection,
+    extract::{rejection::JsonRejection, ConnectInfo},
     http::{self, StatusCode},
+    middleware::Next,
     routing::{get, post},
     Json, Router,
 };
-use axum_prometheus::PrometheusMetricLayerBuilder;
+use axum_prometheus::{metrics, PrometheusMetricLayerBuilder};
 use clap::Parser;
 use concordium_rust_sdk::{
+    cis4::{Cis4Contract, Cis4QueryError},
     contract_client::CredentialStatus,
-    id::{constants::ArCurve, types::GlobalContext},
+    id::constants::ArCurve,
     types::hashes::BlockHash,
     v2::{self, BlockIdentifier, Scheme},
     web3id::{
-        self, did::Network, CredentialLookupError, Presentation, PresentationVerificationError,
-        Web3IdAttribute,
+        self, did::Network, CredentialLookupError, CredentialWithMetadata, Presentation,
+        PresentationVerificationError, ProofMetadata, Web3IdAttribute,
     },
 };
 use futures::{Future, FutureExt};
-use std::sync::Arc;
+use limits::{ClientLimiter, LimitError, Limits};
+use policy::{BuildRequest, CredentialTemplate, Statements, TemplateError};
+use rand::Rng;
+use std::{collections::BTreeMap, net::SocketAddr, path::PathBuf, sync::Arc};
 use tonic::transport::ClientTlsConfig;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse};
+use web3id_crypto_params::{refresh_params, CryptoParams, ParamsReceiver};
+use web3id_verifier::CredentialReference;
+
+mod grpc;
+mod limits;
+mod policy;
 
 #[derive(clap::Parser, Debug)]
 #[clap(version, author)]
@@ -73,53 +84,410 @@ struct App {
         env = "CONCORDIUM_WEB3ID_VERIFIER_PROMETHEUS_ADDRESS"
     )]
     prometheus_address: Option<std::net::SocketAddr>,
+    #[clap(
+        long = "policies",
+        help = "Path to a JSON file with named policies that can be used to construct proof \
+                requests.",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_POLICIES"
+    )]
+    policies: Option<PathBuf>,
+    #[clap(
+        long = "params-refresh-interval",
+        help = "Interval (in seconds) at which to check whether the cryptographic parameters \
+                have changed, e.g., due to a protocol update.",
+        default_value = "60",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_PARAMS_REFRESH_INTERVAL"
+    )]
+    params_refresh_interval: u64,
+    #[clap(
+        long = "max-credentials",
+        help = "Maximum number of credentials in a presentation or proof request.",
+        default_value = "10",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_MAX_CREDENTIALS"
+    )]
+    max_credentials: usize,
+    #[clap(
+        long = "max-statements",
+        help = "Maximum number of statements about a single credential in a presentation.",
+        default_value = "20",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_MAX_STATEMENTS"
+    )]
+    max_statements: usize,
+    #[clap(
+        long = "max-set-size",
+        help = "Maximum size of the set in set membership and non-membership statements.",
+        default_value = "100",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_MAX_SET_SIZE"
+    )]
+    max_set_size: usize,
+    #[clap(
+        long = "max-concurrent-requests-per-client",
+        help = "Maximum number of verification and proof request construction requests that a \
+                single client (identified by IP address) can have in progress.",
+        default_value = "4",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_MAX_CONCURRENT_REQUESTS_PER_CLIENT"
+    )]
+    max_concurrent_requests_per_client: usize,
+    #[clap(
+        long = "grpc-listen-address",
+        help = "Listen address for the gRPC server. If not set, the gRPC server will not start.",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_GRPC_LISTEN_ADDRESS"
+    )]
+    grpc_listen_address: Option<SocketAddr>,
+    #[clap(
+        long = "grpc-max-batch-size",
+        help = "Maximum number of presentations in a single gRPC batch verification request.",
+        default_value = "10",
+        env = "CONCORDIUM_WEB3ID_VERIFIER_GRPC_MAX_BATCH_SIZE"
+    )]
+    grpc_max_batch_size: usize,
 }
 
 #[derive(Debug, thiserror::Error)]
 enum Error {
     #[error("Unable to parse request: {0}")]
     InvalidRequest(#[from] JsonRejection),
-    #[error("Unable to look up all credentials: {0}")]
-    CredentialLookup(#[from] CredentialLookupError),
+    #[error("Unable to parse presentation: {0}")]
+    InvalidPresentation(serde_json::Error),
+    #[error("The request does not contain a presentation.")]
+    MissingPresentation,
+    #[error("Unable to query the node: {0}")]
+    NodeAccess(#[from] v2::QueryError),
+    #[error("Unable to look up all credentials.")]
+    CredentialLookup(Vec<CredentialReport>),
     #[error("One or more credentials are not active.")]
-    InactiveCredentials,
+    InactiveCredentials(Vec<CredentialReport>),
     #[error("Invalid proof: {0}.")]
-    InvalidProof(#[from] PresentationVerificationError),
+    InvalidProof(PresentationVerificationError, Vec<CredentialReport>),
+    #[error("Unknown policy: {0}")]
+    UnknownPolicy(String),
+    #[error("Invalid proof request description: {0}")]
+    InvalidTemplate(#[from] TemplateError),
+    #[error("Unable to query the registry: {0}")]
+    RegistryLookup(#[from] Cis4QueryError),
+    #[error("Request exceeds limits: {0}")]
+    LimitExceeded(#[from] LimitError),
+    #[error("Too many concurrent requests from {0}.")]
+    TooManyRequests(std::net::IpAddr),
 }
 
-impl axum::response::IntoResponse for Error {
-    fn into_response(self) -> axum::response::Response {
-        let r = match self {
+/// Machine readable error codes returned in the body of failed requests.
+/// They are serialized as the strings returned by [`ErrorCode::as_str`].
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(into = "&'static str")]
+enum ErrorCode {
+    /// The request body could not be parsed as a presentation.
+    InvalidRequest,
+    /// The node could not be queried.
+    NodeUnavailable,
+    /// At least one of the credentials could not be looked up.
+    CredentialLookupFailed,
+    /// At least one of the credentials is not active.
+    InactiveCredentials,
+    /// The cryptographic proofs of the presentation are not valid.
+    InvalidProof,
+    /// The requested policy is not configured.
+    UnknownPolicy,
+    /// The description of the proof request does not match the subjects.
+    InvalidTemplate,
+    /// The metadata of a registry contract could not be queried.
+    RegistryLookupFailed,
+    /// The request has too many credentials, statements, or too large sets.
+    LimitExceeded,
+    /// The client has too many requests in progress.
+    TooManyRequests,
+}
+
+impl ErrorCode {
+    /// The code as it appears in responses and metrics labels.
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "INVALID_REQUEST",
+            ErrorCode::NodeUnavailable => "NODE_UNAVAILABLE",
+            ErrorCode::CredentialLookupFailed => "CREDENTIAL_LOOKUP_FAILED",
+            ErrorCode::InactiveCredentials => "INACTIVE_CREDENTIALS",
+            ErrorCode::InvalidProof => "INVALID_PROOF",
+            ErrorCode::UnknownPolicy => "UNKNOWN_POLICY",
+            ErrorCode::InvalidTemplate => "INVALID_TEMPLATE",
+            ErrorCode::RegistryLookupFailed => "REGISTRY_LOOKUP_FAILED",
+            ErrorCode::LimitExceeded => "LIMIT_EXCEEDED",
+            ErrorCode::TooManyRequests => "TOO_MANY_REQUESTS",
+        }
+    }
+}
+
+impl From<ErrorCode> for &'static str {
+    fn from(code: ErrorCode) -> Self {
+        code.as_str()
+    }
+}
+
+/// Machine readable error codes for an individual credential in the
+/// presentation.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum CredentialErrorCode {
+    /// The credential, its issuer or registry could not be found.
+    LookupFailed,
+    /// The credential was revoked.
+    Revoked,
+    /// The credential has expired.
+    Expired,
+    /// The credential is not yet valid.
+    NotActivated,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CredentialError {
+    code: CredentialErrorCode,
+    message: String,
+}
+
+/// Details about a single credential in a presentation that failed
+/// verification. One report is returned for each credential in the
+/// presentation, in the order in which they appear in the presentation.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CredentialReport {
+    /// The index of the credential in the presentation.
+    index: usize,
+    created: chrono::DateTime<chrono::Utc>,
+    network: Network,
+    credential: CredentialReference,
+    /// The status of the credential, if it could be looked up.
+    status: Option<CredentialStatus>,
+    /// The reason this credential was rejected, if any.
+    error: Option<CredentialError>,
+}
+
+impl CredentialReport {
+    fn new(index: usize, metadata: &ProofMetadata) -> Self {
+        Self {
+            index,
+            created: metadata.created,
+            network: metadata.network,
+            credential: (&metadata.cred_metadata).into(),
+            status: None,
+            error: None,
+        }
+    }
+
+    /// Record the result of looking up the credential.
+    fn set_lookup_result(
+        &mut self,
+        result: &Result<CredentialWithMetadata, CredentialLookupError>,
+    ) {
+        match result {
+            Ok(cm) => {
+                self.status = Some(cm.status);
+                self.error = match cm.status {
+                    CredentialStatus::Active => None,
+                    CredentialStatus::Revoked => Some(CredentialError {
+                        code: CredentialErrorCode::Revoked,
+                        message: "The credential has been revoked.".into(),
+                    }),
+                    CredentialStatus::Expired => Some(CredentialError {
+                        code: CredentialErrorCode::Expired,
+                        message: "The credential has expired.".into(),
+                    }),
+                    CredentialStatus::NotActivated => Some(CredentialError {
+                        code: CredentialErrorCode::NotActivated,
+                        message: "The credential is not yet valid.".into(),
+                    }),
+                };
+            }
+            Err(e) => {
+                self.error = Some(CredentialError {
+                    code: CredentialErrorCode::LookupFailed,
+                    message: format!("Unable to look up credential: {e}"),
+                });
+            }
+        }
+    }
+}
+
+/// Machine readable error codes for the cryptographic proofs of a
+/// presentation.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ProofErrorCode {
+    /// The proofs are not valid for the public data of the credentials.
+    InvalidProof,
+}
+
+/// The reason the proofs of a presentation were rejected. The proofs are
+/// verified for the presentation as a whole, so the failure cannot be
+/// attributed to a single credential.
+#[derive(Debug, serde::Serialize)]
+struct ProofError {
+    code: ProofErrorCode,
+    message: String,
+}
+
+/// The body of the response in case verification fails.
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    code: ErrorCode,
+    message: String,
+    /// Reports for all credentials in the presentation. This is empty if
+    /// the presentation could not be parsed or the node could not be
+    /// queried.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    credentials: Vec<CredentialReport>,
+    /// Set if the credentials were looked up and active, but the proofs are
+    /// not valid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proof_error: Option<ProofError>,
+}
+
+impl Error {
+    /// Log the error and construct the status code and body of the response.
+    /// This is shared between the HTTP and gRPC APIs.
+    fn into_parts(self) -> (StatusCode, ErrorResponse) {
+        let mut proof_error = None;
+        let (status, code, message, credentials) = match self {
             Error::InvalidRequest(e) => {
                 tracing::warn!("Invalid request. Failed to parse presentation: {e}");
                 (
                     StatusCode::BAD_REQUEST,
-                    axum::Json(format!("Invalid presentation format: {e}")),
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid presentation format: {e}"),
+                    Vec::new(),
                 )
             }
-            Error::CredentialLookup(e) => {
-                tracing::warn!("One or more credentials were not present: {e}");
+            Error::InvalidPresentation(e) => {
+                tracing::warn!("Invalid request. Failed to parse presentation: {e}");
+                (
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid presentation format: {e}"),
+                    Vec::new(),
+                )
+            }
+            Error::MissingPresentation => {
+                tracing::warn!("Invalid request. Missing presentation.");
+                (
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidRequest,
+                    "The request does not contain a presentation.".into(),
+                    Vec::new(),
+                )
+            }
+            Error::NodeAccess(e) => {
+                tracing::error!("Unable to query the node: {e}");
+                (
+                    StatusCode::BAD_GATEWAY,
+                    ErrorCode::NodeUnavailable,
+                    "Unable to query the node.".into(),
+                    Vec::new(),
+                )
+            }
+            Error::CredentialLookup(credentials) => {
+                for report in &credentials {
+                    if let Some(e) = &report.error {
+                        tracing::warn!("Credential {}: {}", report.index, e.message);
+                    }
+                }
                 (
                     StatusCode::NOT_FOUND,
-                    axum::Json(format!("One or more credentials were not found: {e}")),
+                    ErrorCode::CredentialLookupFailed,
+                    "One or more credentials were not found.".into(),
+                    credentials,
                 )
             }
-            Error::InactiveCredentials => {
-                tracing::warn!("One or more credentials are not active at present.");
+            Error::InactiveCredentials(credentials) => {
+                for report in &credentials {
+                    if let Some(e) = &report.error {
+                        tracing::warn!("Credential {}: {}", report.index, e.message);
+                    }
+                }
                 (
                     StatusCode::BAD_REQUEST,
-                    axum::Json("One or more credentials are not active at present.".into()),
+                    ErrorCode::InactiveCredentials,
+                    "One or more credentials are not active at present.".into(),
+                    credentials,
                 )
             }
-            Error::InvalidProof(e) => {
+            Error::InvalidProof(e, credentials) => {
                 tracing::warn!("Invalid cryptographic proofs: {e}");
+                proof_error = Some(ProofError {
+                    code: ProofErrorCode::InvalidProof,
+                    message: e.to_string(),
+                });
+                (
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::InvalidProof,
+                    format!("Invalid cryptographic proofs: {e}."),
+                    credentials,
+                )
+            }
+            Error::UnknownPolicy(name) => {
+                tracing::warn!("Request for unknown policy {name}.");
+                (
+                    StatusCode::NOT_FOUND,
+                    ErrorCode::UnknownPolicy,
+                    format!("Unknown policy: {name}"),
+                    Vec::new(),
+                )
+            }
+            Error::InvalidTemplate(e) => {
+                tracing::warn!("Invalid proof request description: {e}");
                 (
                     StatusCode::BAD_REQUEST,
-                    axum::Json(format!("Invalid cryptographic proofs: {e}.")),
+                    ErrorCode::InvalidTemplate,
+                    format!("Invalid proof request description: {e}"),
+                    Vec::new(),
+                )
+            }
+            Error::RegistryLookup(e) => {
+                tracing::warn!("Unable to get registry metadata: {e}");
+                (
+                    StatusCode::NOT_FOUND,
+                    ErrorCode::RegistryLookupFailed,
+                    "Unable to get registry metadata.".into(),
+                    Vec::new(),
+                )
+            }
+            Error::LimitExceeded(e) => {
+                tracing::warn!("Request exceeds limits: {e}");
+                (
+                    StatusCode::BAD_REQUEST,
+                    ErrorCode::LimitExceeded,
+                    e.to_string(),
+                    Vec::new(),
+                )
+            }
+            Error::TooManyRequests(client) => {
+                tracing::warn!("Too many concurrent requests from {client}.");
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    ErrorCode::TooManyRequests,
+                    "Too many concurrent requests.".into(),
+                    Vec::new(),
                 )
             }
         };
-        r.into_response()
+        metrics::increment_counter!(
+            "web3id_verifier_rejected_requests_total",
+            "code" => code.as_str()
+        );
+        (
+            status,
+            ErrorResponse {
+                code,
+                message,
+                credentials,
+                proof_error,
+            },
+        )
+    }
+}
+
+impl axum::response::IntoResponse for Error {
+    fn into_response(self) -> axum::response::Response {
+        let (status, response) = self.into_parts();
+        (status, axum::Json(response)).into_response()
     }
 }
 
@@ -127,7 +495,9 @@ impl axum::response::IntoResponse for Error {
 struct State {
     client: v2::Client,
     network: Network,
-    params: Arc<GlobalContext<ArCurve>>,
+    params: ParamsReceiver,
+    policies: Arc<BTreeMap<String, Vec<CredentialTemplate>>>,
+    limits: Limits,
 }
 
 #[derive(serde::Serialize)]
@@ -141,50 +511,170 @@ struct Response {
 
 #[tracing::instrument(level = "info", skip_all)]
 async fn verify_presentation(
-    axum::extract::State(mut state): axum::extract::State<State>,
+    axum::extract::State(state): axum::extract::State<State>,
     presentation: Result<axum::Json<Presentation<ArCurve, Web3IdAttribute>>, JsonRejection>,
 ) -> Result<axum::Json<Response>, Error> {
-    let presentation = presentation?;
+    let axum::Json(presentation) = presentation?;
+    Ok(axum::Json(verify(&state, presentation).await?))
+}
+
+/// Verify the presentation against the public data in the last finalized
+/// block.
+async fn verify(
+    state: &State,
+    presentation: Presentation<ArCurve, Web3IdAttribute>,
+) -> Result<Response, Error> {
+    state.limits.check_presentation(&presentation)?;
     let bi = state
         .client
+        .clone()
         .get_block_info(BlockIdentifier::LastFinal)
-        .await
-        .map_err(|e| Error::CredentialLookup(e.into()))?;
-    let public_data = web3id::get_public_data(
-        &mut state.client,
-        state.network,
-        &presentation,
-        bi.block_hash,
-    )
-    .await?;
+        .await?;
+    let metadata = presentation.metadata().collect::<Vec<_>>();
+    let (public_data, reports) =
+        lookup_credentials(state, &metadata, BlockIdentifier::Given(bi.block_hash)).await?;
+    // And then verify the cryptographic proofs.
+    let params = state.params.borrow().clone();
+    let request = presentation
+        .verify(&params.params, public_data.iter().map(|cm| &cm.inputs))
+        .map_err(|e| Error::InvalidProof(e, reports))?;
+    Ok(Response {
+        block: bi.block_hash,
+        block_time: bi.response.block_slot_time,
+        request,
+    })
+}
+
+/// Look up the public data of all the given credentials, and check that they
+/// are all active. A report is produced for each credential, recording the
+/// outcome of the lookup so that a failure can be reported precisely.
+async fn lookup_credentials(
+    state: &State,
+    metadata: &[ProofMetadata],
+    bi: BlockIdentifier,
+) -> Result<(Vec<CredentialWithMetadata>, Vec<CredentialReport>), Error> {
+    let lookups = futures::future::join_all(metadata.iter().map(|meta| {
+        web3id::verify_credential_metadata(state.client.clone(), state.network, meta, bi)
+    }))
+    .await;
+    let mut reports = Vec::with_capacity(metadata.len());
+    for (index, (meta, lookup)) in metadata.iter().zip(&lookups).enumerate() {
+        let mut report = CredentialReport::new(index, meta);
+        report.set_lookup_result(lookup);
+        reports.push(report);
+    }
+    let Ok(public_data) = lookups.into_iter().collect::<Result<Vec<_>, _>>() else {
+        return Err(Error::CredentialLookup(reports));
+    };
     // Check that all credentials are active at the time of the query.
     if !public_data
         .iter()
         .all(|cm| matches!(cm.status, CredentialStatus::Active))
     {
-        return Err(Error::InactiveCredentials);
+        return Err(Error::InactiveCredentials(reports));
     }
-    // And then verify the cryptographic proofs.
-    let request = presentation.verify(&state.params, public_data.iter().map(|cm| &cm.inputs))?;
-    Ok(axum::Json(Response {
-        block: bi.block_hash,
-        block_time: bi.response.block_slot_time,
-        request,
+    Ok((public_data, reports))
+}
+
+/// Construct a proof request with a fresh challenge from either a named
+/// policy or an explicit description of the statements. The subjects are
+/// checked to exist and be active so that the request can be proved.
+#[tracing::instrument(level = "info", skip_all)]
+async fn build_request(
+    axum::extract::State(state): axum::extract::State<State>,
+    request: Result<axum::Json<BuildRequest>, JsonRejection>,
+) -> Result<axum::Json<web3id::Request<ArCurve, Web3IdAttribute>>, Error> {
+    let axum::Json(BuildRequest {
+        statements,
+        subjects,
+    }) = request?;
+    state.limits.check_credentials(subjects.len())?;
+    let templates = match &statements {
+        Statements::Policy(name) => state
+            .policies
+            .get(name)
+            .ok_or_else(|| Error::UnknownPolicy(name.clone()))?,
+        Statements::Credentials(templates) => templates,
+    };
+    state.limits.check_templates(templates)?;
+    if templates.len() != subjects.len() {
+        return Err(TemplateError::SubjectCount {
+            expected: templates.len(),
+            actual: subjects.len(),
+        }
+        .into());
+    }
+
+    let now = chrono::Utc::now();
+    let metadata = templates
+        .iter()
+        .zip(&subjects)
+        .enumerate()
+        .map(|(index, (template, subject))| {
+            Ok(ProofMetadata {
+                created: now,
+                network: state.network,
+                cred_metadata: template.metadata(index, subject)?,
+            })
+        })
+        .collect::<Result<Vec<_>, TemplateError>>()?;
+    lookup_credentials(&state, &metadata, BlockIdentifier::LastFinal).await?;
+
+    let mut credential_statements = Vec::with_capacity(templates.len());
+    for (index, (template, subject)) in templates.iter().zip(&subjects).enumerate() {
+        let ty = if let Some(registry) = template.registry() {
+            let mut contract = Cis4Contract::create(state.client.clone(), registry).await?;
+            let registry_metadata = contract
+                .registry_metadata(BlockIdentifier::LastFinal)
+                .await?;
+            policy::web3id_credential_types(registry_metadata.credential_type.credential_type)
+        } else {
+            Default::default()
+        };
+        credential_statements.push(template.to_statement(
+            index,
+            state.network,
+            subject,
+            ty,
+            now.date_naive(),
+        )?);
+    }
+    Ok(axum::Json(web3id::Request {
+        challenge: rand::thread_rng().gen::<[u8; 32]>().into(),
+        credential_statements,
     }))
 }
 
+/// Reject the request if the client already has the maximum number of
+/// requests in progress. Otherwise the request is counted until the response
+/// is produced.
+async fn limit_clients<B>(
+    axum::extract::State(limiter): axum::extract::State<Arc<ClientLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: http::Request<B>,
+    next: Next<B>,
+) -> Result<axum::response::Response, Error> {
+    let Some(_permit) = limiter.acquire(addr.ip()) else {
+        return Err(Error::TooManyRequests(addr.ip()));
+    };
+    Ok(next.run(request).await)
+}
+
 /// Struct returned by the `health` endpoint. It returns the version of the
-/// backend.
+/// backend and the hash of the cryptographic parameters in use.
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 struct Health {
     version: &'static str,
+    crypto_params_hash: String,
 }
 
 /// Handles the `health` endpoint, returning the version of the backend.
 #[tracing::instrument(level = "info", skip_all)]
-async fn health() -> Json<Health> {
+async fn health(axum::extract::State(state): axum::extract::State<State>) -> Json<Health> {
     Json(Health {
         version: env!("CARGO_PKG_VERSION"),
+        crypto_params_hash: state.params.borrow().hash.clone(),
     })
 }
 
@@ -208,12 +698,23 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("Connecting to node at {}", app.endpoint.uri());
         tracing::info!("On network: {}", app.network);
         tracing::info!("Listening on: {}", app.listen_address);
+        if let Some(grpc_listen_address) = app.grpc_listen_address {
+            tracing::info!("gRPC listening on: {}", grpc_listen_address);
+        }
     }
 
     anyhow::ensure!(
         app.request_timeout >= 1000,
         "Request timeout should be at least 1s."
     );
+    anyhow::ensure!(
+        app.params_refresh_interval > 0,
+        "Parameters refresh interval should be positive."
+    );
+    anyhow::ensure!(
+        app.max_concurrent_requests_per_client > 0,
+        "The maximum number of concurrent requests per client should be positive."
+    );
 
     let endpoint = if app.endpoint.uri().scheme() == Some(&Scheme::HTTPS) {
         app.endpoint
@@ -238,17 +739,37 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Unable to establish connection to the node.")?;
 
-    let params = client
-        .get_cryptographic_parameters(BlockIdentifier::LastFinal)
+    let params = CryptoParams::query(&mut client)
         .await
-        .context("Unable to get cryptographic parameters.")?
-        .response;
+        .context("Unable to get cryptographic parameters.")?;
+    tracing::info!("Using cryptographic parameters with hash {}.", params.hash);
+    let (params_sender, params) = tokio::sync::watch::channel(Arc::new(params));
+    tokio::spawn(refresh_params(
+        client.clone(),
+        std::time::Duration::from_secs(app.params_refresh_interval),
+        params_sender,
+    ));
+
+    let policies = if let Some(path) = &app.policies {
+        let file = std::fs::File::open(path).context("Unable to open policies file.")?;
+        serde_json::from_reader(file).context("Unable to parse policies.")?
+    } else {
+        BTreeMap::new()
+    };
+    tracing::info!("Loaded {} policies.", policies.len());
 
     let state = State {
         client,
         network: app.network,
-        params: Arc::new(params),
+        params,
+        policies: Arc::new(policies),
+        limits: Limits {
+            max_credentials: app.max_credentials,
+            max_statements: app.max_statements,
+            max_set_size: app.max_set_size,
+        },
     };
+    let limiter = Arc::new(ClientLimiter::new(app.max_concurrent_requests_per_client));
 
     let (prometheus_layer, metric_handle) = PrometheusMetricLayerBuilder::new()
         .with_prefix("web3id-verifier")
@@ -277,9 +798,35 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    let grpc_handle = if let Some(grpc_listen_address) = app.grpc_listen_address {
+        let service = grpc::VerifierServer::new(grpc::VerifierService {
+            state: state.clone(),
+            limiter: limiter.clone(),
+            max_batch_size: app.grpc_max_batch_size,
+        })
+        // at most 100kB of data per presentation, as for the HTTP API.
+        .max_decoding_message_size(100_000 * app.grpc_max_batch_size.max(1));
+        let request_timeout = std::time::Duration::from_millis(app.request_timeout);
+        let shutdown_signal = set_shutdown()?;
+        Some(tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .timeout(request_timeout)
+                .trace_fn(|_| tracing::info_span!("grpc"))
+                .add_service(service)
+                .serve_with_shutdown(grpc_listen_address, shutdown_signal)
+                .await
+                .context("Unable to start gRPC server.")?;
+            Ok::<(), anyhow::Error>(())
+        }))
+    } else {
+        None
+    };
+
     // build routes
     let server = Router::new()
         .route("/v0/verify", post(verify_presentation))
+        .route("/v0/request", post(build_request))
+        .route_layer(axum::middleware::from_fn_with_state(limiter, limit_clients))
         .route("/v0/health", get(health))
         .with_state(state)
         .layer(
@@ -297,27 +844,18 @@ async fn main() -> anyhow::Result<()> {
     let shutdown_signal = set_shutdown()?;
     let server_handle = tokio::spawn(async move {
         axum::Server::bind(&app.listen_address)
-            .serve(server.into_make_service())
+            .serve(server.into_make_service_with_connect_info::<SocketAddr>())
             .with_graceful_shutdown(shutdown_signal)
             .await
+            .context("Server crashed.")
     });
 
-    if let Some(prometheus_handle) = prometheus_handle {
-        tokio::select! {
-            val = prometheus_handle => {
-                val.context("Prometheus task panicked.")?.context("Prometheus server crashed.")?;
-            }
-            val = server_handle => {
-                val.context("Server task panicked.")?.context("Server crashed.")?;
-            }
-        }
-    } else {
-        server_handle
-            .await
-            .context("Server task join error.")?
-            .context("Server crashed.")?;
-    }
-    Ok(())
+    // Run until the first of the servers stops.
+    let handles = std::iter::once(server_handle)
+        .chain(prometheus_handle)
+        .chain(grpc_handle);
+    let (result, _, _) = futures::future::select_all(handles).await;
+    result.context("Server task panicked.")?
 }
 
 /// Construct a future for shutdown signals (for unix: SIGINT and SIGTERM) (for
@@ -0,0 +1,243 @@
+//! Construction of proof requests from named policies or high-level
+//! descriptions of the required statements.
+use concordium_rust_sdk::{
+    id::{
+        constants::{ArCurve, AttributeKind},
+        id_proof_types::{AtomicStatement, AttributeInRangeStatement, RevealAttributeStatement},
+        types::{AttributeTag, CredentialRegistrationID, IpIdentity},
+    },
+    smart_contracts::common::attributes,
+    types::ContractAddress,
+    web3id::{
+        did::Network, CredentialHolderId, CredentialMetadata, CredentialStatement, Web3IdAttribute,
+    },
+};
+use std::{collections::BTreeSet, marker::PhantomData};
+
+/// The earliest date of birth that can be stated, in the `YYYYMMDD` format
+/// used by identity credentials.
+const MIN_DATE_OF_BIRTH: &str = "18000101";
+
+/// A description of the statements a single credential has to satisfy.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CredentialTemplate {
+    /// Statements about an identity credential.
+    #[serde(rename_all = "camelCase")]
+    Account {
+        /// Identity providers whose credentials are accepted. If empty,
+        /// credentials from any identity provider are accepted.
+        #[serde(default)]
+        issuers: Vec<IpIdentity>,
+        /// If set, require a proof that the holder is at least this many
+        /// years old.
+        #[serde(default)]
+        minimum_age: Option<u32>,
+        /// Attributes to reveal.
+        #[serde(default)]
+        reveal: Vec<AttributeTag>,
+        /// Any additional statements.
+        #[serde(default)]
+        statement: Vec<AtomicStatement<ArCurve, AttributeTag, Web3IdAttribute>>,
+    },
+    /// Statements about a Web3 ID credential.
+    #[serde(rename_all = "camelCase")]
+    Web3Id {
+        /// The registry contract that the credential must be registered in.
+        registry: ContractAddress,
+        /// Attributes to reveal.
+        #[serde(default)]
+        reveal: Vec<String>,
+        /// Any additional statements.
+        #[serde(default)]
+        statement: Vec<AtomicStatement<ArCurve, String, Web3IdAttribute>>,
+    },
+}
+
+/// The credential that is used to satisfy a [`CredentialTemplate`].
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Subject {
+    #[serde(rename_all = "camelCase")]
+    Account {
+        issuer: IpIdentity,
+        cred_id: CredentialRegistrationID,
+    },
+    #[serde(rename_all = "camelCase")]
+    Web3Id { holder: CredentialHolderId },
+}
+
+/// Description of the statements to request. Either a named policy
+/// configured in the verifier, or an explicit list of templates.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Statements {
+    Policy(String),
+    Credentials(Vec<CredentialTemplate>),
+}
+
+/// The body of a request to construct a proof request.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildRequest {
+    #[serde(flatten)]
+    pub statements: Statements,
+    /// The credentials to use, one for each template, in the same order.
+    pub subjects: Vec<Subject>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Expected {expected} subjects, got {actual}.")]
+    SubjectCount { expected: usize, actual: usize },
+    #[error("Subject {0} does not match the type of its template.")]
+    SubjectMismatch(usize),
+    #[error("Identity provider {issuer} is not accepted for subject {index}.")]
+    IssuerNotAccepted { index: usize, issuer: IpIdentity },
+    #[error("Unable to compute the date for minimum age {0}.")]
+    InvalidAge(u32),
+}
+
+impl CredentialTemplate {
+    /// The registry contract for Web3 ID templates. Its metadata is needed
+    /// to determine the credential type.
+    pub fn registry(&self) -> Option<ContractAddress> {
+        match self {
+            CredentialTemplate::Account { .. } => None,
+            CredentialTemplate::Web3Id { registry, .. } => Some(*registry),
+        }
+    }
+
+    /// The metadata of the credential that would be used to prove the
+    /// statements of this template for the given subject. This is used to
+    /// check that the subject exists before issuing a request.
+    pub fn metadata(
+        &self,
+        index: usize,
+        subject: &Subject,
+    ) -> Result<CredentialMetadata, TemplateError> {
+        match (self, subject) {
+            (CredentialTemplate::Account { issuers, .. }, Subject::Account { issuer, cred_id }) => {
+                if !issuers.is_empty() && !issuers.contains(issuer) {
+                    return Err(TemplateError::IssuerNotAccepted {
+                        index,
+                        issuer: *issuer,
+                    });
+                }
+                Ok(CredentialMetadata::Account {
+                    issuer: *issuer,
+                    cred_id: *cred_id,
+                })
+            }
+            (CredentialTemplate::Web3Id { registry, .. }, Subject::Web3Id { holder }) => {
+                Ok(CredentialMetadata::Web3Id {
+                    contract: *registry,
+                    holder: *holder,
+                })
+            }
+            _ => Err(TemplateError::SubjectMismatch(index)),
+        }
+    }
+
+    /// Construct the statement for the given subject. For Web3 ID templates
+    /// `ty` must be the set of credential types of the registry.
+    pub fn to_statement(
+        &self,
+        index: usize,
+        network: Network,
+        subject: &Subject,
+        ty: BTreeSet<String>,
+        today: chrono::NaiveDate,
+    ) -> Result<CredentialStatement<ArCurve, Web3IdAttribute>, TemplateError> {
+        match (self, subject) {
+            (
+                CredentialTemplate::Account {
+                    minimum_age,
+                    reveal,
+                    statement,
+                    ..
+                },
+                Subject::Account { cred_id, .. },
+            ) => {
+                let mut statements = Vec::with_capacity(statement.len() + reveal.len() + 1);
+                statements.extend(reveal.iter().map(|&attribute_tag| {
+                    AtomicStatement::RevealAttribute {
+                        statement: RevealAttributeStatement { attribute_tag },
+                    }
+                }));
+                if let Some(age) = *minimum_age {
+                    statements.push(minimum_age_statement(age, today)?);
+                }
+                statements.extend(statement.iter().cloned());
+                Ok(CredentialStatement::Account {
+                    network,
+                    cred_id: *cred_id,
+                    statement: statements,
+                })
+            }
+            (
+                CredentialTemplate::Web3Id {
+                    registry,
+                    reveal,
+                    statement,
+                },
+                Subject::Web3Id { holder },
+            ) => {
+                let statements = reveal
+                    .iter()
+                    .map(|attribute_tag| AtomicStatement::RevealAttribute {
+                        statement: RevealAttributeStatement {
+                            attribute_tag: attribute_tag.clone(),
+                        },
+                    })
+                    .chain(statement.iter().cloned())
+                    .collect();
+                Ok(CredentialStatement::Web3Id {
+                    ty,
+                    network,
+                    contract: *registry,
+                    credential: *holder,
+                    statement: statements,
+                })
+            }
+            _ => Err(TemplateError::SubjectMismatch(index)),
+        }
+    }
+}
+
+/// A statement that the date of birth is such that the holder is at least
+/// `age` years old on `today`.
+fn minimum_age_statement(
+    age: u32,
+    today: chrono::NaiveDate,
+) -> Result<AtomicStatement<ArCurve, AttributeTag, Web3IdAttribute>, TemplateError> {
+    // The upper bound of the range is exclusive, so anybody born on the day
+    // exactly `age` years ago must be included.
+    let upper = today
+        .checked_sub_months(chrono::Months::new(age.saturating_mul(12)))
+        .and_then(|d| d.succ_opt())
+        .ok_or(TemplateError::InvalidAge(age))?;
+    Ok(AtomicStatement::AttributeInRange {
+        statement: AttributeInRangeStatement {
+            attribute_tag: AttributeTag(attributes::DOB.0),
+            lower: Web3IdAttribute::String(AttributeKind(MIN_DATE_OF_BIRTH.into())),
+            upper: Web3IdAttribute::String(AttributeKind(upper.format("%Y%m%d").to_string())),
+            _phantom: PhantomData,
+        },
+    })
+}
+
+/// The credential types of credentials in a registry with the given
+/// credential type.
+pub fn web3id_credential_types(credential_type: String) -> BTreeSet<String> {
+    [
+        "VerifiableCredential".into(),
+        "ConcordiumVerifiableCredential".into(),
+        credential_type,
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests;
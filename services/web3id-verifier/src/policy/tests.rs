@@ -0,0 +1,57 @@
+//! Tests of the construction of statements from templates.
+use super::{minimum_age_statement, TemplateError, MIN_DATE_OF_BIRTH};
+use chrono::NaiveDate;
+use concordium_rust_sdk::{
+    id::{constants::AttributeKind, id_proof_types::AtomicStatement, types::AttributeTag},
+    smart_contracts::common::attributes,
+    web3id::Web3IdAttribute,
+};
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+/// The lower and upper bound of a date of birth range statement.
+fn dob_range(age: u32, today: NaiveDate) -> (String, String) {
+    let statement = minimum_age_statement(age, today).expect("Age is valid.");
+    let AtomicStatement::AttributeInRange { statement } = statement else {
+        panic!("Expected a range statement.");
+    };
+    assert_eq!(statement.attribute_tag, AttributeTag(attributes::DOB.0));
+    let as_string = |attribute: Web3IdAttribute| match attribute {
+        Web3IdAttribute::String(AttributeKind(s)) => s,
+        other => panic!("Expected a string attribute, got {other:?}."),
+    };
+    (as_string(statement.lower), as_string(statement.upper))
+}
+
+#[test]
+fn minimum_age_date_boundaries() {
+    let cases = [
+        // Somebody born exactly 18 years ago turns 18 today, so the day after
+        // is the exclusive upper bound.
+        ("ordinary day", 18, date(2024, 3, 15), "20060316"),
+        ("end of month", 18, date(2024, 4, 30), "20060501"),
+        ("end of year", 21, date(2024, 12, 31), "20040101"),
+        // There is no 29 February a year back, so people born on 28 February
+        // are old enough and people born on 1 March are not.
+        ("leap day", 1, date(2024, 2, 29), "20230301"),
+        ("leap day four years back", 4, date(2024, 2, 29), "20200301"),
+        ("zero", 0, date(2024, 3, 15), "20240316"),
+    ];
+    for (name, age, today, upper) in cases {
+        assert_eq!(
+            dob_range(age, today),
+            (MIN_DATE_OF_BIRTH.to_string(), upper.to_string()),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn minimum_age_out_of_range() {
+    assert!(matches!(
+        minimum_age_statement(u32::MAX, date(2024, 3, 15)),
+        Err(TemplateError::InvalidAge(u32::MAX))
+    ));
+}
@@ -0,0 +1,173 @@
+//! Snapshots of the public data needed to verify a presentation. A snapshot
+//! can be exported while connected to a node, and later used to verify the
+//! presentation without network access, e.g., by auditors or in tests.
+use crate::CredentialReference;
+use concordium_rust_sdk::{
+    contract_client::CredentialStatus,
+    id::{constants::ArCurve, types::GlobalContext},
+    types::hashes::BlockHash,
+    v2::{self, BlockIdentifier, QueryError},
+    web3id::{
+        self, did::Network, CredentialLookupError, CredentialsInputs, Presentation,
+        PresentationVerificationError, Request, Web3IdAttribute,
+    },
+};
+use sha2::{Digest, Sha256};
+
+/// The public data of a single credential at the snapshot block.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialSnapshot {
+    pub credential: CredentialReference,
+    pub status: CredentialStatus,
+    pub inputs: CredentialsInputs<ArCurve>,
+}
+
+/// The contents of a snapshot.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotData {
+    pub network: Network,
+    /// The block at which the public data was retrieved.
+    pub block: BlockHash,
+    pub block_time: chrono::DateTime<chrono::Utc>,
+    pub global_context: GlobalContext<ArCurve>,
+    pub credentials: Vec<CredentialSnapshot>,
+}
+
+impl SnapshotData {
+    /// The SHA-256 hash of the JSON serialization of the data.
+    pub fn hash(&self) -> [u8; 32] {
+        let bytes = serde_json::to_vec(self).expect("Snapshot data can be serialized.");
+        Sha256::digest(bytes).into()
+    }
+}
+
+/// A snapshot of public data together with its hash. The hash can be
+/// communicated out of band to check the integrity of the snapshot.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicDataSnapshot {
+    /// Hex encoded SHA-256 hash of the JSON serialization of `data`.
+    pub hash: String,
+    pub data: SnapshotData,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("Unable to query the node: {0}")]
+    Query(#[from] QueryError),
+    #[error("Unable to look up all credentials: {0}")]
+    CredentialLookup(#[from] CredentialLookupError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OfflineVerificationError {
+    #[error("The snapshot hash does not match its contents.")]
+    HashMismatch,
+    #[error("Credential {index} is for network {actual}, but the snapshot is for {expected}.")]
+    NetworkMismatch {
+        index: usize,
+        expected: Network,
+        actual: Network,
+    },
+    #[error("Credential {0} is not in the snapshot.")]
+    MissingCredential(usize),
+    #[error("Credential {index} is not active ({status:?}).")]
+    InactiveCredential {
+        index: usize,
+        status: CredentialStatus,
+    },
+    #[error("Invalid proof: {0}")]
+    InvalidProof(#[from] PresentationVerificationError),
+}
+
+impl PublicDataSnapshot {
+    pub fn new(data: SnapshotData) -> Self {
+        Self {
+            hash: hex::encode(data.hash()),
+            data,
+        }
+    }
+
+    /// Retrieve the public data needed to verify the presentation at the
+    /// given block.
+    pub async fn create(
+        client: &mut v2::Client,
+        network: Network,
+        presentation: &Presentation<ArCurve, Web3IdAttribute>,
+        bi: BlockIdentifier,
+    ) -> Result<Self, SnapshotError> {
+        let block_info = client.get_block_info(bi).await?;
+        let block = block_info.block_hash;
+        let global_context = client.get_cryptographic_parameters(block).await?.response;
+        let public_data = web3id::get_public_data(client, network, presentation, block).await?;
+        let credentials = presentation
+            .metadata()
+            .zip(public_data)
+            .map(|(meta, cm)| CredentialSnapshot {
+                credential: (&meta.cred_metadata).into(),
+                status: cm.status,
+                inputs: cm.inputs,
+            })
+            .collect();
+        Ok(Self::new(SnapshotData {
+            network,
+            block,
+            block_time: block_info.response.block_slot_time,
+            global_context,
+            credentials,
+        }))
+    }
+
+    /// Check that the hash matches the contents of the snapshot.
+    pub fn check_hash(&self) -> Result<(), OfflineVerificationError> {
+        if hex::encode(self.data.hash()) == self.hash {
+            Ok(())
+        } else {
+            Err(OfflineVerificationError::HashMismatch)
+        }
+    }
+
+    /// Verify the presentation against the public data in the snapshot. This
+    /// performs the same checks as the verifier service, i.e., that all
+    /// credentials are on the right network and active at the snapshot
+    /// block, and that the cryptographic proofs are valid.
+    pub fn verify(
+        &self,
+        presentation: &Presentation<ArCurve, Web3IdAttribute>,
+    ) -> Result<Request<ArCurve, Web3IdAttribute>, OfflineVerificationError> {
+        self.check_hash()?;
+        let mut inputs = Vec::with_capacity(presentation.verifiable_credential.len());
+        for (index, meta) in presentation.metadata().enumerate() {
+            if meta.network != self.data.network {
+                return Err(OfflineVerificationError::NetworkMismatch {
+                    index,
+                    expected: self.data.network,
+                    actual: meta.network,
+                });
+            }
+            let reference = CredentialReference::from(&meta.cred_metadata);
+            let Some(credential) = self
+                .data
+                .credentials
+                .iter()
+                .find(|c| c.credential == reference)
+            else {
+                return Err(OfflineVerificationError::MissingCredential(index));
+            };
+            if !matches!(credential.status, CredentialStatus::Active) {
+                return Err(OfflineVerificationError::InactiveCredential {
+                    index,
+                    status: credential.status,
+                });
+            }
+            inputs.push(&credential.inputs);
+        }
+        let request = presentation.verify(&self.data.global_context, inputs.into_iter())?;
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,270 @@
+//! Tests that the hash of a snapshot detects changes to its contents, and of
+//! verifying presentations against snapshots.
+use super::{CredentialSnapshot, OfflineVerificationError, PublicDataSnapshot, SnapshotData};
+use crate::CredentialReference;
+use concordium_rust_sdk::{
+    common::types::KeyPair,
+    contract_client::{CredentialStatus, IssuerKey},
+    id::{
+        constants::{ArCurve, AttributeKind},
+        id_proof_types::{AtomicStatement, RevealAttributeStatement},
+        pedersen_commitment,
+        types::GlobalContext,
+    },
+    types::{hashes::BlockHash, ContractAddress},
+    web3id::{
+        did::Network, CredentialHolderId, CredentialStatement, CredentialsInputs, Presentation,
+        Request, SignedCommitments, Web3IdAttribute, Web3IdCredential,
+    },
+};
+use std::collections::BTreeMap;
+
+fn snapshot() -> PublicDataSnapshot {
+    PublicDataSnapshot::new(SnapshotData {
+        network: Network::Testnet,
+        block: BlockHash::new([1; 32]),
+        block_time: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        global_context: GlobalContext::generate("snapshot tests".into()),
+        credentials: Vec::new(),
+    })
+}
+
+#[test]
+fn snapshot_hash_matches() {
+    let snapshot = snapshot();
+    assert!(snapshot.check_hash().is_ok());
+    assert_eq!(snapshot.hash, hex::encode(snapshot.data.hash()));
+}
+
+#[test]
+fn snapshot_survives_serialization() {
+    let json = serde_json::to_string(&snapshot()).unwrap();
+    let snapshot: PublicDataSnapshot = serde_json::from_str(&json).unwrap();
+    assert!(snapshot.check_hash().is_ok());
+}
+
+#[test]
+fn tampered_snapshot_is_detected() {
+    let tampered: [(&str, fn(&mut PublicDataSnapshot)); 5] = [
+        ("network", |s| s.data.network = Network::Mainnet),
+        ("block", |s| s.data.block = BlockHash::new([2; 32])),
+        ("block time", |s| {
+            s.data.block_time += chrono::Duration::seconds(1)
+        }),
+        ("global context", |s| {
+            s.data.global_context = GlobalContext::generate("other".into())
+        }),
+        ("hash", |s| {
+            s.hash = hex::encode([0u8; 32]);
+        }),
+    ];
+    for (name, tamper) in tampered {
+        let mut snapshot = snapshot();
+        tamper(&mut snapshot);
+        assert!(
+            matches!(
+                snapshot.check_hash(),
+                Err(OfflineVerificationError::HashMismatch)
+            ),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn tampered_snapshot_file_is_detected() {
+    let mut json = serde_json::to_value(snapshot()).unwrap();
+    json["data"]["network"] = serde_json::to_value(Network::Mainnet).unwrap();
+    let snapshot: PublicDataSnapshot = serde_json::from_value(json).unwrap();
+    assert!(matches!(
+        snapshot.check_hash(),
+        Err(OfflineVerificationError::HashMismatch)
+    ));
+}
+
+/// A presentation revealing the attributes of a Web3ID credential, and a
+/// snapshot with the public data it is valid for.
+struct Fixture {
+    request: Request<ArCurve, Web3IdAttribute>,
+    presentation: Presentation<ArCurve, Web3IdAttribute>,
+    data: SnapshotData,
+}
+
+fn fixture() -> Fixture {
+    let mut rng = rand::thread_rng();
+    let global_context = GlobalContext::generate("snapshot tests".into());
+    let issuer = KeyPair::generate(&mut rng);
+    let holder = KeyPair::generate(&mut rng);
+    let holder_id = CredentialHolderId::new(holder.public());
+    let registry = ContractAddress::new(4718, 0);
+    let values: BTreeMap<_, _> = [("userId", "1234"), ("username", "alice")]
+        .into_iter()
+        .map(|(tag, value)| {
+            (
+                tag.to_string(),
+                Web3IdAttribute::String(AttributeKind(value.into())),
+            )
+        })
+        .collect();
+    let randomness: BTreeMap<_, _> = values
+        .keys()
+        .map(|tag| {
+            (
+                tag.clone(),
+                pedersen_commitment::Randomness::generate(&mut rng),
+            )
+        })
+        .collect();
+    let commitments = SignedCommitments::from_secrets(
+        &global_context,
+        &values,
+        &randomness,
+        &holder_id,
+        &issuer,
+        registry,
+    )
+    .expect("Values and randomness match.");
+    let ty: std::collections::BTreeSet<String> = [
+        "VerifiableCredential".into(),
+        "ConcordiumVerifiableCredential".into(),
+        "TestCredential".into(),
+    ]
+    .into();
+    let credential = Web3IdCredential::<ArCurve, Web3IdAttribute> {
+        holder_id,
+        network: Network::Testnet,
+        registry,
+        credential_type: ty.clone(),
+        valid_from: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        valid_until: None,
+        issuer_key: issuer.public().into(),
+        values,
+        randomness,
+        signature: commitments.signature,
+    };
+    let request = Request::<ArCurve, Web3IdAttribute> {
+        challenge: rand::Rng::gen::<[u8; 32]>(&mut rng).into(),
+        credential_statements: vec![CredentialStatement::Web3Id {
+            ty,
+            network: Network::Testnet,
+            contract: registry,
+            credential: holder_id,
+            statement: ["userId", "username"]
+                .into_iter()
+                .map(|tag| AtomicStatement::RevealAttribute {
+                    statement: RevealAttributeStatement {
+                        attribute_tag: tag.to_string(),
+                    },
+                })
+                .collect(),
+        }],
+    };
+    let presentation = request
+        .clone()
+        .prove(
+            &global_context,
+            std::iter::once(credential.into_inputs(&holder)),
+        )
+        .expect("The credential satisfies the statements.");
+    let credentials = presentation
+        .metadata()
+        .map(|meta| CredentialSnapshot {
+            credential: CredentialReference::from(&meta.cred_metadata),
+            status: CredentialStatus::Active,
+            inputs: CredentialsInputs::Web3 {
+                issuer_pk: IssuerKey::from(issuer.public()),
+            },
+        })
+        .collect();
+    Fixture {
+        request,
+        presentation,
+        data: SnapshotData {
+            network: Network::Testnet,
+            block: BlockHash::new([1; 32]),
+            block_time: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            global_context,
+            credentials,
+        },
+    }
+}
+
+#[test]
+fn presentation_verifies_against_snapshot() {
+    let Fixture {
+        request,
+        presentation,
+        data,
+    } = fixture();
+    let verified = PublicDataSnapshot::new(data)
+        .verify(&presentation)
+        .expect("The presentation is valid.");
+    assert_eq!(verified, request);
+}
+
+#[test]
+fn verification_against_snapshot_fails() {
+    let cases: [(
+        &str,
+        fn(&mut SnapshotData),
+        fn(&OfflineVerificationError) -> bool,
+    ); 4] = [
+        (
+            "wrong network",
+            |data| data.network = Network::Mainnet,
+            |e| {
+                matches!(
+                    e,
+                    OfflineVerificationError::NetworkMismatch {
+                        index: 0,
+                        expected: Network::Mainnet,
+                        actual: Network::Testnet,
+                    }
+                )
+            },
+        ),
+        (
+            "missing credential",
+            |data| data.credentials.clear(),
+            |e| matches!(e, OfflineVerificationError::MissingCredential(0)),
+        ),
+        (
+            "inactive credential",
+            |data| data.credentials[0].status = CredentialStatus::Revoked,
+            |e| {
+                matches!(
+                    e,
+                    OfflineVerificationError::InactiveCredential {
+                        index: 0,
+                        status: CredentialStatus::Revoked,
+                    }
+                )
+            },
+        ),
+        (
+            "bad proof",
+            |data| {
+                // The commitments are signed by another issuer.
+                let other = KeyPair::generate(&mut rand::thread_rng());
+                data.credentials[0].inputs = CredentialsInputs::Web3 {
+                    issuer_pk: IssuerKey::from(other.public()),
+                };
+            },
+            |e| matches!(e, OfflineVerificationError::InvalidProof(_)),
+        ),
+    ];
+    for (name, change, expected) in cases {
+        let Fixture {
+            presentation,
+            mut data,
+            ..
+        } = fixture();
+        change(&mut data);
+        // The snapshot is consistent, so only the verification fails.
+        let result = PublicDataSnapshot::new(data).verify(&presentation);
+        match result {
+            Err(e) => assert!(expected(&e), "{name}: {e}"),
+            Ok(_) => panic!("{name}: the presentation was accepted."),
+        }
+    }
+}
@@ -10,6 +10,7 @@ use concordium_rust_sdk::{
     id::{constants::ArCurve, pedersen_commitment},
     smart_contracts::common::{self as concordium_std, AccountAddress, Amount, Timestamp},
     types::{
+        hashes::BlockHash,
         smart_contracts::{ModuleReference, OwnedContractName, OwnedParameter},
         transactions::{
             send::{self, GivenEnergy},
@@ -20,13 +21,15 @@ use concordium_rust_sdk::{
     v2::{self, BlockIdentifier},
     web3id::{
         did::{IdentifierType, Method, Network},
-        CredentialHolderId, Request, SignedCommitments, Web3IdAttribute, Web3IdCredential,
+        CredentialHolderId, Presentation, Request, SignedCommitments, Web3IdAttribute,
+        Web3IdCredential,
     },
 };
 use key_derivation::{ConcordiumHdWallet, Net};
 use rand::{thread_rng, Rng};
 use std::{collections::BTreeMap, path::PathBuf};
 use web3id_issuer::{CredentialSubject, IssueRequest, IssueResponse};
+use web3id_verifier::snapshot::PublicDataSnapshot;
 
 #[derive(concordium_std::Serial)]
 pub struct InitParams {
@@ -151,8 +154,70 @@ enum Action {
         credential: PathBuf,
         #[clap(long = "seed", help = "The path to the seed phrase.")]
         seed: PathBuf,
-        #[clap(long = "statement", help = "Path to the credential.")]
-        statement: PathBuf,
+        #[clap(
+            long = "statement",
+            help = "Path to the statement.",
+            required_unless_present = "policy"
+        )]
+        statement: Option<PathBuf>,
+        #[clap(
+            long = "policy",
+            help = "Name of a policy configured in the verifier. If set, the request is \
+                    constructed by the verifier instead of from the statement.",
+            conflicts_with = "statement"
+        )]
+        policy: Option<String>,
+        #[clap(
+            long = "request-builder",
+            help = "URL of the verifier endpoint used to construct requests from policies.",
+            default_value = "https://web3id-verifier.testnet.concordium.com/v0/request"
+        )]
+        request_builder: url::Url,
+    },
+    #[clap(
+        name = "snapshot",
+        about = "Export a snapshot of the public data needed to verify a presentation."
+    )]
+    Snapshot {
+        #[clap(long = "presentation", help = "Path to the presentation.")]
+        presentation: PathBuf,
+        #[clap(
+            long = "network",
+            help = "Network of the credentials in the presentation.",
+            default_value = "testnet"
+        )]
+        network: Network,
+        #[clap(
+            long = "block",
+            help = "Block at which to take the snapshot. Defaults to the last finalized block."
+        )]
+        block: Option<BlockHash>,
+        #[clap(long = "out", help = "Path where to write the snapshot.")]
+        out: PathBuf,
+    },
+}
+
+/// All actions: the ones in [`Action`], which need a connection to the node,
+/// and the ones that do not.
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Node(Action),
+    #[clap(
+        name = "verify-offline",
+        about = "Verify a presentation against a snapshot of public data without connecting to \
+                 a node."
+    )]
+    VerifyOffline {
+        #[clap(long = "presentation", help = "Path to the presentation.")]
+        presentation: PathBuf,
+        #[clap(long = "snapshot", help = "Path to the snapshot.")]
+        snapshot: PathBuf,
+        #[clap(
+            long = "hash",
+            help = "Expected hash of the snapshot, if obtained out of band."
+        )]
+        hash: Option<String>,
     },
 }
 
@@ -168,12 +233,20 @@ struct App {
     )]
     endpoint: v2::Endpoint,
     #[command(subcommand)]
-    action: Action,
+    command: Command,
 }
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> anyhow::Result<()> {
     let app: App = App::parse();
+    let action = match app.command {
+        Command::VerifyOffline {
+            presentation,
+            snapshot,
+            hash,
+        } => return verify_offline(presentation, snapshot, hash),
+        Command::Node(action) => action,
+    };
     // TODO: TLS
     let endpoint = app
         .endpoint
@@ -182,7 +255,7 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Unable to connect to the node.")?;
 
-    match app.action {
+    match action {
         Action::NewIssuer {
             metadata_url,
             credential_type,
@@ -249,6 +322,8 @@ async fn main() -> anyhow::Result<()> {
         Action::Prove {
             verifier,
             statement,
+            policy,
+            request_builder,
             seed,
             credential,
         } => {
@@ -274,36 +349,62 @@ async fn main() -> anyhow::Result<()> {
                 wallet.get_verifiable_credential_signing_key(credential.registry, index)?;
             let holder_id = credential.holder_id;
 
-            let mut registry = Cis4Contract::create(client.clone(), credential.registry).await?;
+            let network_client = reqwest::ClientBuilder::new()
+                .connect_timeout(std::time::Duration::from_secs(5))
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?;
 
-            let registry_metadata = registry
-                .registry_metadata(BlockIdentifier::LastFinal)
-                .await?;
+            let request: Request<ArCurve, Web3IdAttribute> = if let Some(policy) = policy {
+                let body = serde_json::json!({
+                    "policy": policy,
+                    "subjects": [{ "type": "web3Id", "holder": holder_id }],
+                });
+                let response = network_client
+                    .post(request_builder)
+                    .json(&body)
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    anyhow::bail!("Unable to construct request: {}", response.text().await?);
+                }
+                response
+                    .json()
+                    .await
+                    .context("Unable to parse constructed request.")?
+            } else {
+                let statement = statement.context("Expected a statement if no policy is set.")?;
+                let mut registry =
+                    Cis4Contract::create(client.clone(), credential.registry).await?;
 
-            let statement = serde_json::from_reader(
-                std::fs::File::open(&statement).context("Unable to open statement.")?,
-            )
-            .context("Unable to parse statement.")?;
-
-            let statement = concordium_rust_sdk::web3id::CredentialStatement::Web3Id::<
-                ArCurve,
-                Web3IdAttribute,
-            > {
-                ty: [
-                    "VerifiableCredential".into(),
-                    "ConcordiumVerifiableCredential".into(),
-                    registry_metadata.credential_type.credential_type,
-                ]
-                .into_iter()
-                .collect(),
-                network: concordium_rust_sdk::web3id::did::Network::Testnet,
-                contract: credential.registry,
-                credential: holder_id,
-                statement,
-            };
-            let request = Request {
-                challenge: thread_rng().gen::<[u8; 32]>().into(),
-                credential_statements: vec![statement],
+                let registry_metadata = registry
+                    .registry_metadata(BlockIdentifier::LastFinal)
+                    .await?;
+
+                let statement = serde_json::from_reader(
+                    std::fs::File::open(&statement).context("Unable to open statement.")?,
+                )
+                .context("Unable to parse statement.")?;
+
+                let statement = concordium_rust_sdk::web3id::CredentialStatement::Web3Id::<
+                    ArCurve,
+                    Web3IdAttribute,
+                > {
+                    ty: [
+                        "VerifiableCredential".into(),
+                        "ConcordiumVerifiableCredential".into(),
+                        registry_metadata.credential_type.credential_type,
+                    ]
+                    .into_iter()
+                    .collect(),
+                    network: concordium_rust_sdk::web3id::did::Network::Testnet,
+                    contract: credential.registry,
+                    credential: holder_id,
+                    statement,
+                };
+                Request {
+                    challenge: thread_rng().gen::<[u8; 32]>().into(),
+                    credential_statements: vec![statement],
+                }
             };
             let gc = client
                 .get_cryptographic_parameters(BlockIdentifier::LastFinal)
@@ -321,11 +422,6 @@ async fn main() -> anyhow::Result<()> {
                 end.signed_duration_since(start).num_milliseconds()
             );
 
-            let network_client = reqwest::ClientBuilder::new()
-                .connect_timeout(std::time::Duration::from_secs(5))
-                .timeout(std::time::Duration::from_secs(10))
-                .build()?;
-
             let start = chrono::Utc::now();
             let response = network_client.post(verifier).json(&proof).send().await?;
             let end = chrono::Utc::now();
@@ -341,6 +437,28 @@ async fn main() -> anyhow::Result<()> {
                 println!("Verification failed.");
             }
         }
+        Action::Snapshot {
+            presentation,
+            network,
+            block,
+            out,
+        } => {
+            let presentation: Presentation<ArCurve, Web3IdAttribute> = serde_json::from_reader(
+                std::fs::File::open(&presentation).context("Unable to open presentation.")?,
+            )
+            .context("Unable to parse presentation.")?;
+            let bi = block.map_or(BlockIdentifier::LastFinal, BlockIdentifier::Given);
+            let snapshot = PublicDataSnapshot::create(&mut client, network, &presentation, bi)
+                .await
+                .context("Unable to create snapshot.")?;
+            std::fs::write(&out, serde_json::to_string_pretty(&snapshot)?)?;
+            println!(
+                "Snapshot of block {} with hash {} written to {}.",
+                snapshot.data.block,
+                snapshot.hash,
+                out.display()
+            );
+        }
         Action::View {
             registry,
             seed,
@@ -568,3 +686,36 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Verify a presentation against a snapshot of public data. This does not
+/// need a connection to the node.
+fn verify_offline(
+    presentation: PathBuf,
+    snapshot: PathBuf,
+    hash: Option<String>,
+) -> anyhow::Result<()> {
+    let presentation: Presentation<ArCurve, Web3IdAttribute> = serde_json::from_reader(
+        std::fs::File::open(&presentation).context("Unable to open presentation.")?,
+    )
+    .context("Unable to parse presentation.")?;
+    let snapshot: PublicDataSnapshot = serde_json::from_reader(
+        std::fs::File::open(&snapshot).context("Unable to open snapshot.")?,
+    )
+    .context("Unable to parse snapshot.")?;
+    if let Some(hash) = hash {
+        anyhow::ensure!(
+            snapshot.hash.eq_ignore_ascii_case(&hash),
+            "Snapshot hash {} does not match the expected hash.",
+            snapshot.hash
+        );
+    }
+    let request = snapshot
+        .verify(&presentation)
+        .context("Verification failed.")?;
+    println!(
+        "Presentation verified against snapshot of block {} at {}.",
+        snapshot.data.block, snapshot.data.block_time
+    );
+    println!("{}", serde_json::to_string_pretty(&request)?);
+    Ok(())
+}